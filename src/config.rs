@@ -1,125 +1,897 @@
-use serde::Deserialize;
+use crate::{
+    error::{Error, Result},
+    message::{
+        framing::{Checksum, Framing, DEFAULT_MAXIMUM_BODY_SIZE},
+        header::HeaderFormat,
+        payload::{Pattern, Payload, MAXIMUM_PATTERN_SIZE},
+    },
+    network::{socket::SocketOptions, udp, util},
+    pacer::Pacing,
+    results::OutputFormat,
+    unit::{parse_metric, throughput_to_string},
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde_yaml;
-use std::{error::Error, net::IpAddr};
+use std::{
+    fmt::{self, Display},
+    fs::File,
+    io,
+    net::{IpAddr, SocketAddr},
+    path::PathBuf,
+    str::FromStr,
+    time::Duration,
+};
 use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
 
 #[derive(Debug, EnumIter)]
 enum Variables {
     Client,
+    SourcePort,
     Server,
+    BindAddress,
+    Interface,
     Port,
     MessageSize,
     BatchSize,
     NumberBatches,
     NumberClients,
     Role,
+    HeaderFormat,
+    Checksum,
+    MaxBodySize,
+    Mode,
+    Direction,
+    Protocol,
+    SocketPath,
+    Duration,
+    Warmup,
+    Cooldown,
+    Bandwidth,
+    MessageRate,
+    Burst,
+    Arrivals,
+    Pattern,
+    PatternFile,
+    Verify,
+    Interval,
+    OutputFormat,
+    OutputFile,
+    Parallel,
+    Workers,
+    Runtime,
+    SendMode,
+    CompareSendModes,
+    NoDelay,
+    SendBuffer,
+    ReceiveBuffer,
+    Congestion,
+    Mss,
+    Cork,
+    ReusePort,
 }
 
-#[derive(Debug, Deserialize, Clone, Copy)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
 pub enum Role {
     Server,
     Client,
 }
 
-#[derive(Debug, Deserialize)]
+/// What the client measures
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Mode {
+    /// One-way send throughput of `Benchmark` messages
+    #[default]
+    Throughput,
+    /// Round-trip time of `Ping` messages acknowledged by the server
+    Latency,
+    /// Round-trip time of `Ping` messages sent on a schedule at the paced
+    /// rate, whether earlier ones were answered or not, and timed from when
+    /// they were due
+    OpenLoop,
+}
+
+/// Which way `Benchmark` messages flow in throughput mode
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Direction {
+    /// From the client to the server
+    #[default]
+    Forward,
+    /// From the server to the client, like iperf's `-R`
+    Reverse,
+    /// Both ways at once on the same connection, each measured on its own
+    Bidirectional,
+}
+
+/// When the requests of an open-loop run are due
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Arrivals {
+    /// One every period
+    #[default]
+    Constant,
+    /// Exponentially distributed gaps, as from many independent users
+    Poisson,
+}
+
+/// Transport protocol carrying the messages
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Protocol {
+    #[default]
+    Tcp,
+    /// One message per datagram; the server reports loss and jitter
+    Udp,
+    /// Unix domain stream socket at `socket_path`, for host-local IPC
+    Unix,
+}
+
+/// How connections are driven
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Runtime {
+    /// One OS thread per connection
+    #[default]
+    Blocking,
+    /// Tasks on a tokio runtime of `workers` threads; requires the `async`
+    /// feature and TCP
+    Async,
+    /// One OS thread per connection, sending and receiving through
+    /// io_uring; requires Linux, the `uring` feature and TCP
+    Uring,
+}
+
+/// How the client hands message bodies to the kernel
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SendMode {
+    /// Header and body copied into the socket buffer by one vectored write
+    #[default]
+    Copy,
+    /// Large bodies sent with `MSG_ZEROCOPY`, read in place by the kernel;
+    /// requires Linux and TCP
+    ZeroCopy,
+}
+
+/// A host given by IP address or by name. Names are resolved once, when
+/// the configuration is read, and keep their first address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Host {
+    name: String,
+    address: IpAddr,
+}
+
+impl Host {
+    /// The host as it was given
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn address(&self) -> IpAddr {
+        self.address
+    }
+}
+
+impl FromStr for Host {
+    type Err = io::Error;
+
+    fn from_str(name: &str) -> io::Result<Host> {
+        Ok(Host {
+            name: name.to_string(),
+            address: util::resolve(name)?,
+        })
+    }
+}
+
+impl Display for Host {
+    /// Shows the address a name resolved to
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name.parse::<IpAddr>() {
+            Ok(_) => write!(f, "{}", self.address),
+            Err(_) => write!(f, "{} ({})", self.name, self.address),
+        }
+    }
+}
+
+impl Serialize for Host {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.name)
+    }
+}
+
+impl<'de> Deserialize<'de> for Host {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Host, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        name.parse()
+            .map_err(|e| serde::de::Error::custom(format!("Unable to resolve '{}': {}", name, e)))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Config {
-    client: IpAddr,
-    server: IpAddr,
+    // IP addresses or host names; the client connects from its address
+    // unless it is unspecified (0.0.0.0 or ::)
+    client: Host,
+    // Port the client connects from; the system chooses when unset
+    #[serde(default)]
+    source_port: Option<u16>,
+    server: Host,
+    // Address the server listens on; every IPv4 and IPv6 address when unset
+    #[serde(default)]
+    bind_address: Option<IpAddr>,
     port: u16,
     message_size: usize,
     batch_size: usize,
     number_batches: usize,
     number_clients: usize,
     role: Role,
+    #[serde(default)]
+    header_format: HeaderFormat,
+    // Integrity check of the messages sent; received messages are checked
+    // against whichever one they carry
+    #[serde(default)]
+    checksum: Checksum,
+    // Largest body read, in bytes; 256MiB when unset
+    #[serde(default)]
+    max_body_size: Option<usize>,
+    #[serde(default)]
+    mode: Mode,
+    // The server learns it from the client when the connection opens
+    #[serde(default)]
+    direction: Direction,
+    #[serde(default)]
+    protocol: Protocol,
+    #[serde(default)]
+    socket_path: Option<PathBuf>,
+    // Seconds of measured traffic; replaces number_batches when set
+    #[serde(default)]
+    duration: Option<f64>,
+    // Seconds sent before and after the measured traffic
+    #[serde(default)]
+    warmup: f64,
+    #[serde(default)]
+    cooldown: f64,
+    // Target rate of the sender, in bits of message bodies per second or
+    // in messages per second; as fast as it can when unset
+    #[serde(default)]
+    bandwidth: Option<f64>,
+    #[serde(default)]
+    message_rate: Option<f64>,
+    // Messages a paced sender may send back to back; 1 when unset
+    #[serde(default)]
+    burst: Option<u32>,
+    // Schedule of the requests of an open-loop run
+    #[serde(default)]
+    arrivals: Arrivals,
+    // What message bodies are filled with; zeros when unset
+    #[serde(default)]
+    pattern: Pattern,
+    // Content of the file pattern
+    #[serde(default)]
+    pattern_file: Option<PathBuf>,
+    // The receiver checks every body against the pattern
+    #[serde(default)]
+    verify: bool,
+    // Milliseconds between two interval reports; reports per batch when unset
+    #[serde(default)]
+    interval: Option<u64>,
+    // Machine-readable results, written at the end of the run
+    #[serde(default)]
+    output_format: OutputFormat,
+    // Results go to the standard output when unset
+    #[serde(default)]
+    output_file: Option<PathBuf>,
+    // Concurrent client streams, each with its own connection; 1 when unset
+    #[serde(default)]
+    parallel: Option<usize>,
+    // Server connections handled at once; number_clients when unset
+    #[serde(default)]
+    workers: Option<usize>,
+    #[serde(default)]
+    runtime: Runtime,
+    #[serde(default)]
+    send_mode: SendMode,
+    // Runs the workload once per send mode, one after the other
+    #[serde(default)]
+    compare_send_modes: bool,
+    // TCP socket options, on both sides; kernel defaults when unset
+    #[serde(default)]
+    nodelay: Option<bool>,
+    // Bytes of SO_SNDBUF and SO_RCVBUF
+    #[serde(default)]
+    send_buffer: Option<usize>,
+    #[serde(default)]
+    receive_buffer: Option<usize>,
+    // Congestion control algorithm: cubic, reno, bbr...
+    #[serde(default)]
+    congestion: Option<String>,
+    // Largest TCP segment sent, in bytes
+    #[serde(default)]
+    mss: Option<u32>,
+    #[serde(default)]
+    cork: Option<bool>,
+    #[serde(default)]
+    reuse_port: Option<bool>,
+    // Network interface both sides send and receive through, Linux only
+    #[serde(default)]
+    interface: Option<String>,
 }
 
 #[allow(unused)]
 impl Config {
     /// Reads Config file `config.yaml` from the base directory
-    pub fn read_config_file() -> Config {
-        let config_file = std::fs::File::open("config.yaml").expect("Unable to open Config file");
-        let config: Config =
-            serde_yaml::from_reader(config_file).expect("Unable to read Config file");
-        config
+    pub fn read_config_file() -> Result<Config> {
+        let config_file = File::open("config.yaml")?;
+        serde_yaml::from_reader(config_file)
+            .map_err(|e| Error::Config(format!("Unable to read config.yaml: {}", e)))
     }
 
-    ///
-    pub fn parse_attributes(
-        &mut self,
-        variable: &str,
-        value: Option<&str>,
-    ) -> Result<(), Box<dyn Error>> {
+    /// Sets a Config variable from a console argument `--variable=value`
+    pub fn parse_attributes(&mut self, variable: &str, value: Option<&str>) -> Result<()> {
         match variable {
             "client" | "Client" | "c" => {
                 self.role = Role::Client;
                 if let Some(value) = value {
-                    self.client = value
-                        .parse::<IpAddr>()
-                        .expect("Unable to retrieve client IP address from terminal")
+                    self.client = parse::<Host>(variable, value)?
                 }
             }
+            "sourceport" | "SourcePort" | "source_port" | "source-port" | "cport" => match value {
+                Some(value) => self.source_port = Some(parse::<u16>(variable, value)?),
+                None => return Err(Error::Config("Unknown Argument: source_port!".into())),
+            },
             "server" | "Server" | "s" => {
                 self.role = Role::Server;
                 if let Some(value) = value {
-                    self.server = value
-                        .parse::<IpAddr>()
-                        .expect("Unable to retrieve server IP address from terminal")
+                    self.server = parse::<Host>(variable, value)?
                 }
             }
+            "bindaddress" | "BindAddress" | "bind_address" | "bind-address" | "bind" => match value
+            {
+                Some(value) => self.bind_address = Some(parse::<IpAddr>(variable, value)?),
+                None => return Err(Error::Config("Unknown Argument: bind_address!".into())),
+            },
             "port" | "Port" | "p" => match value {
-                Some(value) => {
-                    self.port = value
-                        .parse::<u16>()
-                        .expect("Unable to retrieve port from terminal")
-                }
-                None => return Err("Unknown Argument: port!")?,
+                Some(value) => self.port = parse::<u16>(variable, value)?,
+                None => return Err(Error::Config("Unknown Argument: port!".into())),
             },
             "messagesize" | "MessageSize" | "m" => match value {
-                Some(value) => {
-                    self.message_size = value
-                        .parse::<usize>()
-                        .expect("Unable to retrieve port from terminal")
-                }
-                None => return Err("Unknown Argument: message_size!")?,
+                Some(value) => self.message_size = parse::<usize>(variable, value)?,
+                None => return Err(Error::Config("Unknown Argument: message_size!".into())),
             },
             "batchsize" | "BatchSize" | "b" => match value {
-                Some(value) => {
-                    self.batch_size = value
-                        .parse::<usize>()
-                        .expect("Unable to retrieve port from terminal")
-                }
-                None => return Err("Unknown Argument: batch_size!")?,
+                Some(value) => self.batch_size = parse::<usize>(variable, value)?,
+                None => return Err(Error::Config("Unknown Argument: batch_size!".into())),
             },
             "numberbatches" | "NumberBatches" | "number_batches" | "n" => match value {
-                Some(value) => {
-                    self.number_batches = value
-                        .parse::<usize>()
-                        .expect("Unable to retrieve port from terminal")
-                }
-                None => return Err("Unknown Argument: number_batches!")?,
+                Some(value) => self.number_batches = parse::<usize>(variable, value)?,
+                None => return Err(Error::Config("Unknown Argument: number_batches!".into())),
             },
             "numberclients" | "numberClients" | "number_clients" | "nc" => match value {
-                Some(value) => {
-                    self.number_clients = value
-                        .parse::<usize>()
-                        .expect("Unable to retrieve port from terminal")
-                }
-                None => return Err("Unknown Argument: number_clients!")?,
+                Some(value) => self.number_clients = parse::<usize>(variable, value)?,
+                None => return Err(Error::Config("Unknown Argument: number_clients!".into())),
             },
             "role" | "Role" | "r" => match value {
                 Some("client") | Some("Client") | Some("c") => self.role = Role::Client,
                 Some("server") | Some("Server") | Some("s") => self.role = Role::Server,
-                Some(_) | None => return Err("Unknown Argument: role!")?,
+                Some(_) | None => return Err(Error::Config("Unknown Argument: role!".into())),
+            },
+            "headerformat" | "HeaderFormat" | "header_format" | "header-format" => match value {
+                Some("wire") | Some("Wire") => self.header_format = HeaderFormat::Wire,
+                Some("legacy") | Some("Legacy") => self.header_format = HeaderFormat::Legacy,
+                Some(_) | None => {
+                    return Err(Error::Config("Unknown Argument: header_format!".into()))
+                }
+            },
+            "checksum" | "Checksum" => match value {
+                Some("none") | Some("None") => self.checksum = Checksum::None,
+                Some("crc32c") | Some("Crc32c") | Some("crc") => self.checksum = Checksum::Crc32c,
+                Some("xxh3") | Some("Xxh3") | Some("xxhash") => self.checksum = Checksum::Xxh3,
+                Some(_) | None => return Err(Error::Config("Unknown Argument: checksum!".into())),
+            },
+            "maxbodysize" | "MaxBodySize" | "max_body_size" | "max-body-size" => match value {
+                Some(value) => self.max_body_size = Some(parse::<usize>(variable, value)?),
+                None => return Err(Error::Config("Unknown Argument: max_body_size!".into())),
+            },
+            "mode" | "Mode" => match value {
+                Some("throughput") | Some("Throughput") => self.mode = Mode::Throughput,
+                Some("latency") | Some("Latency") => self.mode = Mode::Latency,
+                Some("openloop") | Some("OpenLoop") | Some("open_loop") | Some("open-loop") => {
+                    self.mode = Mode::OpenLoop
+                }
+                Some(_) | None => return Err(Error::Config("Unknown Argument: mode!".into())),
+            },
+            "direction" | "Direction" => match value {
+                Some("forward") | Some("Forward") => self.direction = Direction::Forward,
+                Some("reverse") | Some("Reverse") => self.direction = Direction::Reverse,
+                Some("bidirectional") | Some("Bidirectional") | Some("bidir") => {
+                    self.direction = Direction::Bidirectional
+                }
+                Some(_) | None => return Err(Error::Config("Unknown Argument: direction!".into())),
+            },
+            "protocol" | "Protocol" => match value {
+                Some("tcp") | Some("Tcp") | Some("TCP") => self.protocol = Protocol::Tcp,
+                Some("udp") | Some("Udp") | Some("UDP") => self.protocol = Protocol::Udp,
+                Some("unix") | Some("Unix") => self.protocol = Protocol::Unix,
+                Some(_) | None => return Err(Error::Config("Unknown Argument: protocol!".into())),
+            },
+            "socketpath" | "SocketPath" | "socket_path" | "socket-path" => match value {
+                // A socket path replaces server and port
+                Some(value) => {
+                    self.socket_path = Some(PathBuf::from(value));
+                    self.protocol = Protocol::Unix;
+                }
+                None => return Err(Error::Config("Unknown Argument: socket_path!".into())),
+            },
+            "duration" | "Duration" | "d" => match value {
+                Some(value) => self.duration = Some(parse::<f64>(variable, value)?),
+                None => return Err(Error::Config("Unknown Argument: duration!".into())),
+            },
+            "warmup" | "Warmup" | "warm-up" => match value {
+                Some(value) => self.warmup = parse::<f64>(variable, value)?,
+                None => return Err(Error::Config("Unknown Argument: warmup!".into())),
+            },
+            "cooldown" | "Cooldown" | "cool-down" => match value {
+                Some(value) => self.cooldown = parse::<f64>(variable, value)?,
+                None => return Err(Error::Config("Unknown Argument: cooldown!".into())),
+            },
+            "bandwidth" | "Bandwidth" | "bitrate" | "target_bandwidth" | "target-bandwidth" => {
+                match value {
+                    Some(value) => {
+                        let bandwidth = parse_metric(value).map_err(|e| {
+                            Error::Config(format!("Invalid {} '{}': {}", variable, value, e))
+                        })?;
+                        self.bandwidth = Some(bandwidth)
+                    }
+                    None => return Err(Error::Config("Unknown Argument: bandwidth!".into())),
+                }
+            }
+            "messagerate" | "MessageRate" | "message_rate" | "message-rate" | "rate" => match value
+            {
+                Some(value) => self.message_rate = Some(parse::<f64>(variable, value)?),
+                None => return Err(Error::Config("Unknown Argument: message_rate!".into())),
+            },
+            "burst" | "Burst" | "burst_size" | "burst-size" => match value {
+                Some(value) => self.burst = Some(parse::<u32>(variable, value)?),
+                None => return Err(Error::Config("Unknown Argument: burst!".into())),
+            },
+            "arrivals" | "Arrivals" => match value {
+                Some("constant") | Some("Constant") | Some("fixed") => {
+                    self.arrivals = Arrivals::Constant
+                }
+                Some("poisson") | Some("Poisson") => self.arrivals = Arrivals::Poisson,
+                Some(_) | None => return Err(Error::Config("Unknown Argument: arrivals!".into())),
+            },
+            "pattern" | "Pattern" => match value {
+                Some("zeros") | Some("Zeros") | Some("zero") => self.pattern = Pattern::Zeros,
+                Some("random") | Some("Random") => self.pattern = Pattern::Random,
+                Some("counter") | Some("Counter") => self.pattern = Pattern::Counter,
+                Some("file") | Some("File") => self.pattern = Pattern::File,
+                Some(_) | None => return Err(Error::Config("Unknown Argument: pattern!".into())),
+            },
+            "patternfile" | "PatternFile" | "pattern_file" | "pattern-file" => match value {
+                Some(value) => self.pattern_file = Some(PathBuf::from(value)),
+                None => return Err(Error::Config("Unknown Argument: pattern_file!".into())),
+            },
+            "verify" | "Verify" => match value {
+                Some(value) => self.verify = parse::<bool>(variable, value)?,
+                None => self.verify = true,
+            },
+            "interval" | "Interval" | "i" => match value {
+                Some(value) => self.interval = Some(parse::<u64>(variable, value)?),
+                None => return Err(Error::Config("Unknown Argument: interval!".into())),
+            },
+            "outputformat" | "OutputFormat" | "output_format" | "output-format" => match value {
+                Some("text") | Some("Text") => self.output_format = OutputFormat::Text,
+                Some("json") | Some("Json") | Some("JSON") => {
+                    self.output_format = OutputFormat::Json
+                }
+                Some("csv") | Some("Csv") | Some("CSV") => self.output_format = OutputFormat::Csv,
+                Some(_) | None => {
+                    return Err(Error::Config("Unknown Argument: output_format!".into()))
+                }
+            },
+            "outputfile" | "OutputFile" | "output_file" | "output-file" => match value {
+                Some(value) => self.output_file = Some(PathBuf::from(value)),
+                None => return Err(Error::Config("Unknown Argument: output_file!".into())),
+            },
+            "parallel" | "Parallel" | "P" => match value {
+                Some(value) => self.parallel = Some(parse::<usize>(variable, value)?),
+                None => return Err(Error::Config("Unknown Argument: parallel!".into())),
+            },
+            "workers" | "Workers" | "w" => match value {
+                Some(value) => self.workers = Some(parse::<usize>(variable, value)?),
+                None => return Err(Error::Config("Unknown Argument: workers!".into())),
+            },
+            "runtime" | "Runtime" => match value {
+                Some("blocking") | Some("Blocking") => self.runtime = Runtime::Blocking,
+                Some("async") | Some("Async") => self.runtime = Runtime::Async,
+                Some("uring") | Some("Uring") => self.runtime = Runtime::Uring,
+                Some(_) | None => return Err(Error::Config("Unknown Argument: runtime!".into())),
+            },
+            "sendmode" | "SendMode" | "send_mode" | "send-mode" => match value {
+                Some("copy") | Some("Copy") => self.send_mode = SendMode::Copy,
+                Some("zerocopy") | Some("ZeroCopy") | Some("zero_copy") | Some("zero-copy") => {
+                    self.send_mode = SendMode::ZeroCopy
+                }
+                Some(_) | None => return Err(Error::Config("Unknown Argument: send_mode!".into())),
+            },
+            "comparesendmodes" | "CompareSendModes" | "compare_send_modes"
+            | "compare-send-modes" => match value {
+                Some(value) => self.compare_send_modes = parse::<bool>(variable, value)?,
+                None => self.compare_send_modes = true,
+            },
+            "nodelay" | "NoDelay" | "no_delay" | "no-delay" => match value {
+                Some(value) => self.nodelay = Some(parse::<bool>(variable, value)?),
+                None => self.nodelay = Some(true),
+            },
+            "sendbuffer" | "SendBuffer" | "send_buffer" | "send-buffer" | "sndbuf" => match value {
+                Some(value) => self.send_buffer = Some(parse::<usize>(variable, value)?),
+                None => return Err(Error::Config("Unknown Argument: send_buffer!".into())),
+            },
+            "receivebuffer" | "ReceiveBuffer" | "receive_buffer" | "receive-buffer" | "rcvbuf" => {
+                match value {
+                    Some(value) => self.receive_buffer = Some(parse::<usize>(variable, value)?),
+                    None => return Err(Error::Config("Unknown Argument: receive_buffer!".into())),
+                }
+            }
+            "congestion" | "Congestion" => match value {
+                Some(value) => self.congestion = Some(value.to_string()),
+                None => return Err(Error::Config("Unknown Argument: congestion!".into())),
+            },
+            "mss" | "Mss" | "MSS" => match value {
+                Some(value) => self.mss = Some(parse::<u32>(variable, value)?),
+                None => return Err(Error::Config("Unknown Argument: mss!".into())),
+            },
+            "cork" | "Cork" => match value {
+                Some(value) => self.cork = Some(parse::<bool>(variable, value)?),
+                None => self.cork = Some(true),
+            },
+            "reuseport" | "ReusePort" | "reuse_port" | "reuse-port" => match value {
+                Some(value) => self.reuse_port = Some(parse::<bool>(variable, value)?),
+                None => self.reuse_port = Some(true),
+            },
+            "interface" | "Interface" | "bind_device" | "bind-device" | "dev" => match value {
+                Some(value) => self.interface = Some(value.to_string()),
+                None => return Err(Error::Config("Unknown Argument: interface!".into())),
             },
 
-            _ => return Err("Unknown Argument!")?,
+            _ => return Err(Error::Config("Unknown Argument!".into())),
+        }
+        Ok(())
+    }
+
+    /// Checks that the variables are consistent with each other
+    pub fn validate(&self) -> Result<()> {
+        if self.protocol == Protocol::Udp {
+            // Only the client sends messages of `message_size`
+            if matches!(self.role, Role::Client) && self.message_size > udp::MAXIMUM_BODY_SIZE {
+                return Err(Error::Config(format!(
+                    "message_size {} does not fit in a UDP datagram (maximum {} bytes)",
+                    self.message_size,
+                    udp::MAXIMUM_BODY_SIZE
+                )));
+            }
+            if self.mode != Mode::Throughput {
+                return Err(Error::Config(
+                    "Latency and open-loop modes are only supported over TCP".into(),
+                ));
+            }
+            // The server accounts for every datagram it receives
+            if self.warmup != 0.0 || self.cooldown != 0.0 {
+                return Err(Error::Config(
+                    "warmup and cooldown are not supported over UDP".into(),
+                ));
+            }
+        }
+        if self.direction != Direction::Forward {
+            if self.mode != Mode::Throughput {
+                return Err(Error::Config(
+                    "Reverse and bidirectional transfers require throughput mode".into(),
+                ));
+            }
+            if self.protocol == Protocol::Udp || self.runtime == Runtime::Async {
+                return Err(Error::Config(
+                    "Reverse and bidirectional transfers require TCP or Unix sockets and \
+                    the blocking or uring runtime"
+                        .into(),
+                ));
+            }
+        }
+        match self.duration {
+            Some(duration) if !(duration.is_finite() && duration > 0.0) => {
+                return Err(Error::Config(format!(
+                    "duration must be a positive number of seconds, got {}",
+                    duration
+                )));
+            }
+            None if self.warmup != 0.0 || self.cooldown != 0.0 => {
+                return Err(Error::Config(
+                    "warmup and cooldown require a duration".into(),
+                ));
+            }
+            _ => (),
+        }
+        for (name, value) in [("warmup", self.warmup), ("cooldown", self.cooldown)] {
+            if !(value.is_finite() && value >= 0.0) {
+                return Err(Error::Config(format!(
+                    "{} must be a non-negative number of seconds, got {}",
+                    name, value
+                )));
+            }
+        }
+        // The phases of the run end at their sum
+        let total = self.warmup + self.duration.unwrap_or_default() + self.cooldown;
+        Duration::try_from_secs_f64(total).map_err(|_| {
+            Error::Config(format!(
+                "duration, warmup and cooldown add up to {} seconds, too long a run",
+                total
+            ))
+        })?;
+        for (name, value) in [
+            ("bandwidth", self.bandwidth),
+            ("message_rate", self.message_rate),
+        ] {
+            if value.is_some_and(|value| !(value.is_finite() && value > 0.0)) {
+                return Err(Error::Config(format!(
+                    "{} must be a positive rate, got {}",
+                    name,
+                    value.unwrap_or_default()
+                )));
+            }
+        }
+        if self.bandwidth.is_some() && self.message_rate.is_some() {
+            return Err(Error::Config(
+                "bandwidth and message_rate cannot be combined".into(),
+            ));
+        }
+        if self.bandwidth.is_some() && self.message_size == 0 {
+            return Err(Error::Config(
+                "bandwidth requires a message_size, use message_rate instead".into(),
+            ));
+        }
+        if self
+            .pacing()
+            .is_some_and(|pacing| pacing.period().is_none())
+        {
+            return Err(Error::Config(
+                "bandwidth or message_rate is too low for messages to be scheduled".into(),
+            ));
+        }
+        match self.burst {
+            Some(0) => return Err(Error::Config("burst must be at least 1 message".into())),
+            Some(_) if self.pacing().is_none() => {
+                return Err(Error::Config(
+                    "burst requires a bandwidth or a message_rate".into(),
+                ));
+            }
+            _ => (),
+        }
+        if self.mode == Mode::OpenLoop {
+            if self.pacing().is_none() {
+                return Err(Error::Config(
+                    "Open-loop mode requires a message_rate or a bandwidth to send requests at"
+                        .into(),
+                ));
+            }
+            // Requests are due on their schedule, never ahead of it
+            if self.burst.is_some() {
+                return Err(Error::Config(
+                    "burst does not apply to open-loop mode".into(),
+                ));
+            }
+            if self.runtime == Runtime::Async {
+                return Err(Error::Config(
+                    "Open-loop mode requires the blocking or uring runtime".into(),
+                ));
+            }
+        } else if self.arrivals != Arrivals::Constant {
+            return Err(Error::Config("arrivals require open-loop mode".into()));
+        }
+        match (self.pattern, &self.pattern_file) {
+            (Pattern::File, None) => {
+                return Err(Error::Config(
+                    "The file pattern requires a pattern_file".into(),
+                ));
+            }
+            (Pattern::File, Some(_)) => {
+                self.payload()?;
+            }
+            (_, Some(_)) => {
+                return Err(Error::Config(
+                    "pattern_file requires the file pattern".into(),
+                ));
+            }
+            _ => (),
+        }
+        // The pattern is negotiated with the server when the test starts
+        if self.verify && (self.protocol == Protocol::Udp || self.runtime == Runtime::Async) {
+            return Err(Error::Config(
+                "Payload verification requires TCP or Unix sockets and the blocking or \
+                uring runtime"
+                    .into(),
+            ));
+        }
+        if self.checksum != Checksum::None {
+            // Only the wire header has room for the checksum
+            if self.header_format != HeaderFormat::Wire {
+                return Err(Error::Config(
+                    "checksum requires the wire header format".into(),
+                ));
+            }
+            if self.protocol == Protocol::Udp {
+                return Err(Error::Config("checksum is not supported over UDP".into()));
+            }
+        }
+        match self.max_body_size {
+            Some(0) => {
+                return Err(Error::Config(
+                    "max_body_size must be a positive number of bytes".into(),
+                ));
+            }
+            // The bodies a client reads back are as large as the ones it sends
+            Some(maximum)
+                if matches!(self.role, Role::Client)
+                    && self.direction != Direction::Forward
+                    && self.message_size > maximum =>
+            {
+                return Err(Error::Config(format!(
+                    "message_size of {} bytes exceeds max_body_size of {} bytes",
+                    self.message_size, maximum
+                )));
+            }
+            _ => (),
+        }
+        if self.interval == Some(0) {
+            return Err(Error::Config(
+                "interval must be a positive number of milliseconds".into(),
+            ));
+        }
+        if self.parallel == Some(0) {
+            return Err(Error::Config(
+                "parallel must be a positive number of streams".into(),
+            ));
+        }
+        if self.workers == Some(0) {
+            return Err(Error::Config(
+                "workers must be a positive number of threads".into(),
+            ));
+        }
+        if self.runtime == Runtime::Async {
+            if cfg!(not(feature = "async")) {
+                return Err(Error::Config(
+                    "Async runtime requires building with the async feature".into(),
+                ));
+            }
+            if self.protocol != Protocol::Tcp {
+                return Err(Error::Config(
+                    "Async runtime is only supported over TCP".into(),
+                ));
+            }
+        }
+        if self.runtime == Runtime::Uring {
+            if cfg!(not(all(target_os = "linux", feature = "uring"))) {
+                return Err(Error::Config(
+                    "Uring runtime requires Linux and the uring feature".into(),
+                ));
+            }
+            if self.protocol != Protocol::Tcp {
+                return Err(Error::Config(
+                    "Uring runtime is only supported over TCP".into(),
+                ));
+            }
+        }
+        if self.send_mode == SendMode::ZeroCopy || self.compare_send_modes {
+            if cfg!(not(target_os = "linux")) {
+                return Err(Error::Config(
+                    "Zero-copy sending is only supported on Linux".into(),
+                ));
+            }
+            if self.protocol != Protocol::Tcp || self.runtime != Runtime::Blocking {
+                return Err(Error::Config(
+                    "Zero-copy sending requires TCP and the blocking runtime".into(),
+                ));
+            }
+            if self.mode != Mode::Throughput {
+                return Err(Error::Config(
+                    "Zero-copy sending is only supported in throughput mode".into(),
+                ));
+            }
+            if self.direction != Direction::Forward {
+                return Err(Error::Config(
+                    "Zero-copy sending is only supported in the forward direction".into(),
+                ));
+            }
+            // The kernel reads the body in place until the sends complete
+            if matches!(self.pattern, Pattern::Random | Pattern::Counter) {
+                return Err(Error::Config(
+                    "Zero-copy sending requires the zeros or file pattern, the others \
+                    change the body of every message"
+                        .into(),
+                ));
+            }
+        }
+        let socket_options = self.socket_options();
+        if !socket_options.is_default() && self.protocol != Protocol::Tcp {
+            return Err(Error::Config(
+                "Socket options are only supported over TCP".into(),
+            ));
+        }
+        for (name, value) in [
+            ("send_buffer", self.send_buffer),
+            ("receive_buffer", self.receive_buffer),
+            ("mss", self.mss.map(|mss| mss as usize)),
+        ] {
+            if value == Some(0) {
+                return Err(Error::Config(format!(
+                    "{} must be a positive number of bytes",
+                    name
+                )));
+            }
+        }
+        if self.congestion.as_deref() == Some("") {
+            return Err(Error::Config("congestion must name an algorithm".into()));
+        }
+        if (self.congestion.is_some() || self.cork.is_some()) && cfg!(not(target_os = "linux")) {
+            return Err(Error::Config(
+                "congestion and cork are only supported on Linux".into(),
+            ));
+        }
+        if (self.mss.is_some() || self.reuse_port.is_some()) && cfg!(not(unix)) {
+            return Err(Error::Config(
+                "mss and reuse_port are not supported on this platform".into(),
+            ));
+        }
+        if self.interface.as_deref() == Some("") {
+            return Err(Error::Config(
+                "interface must name a network interface".into(),
+            ));
+        }
+        if self.interface.is_some() && cfg!(not(target_os = "linux")) {
+            return Err(Error::Config("interface is only supported on Linux".into()));
+        }
+        if matches!(self.role, Role::Client) && self.protocol != Protocol::Unix {
+            let (client, server) = (self.client(), self.server());
+            if !client.is_unspecified() && client.is_ipv4() != server.is_ipv4() {
+                return Err(Error::Config(format!(
+                    "client {} and server {} are not the same IP version",
+                    self.client, self.server
+                )));
+            }
+            // Each stream needs a source port of its own
+            if self.source_port.is_some() && self.parallel() > 1 {
+                return Err(Error::Config(
+                    "source_port is not supported with parallel streams".into(),
+                ));
+            }
+        }
+        if self.output_file.is_some() && self.output_format == OutputFormat::Text {
+            return Err(Error::Config(
+                "output_file requires an output_format of json or csv".into(),
+            ));
+        }
+        if self.protocol == Protocol::Unix {
+            if cfg!(not(unix)) {
+                return Err(Error::Config(
+                    "Unix sockets are not supported on this platform".into(),
+                ));
+            }
+            if self.socket_path.is_none() {
+                return Err(Error::Config("Unix protocol requires a socket_path".into()));
+            }
         }
         Ok(())
     }
 
     pub fn client(&self) -> IpAddr {
-        self.client
+        self.client.address()
+    }
+
+    /// Address the client connects from, unless the system should choose
+    /// both the address and the port
+    pub fn source_address(&self) -> Option<SocketAddr> {
+        let client = self.client();
+        match (client.is_unspecified(), self.source_port) {
+            (true, None) => None,
+            // Only the port is fixed, over the IP version of the server
+            (true, Some(port)) => Some(SocketAddr::new(util::unspecified(self.server()), port)),
+            (false, port) => Some(SocketAddr::new(client, port.unwrap_or(0))),
+        }
     }
 
     pub fn role(&self) -> Role {
@@ -127,7 +899,12 @@ impl Config {
     }
 
     pub fn server(&self) -> IpAddr {
-        self.server
+        self.server.address()
+    }
+
+    /// Address the server listens on
+    pub fn bind_address(&self) -> IpAddr {
+        self.bind_address.unwrap_or(util::ANY_ADDRESS)
     }
 
     pub fn port(&self) -> u16 {
@@ -150,6 +927,158 @@ impl Config {
         self.number_clients
     }
 
+    /// Connections the server accepts before stopping; 0 clients means
+    /// the server runs until it is killed
+    pub fn clients_limit(&self) -> Option<usize> {
+        match self.number_clients {
+            0 => None,
+            number_clients => Some(number_clients),
+        }
+    }
+
+    pub fn header_format(&self) -> HeaderFormat {
+        self.header_format
+    }
+
+    /// How connections checksum the messages they send and bound the
+    /// bodies they read
+    pub fn framing(&self) -> Framing {
+        Framing {
+            checksum: self.checksum,
+            maximum_body_size: self.max_body_size.unwrap_or(DEFAULT_MAXIMUM_BODY_SIZE),
+        }
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    pub fn arrivals(&self) -> Arrivals {
+        self.arrivals
+    }
+
+    pub fn protocol(&self) -> Protocol {
+        self.protocol
+    }
+
+    pub fn socket_path(&self) -> Option<&PathBuf> {
+        self.socket_path.as_ref()
+    }
+
+    /// Measured time of a duration-based run, if any
+    pub fn duration(&self) -> Option<Duration> {
+        self.duration.map(seconds)
+    }
+
+    pub fn warmup(&self) -> Duration {
+        seconds(self.warmup)
+    }
+
+    pub fn cooldown(&self) -> Duration {
+        seconds(self.cooldown)
+    }
+
+    /// Target rate of the sender, if it is paced
+    pub fn pacing(&self) -> Option<Pacing> {
+        let messages_per_second = match (self.bandwidth, self.message_rate) {
+            (_, Some(message_rate)) => message_rate,
+            (Some(bandwidth), None) => bandwidth / (self.message_size as f64 * 8.0),
+            (None, None) => return None,
+        };
+        Some(Pacing {
+            messages_per_second,
+            burst: self.burst.unwrap_or(1),
+        })
+    }
+
+    pub fn pattern(&self) -> Pattern {
+        self.pattern
+    }
+
+    /// What message bodies are filled with, with the content of the
+    /// pattern file if any
+    pub fn payload(&self) -> Result<Payload> {
+        let content = match (self.pattern, &self.pattern_file) {
+            (Pattern::File, Some(path)) => std::fs::read(path).map_err(|e| {
+                Error::Config(format!(
+                    "Unable to read pattern_file {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?,
+            _ => Vec::new(),
+        };
+        if self.pattern == Pattern::File
+            && (content.is_empty() || content.len() > MAXIMUM_PATTERN_SIZE)
+        {
+            return Err(Error::Config(format!(
+                "pattern_file must hold 1 to {} bytes, got {}",
+                MAXIMUM_PATTERN_SIZE,
+                content.len()
+            )));
+        }
+        Ok(Payload::new(self.pattern, content))
+    }
+
+    /// Whether the receiver checks bodies against their pattern
+    pub fn verify(&self) -> bool {
+        self.verify
+    }
+
+    /// Time between two interval reports, if any
+    pub fn interval(&self) -> Option<Duration> {
+        self.interval.map(Duration::from_millis)
+    }
+
+    pub fn output_format(&self) -> OutputFormat {
+        self.output_format
+    }
+
+    pub fn output_file(&self) -> Option<&PathBuf> {
+        self.output_file.as_ref()
+    }
+
+    /// Number of concurrent client streams
+    pub fn parallel(&self) -> usize {
+        self.parallel.unwrap_or(1)
+    }
+
+    /// Server worker threads, when set explicitly
+    pub fn workers(&self) -> Option<usize> {
+        self.workers
+    }
+
+    pub fn runtime(&self) -> Runtime {
+        self.runtime
+    }
+
+    pub fn send_mode(&self) -> SendMode {
+        self.send_mode
+    }
+
+    /// Whether to run the workload in every send mode, to compare them
+    pub fn compare_send_modes(&self) -> bool {
+        self.compare_send_modes
+    }
+
+    /// TCP socket options, applied on both sides
+    pub fn socket_options(&self) -> SocketOptions {
+        SocketOptions {
+            nodelay: self.nodelay,
+            send_buffer: self.send_buffer,
+            receive_buffer: self.receive_buffer,
+            congestion: self.congestion.clone(),
+            mss: self.mss,
+            cork: self.cork,
+            reuse_port: self.reuse_port,
+            interface: self.interface.clone(),
+        }
+    }
+
     pub fn usage() {
         println!("usage:");
         println!("======");
@@ -168,29 +1097,554 @@ impl Config {
         println!("=======");
         println!("server: {}", self.server);
         println!("client: {}", self.client);
+        if let Some(source_port) = self.source_port {
+            println!("source_port: {}", source_port);
+        }
+        if let Some(bind_address) = self.bind_address {
+            println!("bind_address: {}", bind_address);
+        }
         println!("message_size: {}", self.message_size);
         println!("batch_size: {}", self.batch_size);
         println!("number_batches: {}", self.number_batches);
         println!("number_clients: {}", self.number_clients);
         println!("role: {:?}", self.role);
+        println!("header_format: {:?}", self.header_format);
+        if self.checksum != Checksum::None {
+            println!("checksum: {:?}", self.checksum);
+        }
+        if let Some(max_body_size) = self.max_body_size {
+            println!("max_body_size: {}", max_body_size);
+        }
+        println!("mode: {:?}", self.mode);
+        println!("direction: {:?}", self.direction);
+        println!("protocol: {:?}", self.protocol);
+        if let Some(socket_path) = &self.socket_path {
+            println!("socket_path: {}", socket_path.display());
+        }
+        if let Some(duration) = self.duration {
+            println!("duration: {}s", duration);
+            println!("warmup: {}s", self.warmup);
+            println!("cooldown: {}s", self.cooldown);
+        }
+        if let Some(bandwidth) = self.bandwidth {
+            println!("bandwidth: {}", throughput_to_string(bandwidth));
+        }
+        if let Some(message_rate) = self.message_rate {
+            println!("message_rate: {}/s", message_rate);
+        }
+        if let Some(burst) = self.burst {
+            println!("burst: {}", burst);
+        }
+        if self.mode == Mode::OpenLoop {
+            println!("arrivals: {:?}", self.arrivals);
+        }
+        if self.pattern != Pattern::Zeros {
+            println!("pattern: {:?}", self.pattern);
+        }
+        if let Some(pattern_file) = &self.pattern_file {
+            println!("pattern_file: {}", pattern_file.display());
+        }
+        if self.verify {
+            println!("verify: true");
+        }
+        if let Some(interval) = self.interval {
+            println!("interval: {}ms", interval);
+        }
+        if let Some(parallel) = self.parallel {
+            println!("parallel: {}", parallel);
+        }
+        if let Some(workers) = self.workers {
+            println!("workers: {}", workers);
+        }
+        println!("runtime: {:?}", self.runtime);
+        println!("send_mode: {:?}", self.send_mode);
+        if self.compare_send_modes {
+            println!("compare_send_modes: true");
+        }
+        if let Some(nodelay) = self.nodelay {
+            println!("nodelay: {}", nodelay);
+        }
+        if let Some(send_buffer) = self.send_buffer {
+            println!("send_buffer: {}", send_buffer);
+        }
+        if let Some(receive_buffer) = self.receive_buffer {
+            println!("receive_buffer: {}", receive_buffer);
+        }
+        if let Some(congestion) = &self.congestion {
+            println!("congestion: {}", congestion);
+        }
+        if let Some(mss) = self.mss {
+            println!("mss: {}", mss);
+        }
+        if let Some(cork) = self.cork {
+            println!("cork: {}", cork);
+        }
+        if let Some(reuse_port) = self.reuse_port {
+            println!("reuse_port: {}", reuse_port);
+        }
+        if let Some(interface) = &self.interface {
+            println!("interface: {}", interface);
+        }
+        println!("output_format: {:?}", self.output_format);
+        if let Some(output_file) = &self.output_file {
+            println!("output_file: {}", output_file.display());
+        }
         println!("=====================");
     }
 }
 
+/// A number of seconds that `validate` checked fits in a `Duration`, the
+/// longest one otherwise
+pub(crate) fn seconds(value: f64) -> Duration {
+    Duration::try_from_secs_f64(value).unwrap_or(Duration::MAX)
+}
+
+/// Parses the value of a console argument, naming the variable on failure
+fn parse<T>(variable: &str, value: &str) -> Result<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    value
+        .parse::<T>()
+        .map_err(|e| Error::Config(format!("Invalid {} '{}': {}", variable, value, e)))
+}
+
 #[cfg(test)]
 mod test {
 
-    use super::Config;
+    use super::{
+        Arrivals, Checksum, Config, Direction, Framing, Mode, Pattern, Protocol, Runtime, SendMode,
+    };
+    use crate::error::Error;
+    use crate::network::udp;
+    use crate::results::OutputFormat;
+    use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+    use std::time::Duration;
+
+    const CONFIG: &str = "
+server: \"127.0.0.1\"
+client: \"127.0.0.1\"
+port: 7777
+message_size: 100
+batch_size: 10
+number_batches: 1
+number_clients: 1
+role: \"Client\"
+";
 
     #[test]
     #[ignore = "Config might Change"]
     fn test_read_config_file() {
-        let config = Config::read_config_file();
+        let config = Config::read_config_file().unwrap();
         assert_eq!(config.server().to_string(), "127.0.0.1".to_string());
-        assert_eq!(config.client().to_string(), "127.0.0.1".to_string());
+        assert_eq!(config.client().to_string(), "0.0.0.0".to_string());
         assert_eq!(config.port(), 7777);
         assert_eq!(config.message_size(), 10000);
         assert_eq!(config.batch_size(), 10000);
         assert_eq!(config.number_batches(), 1);
     }
+
+    #[test]
+    fn test_validate_udp_message_size() {
+        let mut config: Config = serde_yaml::from_str(CONFIG).unwrap();
+        assert_eq!(config.protocol(), Protocol::Tcp);
+        config.parse_attributes("protocol", Some("udp")).unwrap();
+        assert!(config.validate().is_ok());
+
+        let too_large = (udp::MAXIMUM_BODY_SIZE + 1).to_string();
+        config.parse_attributes("m", Some(&too_large)).unwrap();
+        assert!(config.validate().is_err());
+
+        config.parse_attributes("protocol", Some("tcp")).unwrap();
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_duration() {
+        let mut config: Config = serde_yaml::from_str(CONFIG).unwrap();
+        assert_eq!(config.duration(), None);
+        config.parse_attributes("warmup", Some("1")).unwrap();
+        assert!(config.validate().is_err());
+
+        config.parse_attributes("duration", Some("2.5")).unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.duration(), Some(Duration::from_millis(2500)));
+        assert_eq!(config.warmup(), Duration::from_secs(1));
+
+        config.parse_attributes("duration", Some("0")).unwrap();
+        assert!(config.validate().is_err());
+        assert!(config.parse_attributes("duration", Some("ten")).is_err());
+
+        // Longer than a Duration holds
+        config.parse_attributes("duration", Some("1e30")).unwrap();
+        assert!(config.validate().is_err());
+        config.parse_attributes("duration", Some("1e19")).unwrap();
+        config.parse_attributes("cooldown", Some("1e19")).unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_unix_socket_path() {
+        let mut config: Config = serde_yaml::from_str(CONFIG).unwrap();
+        config.parse_attributes("protocol", Some("unix")).unwrap();
+        assert!(config.validate().is_err());
+
+        config
+            .parse_attributes("socket-path", Some("/tmp/benchmark.sock"))
+            .unwrap();
+        assert_eq!(config.protocol(), Protocol::Unix);
+        assert_eq!(config.validate().is_ok(), cfg!(unix));
+    }
+
+    #[test]
+    fn test_validate_output() {
+        let mut config: Config = serde_yaml::from_str(CONFIG).unwrap();
+        assert_eq!(config.output_format(), OutputFormat::Text);
+        config
+            .parse_attributes("output-file", Some("results.json"))
+            .unwrap();
+        assert!(config.validate().is_err());
+
+        config
+            .parse_attributes("output-format", Some("json"))
+            .unwrap();
+        assert!(config.validate().is_ok());
+        assert!(config
+            .parse_attributes("output-format", Some("xml"))
+            .is_err());
+    }
+
+    #[test]
+    fn test_validate_parallel() {
+        let mut config: Config = serde_yaml::from_str(CONFIG).unwrap();
+        assert_eq!(config.parallel(), 1);
+        config.parse_attributes("parallel", Some("4")).unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.parallel(), 4);
+
+        config.parse_attributes("P", Some("0")).unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_clients_limit() {
+        let mut config: Config = serde_yaml::from_str(CONFIG).unwrap();
+        assert_eq!(config.clients_limit(), Some(1));
+        config.parse_attributes("nc", Some("0")).unwrap();
+        assert_eq!(config.clients_limit(), None);
+
+        config.parse_attributes("workers", Some("0")).unwrap();
+        assert!(config.validate().is_err());
+        config.parse_attributes("workers", Some("8")).unwrap();
+        assert_eq!(config.workers(), Some(8));
+    }
+
+    #[test]
+    fn test_validate_runtime() {
+        let mut config: Config = serde_yaml::from_str(CONFIG).unwrap();
+        assert_eq!(config.runtime(), Runtime::Blocking);
+        config.parse_attributes("runtime", Some("async")).unwrap();
+        assert_eq!(config.validate().is_ok(), cfg!(feature = "async"));
+
+        config.parse_attributes("protocol", Some("udp")).unwrap();
+        assert!(config.validate().is_err());
+
+        config.parse_attributes("protocol", Some("tcp")).unwrap();
+        config.parse_attributes("runtime", Some("uring")).unwrap();
+        assert_eq!(config.runtime(), Runtime::Uring);
+        assert_eq!(
+            config.validate().is_ok(),
+            cfg!(all(target_os = "linux", feature = "uring"))
+        );
+    }
+
+    #[test]
+    fn test_validate_send_mode() {
+        let mut config: Config = serde_yaml::from_str(CONFIG).unwrap();
+        assert_eq!(config.send_mode(), SendMode::Copy);
+        assert!(!config.compare_send_modes());
+        config
+            .parse_attributes("send_mode", Some("zerocopy"))
+            .unwrap();
+        assert_eq!(config.send_mode(), SendMode::ZeroCopy);
+        assert_eq!(config.validate().is_ok(), cfg!(target_os = "linux"));
+
+        config.parse_attributes("mode", Some("latency")).unwrap();
+        assert!(config.validate().is_err());
+
+        config.parse_attributes("mode", Some("throughput")).unwrap();
+        config.parse_attributes("pattern", Some("random")).unwrap();
+        assert!(config.validate().is_err());
+
+        config.parse_attributes("pattern", Some("zeros")).unwrap();
+        config.parse_attributes("send_mode", Some("copy")).unwrap();
+        config.parse_attributes("compare_send_modes", None).unwrap();
+        assert!(config.compare_send_modes());
+        config.parse_attributes("protocol", Some("udp")).unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_direction() {
+        let mut config: Config = serde_yaml::from_str(CONFIG).unwrap();
+        assert_eq!(config.direction(), Direction::Forward);
+        config.parse_attributes("direction", Some("bidir")).unwrap();
+        assert_eq!(config.direction(), Direction::Bidirectional);
+        assert!(config.validate().is_ok());
+
+        config.parse_attributes("mode", Some("latency")).unwrap();
+        assert!(config.validate().is_err());
+        config.parse_attributes("mode", Some("throughput")).unwrap();
+        config.parse_attributes("protocol", Some("udp")).unwrap();
+        assert!(config.validate().is_err());
+        config.parse_attributes("protocol", Some("tcp")).unwrap();
+        config.parse_attributes("runtime", Some("async")).unwrap();
+        assert!(config.validate().is_err());
+        config
+            .parse_attributes("runtime", Some("blocking"))
+            .unwrap();
+        config
+            .parse_attributes("send_mode", Some("zerocopy"))
+            .unwrap();
+        assert!(config.validate().is_err());
+        assert!(config.parse_attributes("direction", Some("up")).is_err());
+    }
+
+    #[test]
+    fn test_validate_pacing() {
+        let mut config: Config = serde_yaml::from_str(CONFIG).unwrap();
+        assert_eq!(config.pacing(), None);
+        config.parse_attributes("burst", Some("8")).unwrap();
+        assert!(config.validate().is_err());
+
+        // 100 byte messages: 800 bits each
+        config.parse_attributes("bandwidth", Some("8M")).unwrap();
+        assert!(config.validate().is_ok());
+        let pacing = config.pacing().unwrap();
+        assert_eq!(pacing.messages_per_second, 10_000.0);
+        assert_eq!(pacing.burst, 8);
+
+        config
+            .parse_attributes("message_rate", Some("500"))
+            .unwrap();
+        assert!(config.validate().is_err());
+        config.bandwidth = None;
+        assert!(config.validate().is_ok());
+        assert_eq!(config.pacing().unwrap().messages_per_second, 500.0);
+
+        config.parse_attributes("rate", Some("0")).unwrap();
+        assert!(config.validate().is_err());
+        // Too slow for the time between two messages to be represented
+        config.parse_attributes("rate", Some("1e-300")).unwrap();
+        assert!(config.validate().is_err());
+        config.parse_attributes("burst", Some("0")).unwrap();
+        assert!(config.validate().is_err());
+        assert!(config.parse_attributes("bandwidth", Some("fast")).is_err());
+        assert!(config.parse_attributes("bandwidth", Some("1.5G")).is_ok());
+        assert_eq!(config.bandwidth, Some(1.5e9));
+    }
+
+    #[test]
+    fn test_validate_open_loop() {
+        let mut config: Config = serde_yaml::from_str(CONFIG).unwrap();
+        config.parse_attributes("mode", Some("open_loop")).unwrap();
+        assert!(matches!(config.mode(), Mode::OpenLoop));
+        // Requests need a schedule
+        assert!(config.validate().is_err());
+        config.parse_attributes("rate", Some("1000")).unwrap();
+        assert!(config.validate().is_ok());
+
+        config
+            .parse_attributes("arrivals", Some("poisson"))
+            .unwrap();
+        assert_eq!(config.arrivals(), Arrivals::Poisson);
+        assert!(config.validate().is_ok());
+        config.parse_attributes("burst", Some("4")).unwrap();
+        assert!(config.validate().is_err());
+        config.burst = None;
+        assert!(config.parse_attributes("arrivals", Some("bursty")).is_err());
+
+        // Arrivals only schedule open-loop requests
+        config.parse_attributes("mode", Some("latency")).unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_pattern() {
+        let mut config: Config = serde_yaml::from_str(CONFIG).unwrap();
+        assert_eq!(config.payload().unwrap().pattern, Pattern::Zeros);
+        config.parse_attributes("pattern", Some("counter")).unwrap();
+        config.parse_attributes("verify", None).unwrap();
+        assert!(config.validate().is_ok());
+        assert!(config.parse_attributes("pattern", Some("noise")).is_err());
+
+        config.parse_attributes("pattern", Some("file")).unwrap();
+        assert!(config.validate().is_err());
+        let path = std::env::temp_dir().join(format!("pattern-{}", std::process::id()));
+        std::fs::write(&path, b"0123456789").unwrap();
+        config
+            .parse_attributes("pattern_file", path.to_str())
+            .unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.payload().unwrap().content, b"0123456789");
+        std::fs::write(&path, b"").unwrap();
+        assert!(config.validate().is_err());
+        std::fs::remove_file(&path).unwrap();
+
+        config.parse_attributes("pattern", Some("random")).unwrap();
+        assert!(config.validate().is_err());
+        config.pattern_file = None;
+        config.parse_attributes("protocol", Some("udp")).unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_framing() {
+        let mut config: Config = serde_yaml::from_str(CONFIG).unwrap();
+        assert_eq!(config.framing(), Framing::default());
+        config.parse_attributes("checksum", Some("xxh3")).unwrap();
+        config
+            .parse_attributes("max_body_size", Some("1024"))
+            .unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(
+            config.framing(),
+            Framing {
+                checksum: Checksum::Xxh3,
+                maximum_body_size: 1024
+            }
+        );
+        assert!(config.parse_attributes("checksum", Some("md5")).is_err());
+
+        config
+            .parse_attributes("header_format", Some("legacy"))
+            .unwrap();
+        assert!(config.validate().is_err());
+        config
+            .parse_attributes("header_format", Some("wire"))
+            .unwrap();
+        config.parse_attributes("protocol", Some("udp")).unwrap();
+        assert!(config.validate().is_err());
+        config.parse_attributes("protocol", Some("tcp")).unwrap();
+
+        // The client reads back bodies as large as the ones it sends
+        config
+            .parse_attributes("direction", Some("reverse"))
+            .unwrap();
+        config
+            .parse_attributes("max_body_size", Some("99"))
+            .unwrap();
+        assert!(config.validate().is_err());
+        config.parse_attributes("max_body_size", Some("0")).unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_socket_options() {
+        let mut config: Config = serde_yaml::from_str(CONFIG).unwrap();
+        assert!(config.socket_options().is_default());
+        config.parse_attributes("nodelay", None).unwrap();
+        config.parse_attributes("sndbuf", Some("262144")).unwrap();
+        config.parse_attributes("mss", Some("1400")).unwrap();
+        let options = config.socket_options();
+        assert_eq!(options.nodelay, Some(true));
+        assert_eq!(options.send_buffer, Some(262144));
+        assert_eq!(options.mss, Some(1400));
+        assert_eq!(config.validate().is_ok(), cfg!(unix));
+
+        config.parse_attributes("congestion", Some("bbr")).unwrap();
+        assert_eq!(config.validate().is_ok(), cfg!(target_os = "linux"));
+
+        config
+            .parse_attributes("receive_buffer", Some("0"))
+            .unwrap();
+        assert!(config.validate().is_err());
+        config
+            .parse_attributes("receive_buffer", Some("1024"))
+            .unwrap();
+        config.parse_attributes("protocol", Some("udp")).unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_source_address() {
+        let mut config: Config = serde_yaml::from_str(CONFIG).unwrap();
+        let client = config.client();
+        assert_eq!(config.source_address(), Some(SocketAddr::new(client, 0)));
+        config
+            .parse_attributes("source_port", Some("40000"))
+            .unwrap();
+        assert_eq!(
+            config.source_address(),
+            Some(SocketAddr::new(client, 40000))
+        );
+        assert!(config.validate().is_ok());
+        config.parse_attributes("parallel", Some("2")).unwrap();
+        assert!(config.validate().is_err());
+        config.parse_attributes("parallel", Some("1")).unwrap();
+
+        // An unspecified client only fixes the port
+        config.parse_attributes("client", Some("::")).unwrap();
+        config.parse_attributes("server", Some("::1")).unwrap();
+        config.parse_attributes("role", Some("client")).unwrap();
+        let unspecified = IpAddr::V6(Ipv6Addr::UNSPECIFIED);
+        assert_eq!(
+            config.source_address(),
+            Some(SocketAddr::new(unspecified, 40000))
+        );
+        assert!(config.validate().is_ok());
+
+        // The source must be of the IP version of the server
+        config
+            .parse_attributes("client", Some("127.0.0.1"))
+            .unwrap();
+        assert!(config.validate().is_err());
+
+        config.parse_attributes("client", Some("0.0.0.0")).unwrap();
+        config.source_port = None;
+        assert_eq!(config.source_address(), None);
+        assert!(config.validate().is_ok());
+
+        config.parse_attributes("interface", Some("lo")).unwrap();
+        assert_eq!(config.socket_options().interface.as_deref(), Some("lo"));
+        assert_eq!(config.validate().is_ok(), cfg!(target_os = "linux"));
+        config.parse_attributes("interface", Some("")).unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_parse_hosts() {
+        let mut config: Config = serde_yaml::from_str(CONFIG).unwrap();
+        assert_eq!(config.bind_address(), IpAddr::V6(Ipv6Addr::UNSPECIFIED));
+        config.parse_attributes("server", Some("::1")).unwrap();
+        assert_eq!(config.server(), IpAddr::V6(Ipv6Addr::LOCALHOST));
+        config.parse_attributes("bind", Some("::1")).unwrap();
+        assert_eq!(config.bind_address(), IpAddr::V6(Ipv6Addr::LOCALHOST));
+
+        let config: Config =
+            serde_yaml::from_str(&CONFIG.replace("\"127.0.0.1\"", "\"localhost\"")).unwrap();
+        assert!(config.server().is_loopback());
+        assert_eq!(config.server.name(), "localhost");
+        assert!(config.server.to_string().starts_with("localhost ("));
+        // The name is written back as given
+        let yaml = serde_yaml::to_string(&config).unwrap();
+        assert!(yaml.contains("server: localhost"));
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        let mut config: Config = serde_yaml::from_str(CONFIG).unwrap();
+        for (variable, value) in [
+            ("port", "70000"),
+            ("m", "big"),
+            ("server", "nowhere.invalid"),
+            ("bind", "localhost"),
+        ] {
+            let result = config.parse_attributes(variable, Some(value));
+            assert!(matches!(result, Err(Error::Config(_))));
+        }
+        assert!(config.parse_attributes("unknown", None).is_err());
+        assert_eq!(config.port(), 7777);
+    }
 }
@@ -0,0 +1,105 @@
+use crate::message::{framing::FramingError, header::HeaderError};
+use std::{fmt, io};
+
+/// Errors of the library. Bad arguments, failing sockets and misbehaving
+/// peers are all reported through it, so that a harness embedding the
+/// crate can carry on.
+#[derive(Debug)]
+pub enum Error {
+    /// An unknown, invalid or inconsistent configuration variable
+    Config(String),
+    /// A system call failed
+    Io(io::Error),
+    /// The peer sent bytes that are not a valid message, or an unexpected one
+    Protocol(String),
+    /// Nothing happened in the allotted time
+    Timeout(String),
+    /// The peer closed the connection
+    PeerClosed,
+    /// The server refused the test the client proposed, for the given reason
+    Rejected(String),
+    /// A received message failed its checksum or announced too large a
+    /// body: the stream is out of sync or corrupted
+    Framing(FramingError),
+    /// A thread or task of the benchmark panicked
+    Panicked(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Config(message) => write!(f, "Configuration error: {}", message),
+            Error::Io(error) => write!(f, "I/O error: {}", error),
+            Error::Protocol(message) => write!(f, "Protocol error: {}", message),
+            Error::Timeout(message) => write!(f, "Timed out: {}", message),
+            Error::PeerClosed => write!(f, "Connection closed by the peer"),
+            Error::Rejected(reason) => write!(f, "Rejected by the server: {}", reason),
+            Error::Framing(error) => write!(f, "Framing error: {}", error),
+            Error::Panicked(what) => write!(f, "{} panicked", what),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(error) => Some(error),
+            Error::Framing(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    /// Tells a closed connection and a timeout apart from other failures
+    fn from(error: io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::UnexpectedEof
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::BrokenPipe => Error::PeerClosed,
+            io::ErrorKind::TimedOut => Error::Timeout(error.to_string()),
+            _ => Error::Io(error),
+        }
+    }
+}
+
+impl From<HeaderError> for Error {
+    fn from(error: HeaderError) -> Self {
+        Error::Protocol(error.to_string())
+    }
+}
+
+impl From<FramingError> for Error {
+    fn from(error: FramingError) -> Self {
+        Error::Framing(error)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        Error::Io(error.into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::Error;
+    use crate::message::header::HeaderError;
+    use std::io;
+
+    #[test]
+    fn test_from() {
+        let eof = io::Error::from(io::ErrorKind::UnexpectedEof);
+        assert!(matches!(Error::from(eof), Error::PeerClosed));
+        let timeout = io::Error::from(io::ErrorKind::TimedOut);
+        assert!(matches!(Error::from(timeout), Error::Timeout(_)));
+        let refused = io::Error::from(io::ErrorKind::ConnectionRefused);
+        assert!(matches!(Error::from(refused), Error::Io(_)));
+        let header = HeaderError::Truncated(3);
+        assert!(matches!(Error::from(header), Error::Protocol(_)));
+    }
+}
@@ -0,0 +1,180 @@
+use crate::unit::duration_to_string;
+
+/// Number of bits resolved exactly within each power of two. 7 bits keep
+/// the relative error of any recorded value under 1%.
+const SUB_BUCKET_BITS: u32 = 7;
+const SUB_BUCKET_COUNT: u64 = 1 << SUB_BUCKET_BITS;
+const SUB_BUCKET_HALF: u64 = SUB_BUCKET_COUNT / 2;
+const BUCKET_COUNT: usize = ((66 - SUB_BUCKET_BITS) as u64 * SUB_BUCKET_HALF) as usize;
+
+/// A log-linear histogram of latencies in nanoseconds.
+/// Memory is fixed (a few thousand counters) whatever the number of samples,
+/// so it can record long runs without growing.
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    counts: Vec<u64>,
+    count: u64,
+    sum: u128,
+    min: u64,
+    max: u64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Histogram {
+    pub fn new() -> Histogram {
+        Self {
+            counts: vec![0; BUCKET_COUNT],
+            count: 0,
+            sum: 0,
+            min: u64::MAX,
+            max: 0,
+        }
+    }
+
+    /// Records one value (in nanoseconds)
+    pub fn record(&mut self, value: u64) {
+        self.counts[Self::index(value)] += 1;
+        self.count += 1;
+        self.sum += value as u128;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    /// Adds all the values recorded by another histogram
+    pub fn merge(&mut self, other: &Histogram) {
+        for (count, other_count) in self.counts.iter_mut().zip(&other.counts) {
+            *count += other_count;
+        }
+        self.count += other.count;
+        self.sum += other.sum;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn min(&self) -> u64 {
+        match self.count {
+            0 => 0,
+            _ => self.min,
+        }
+    }
+
+    pub fn max(&self) -> u64 {
+        self.max
+    }
+
+    pub fn mean(&self) -> f64 {
+        match self.count {
+            0 => 0.0,
+            _ => self.sum as f64 / self.count as f64,
+        }
+    }
+
+    /// Returns the value below which `percentile` percent of the recorded
+    /// values fall, e.g. `percentile(99.9)`.
+    pub fn percentile(&self, percentile: f64) -> u64 {
+        if self.count == 0 {
+            return 0;
+        }
+        let rank = ((percentile / 100.0) * self.count as f64).ceil() as u64;
+        let rank = rank.clamp(1, self.count);
+        let mut seen = 0;
+        for (index, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Self::highest_value(index).clamp(self.min, self.max);
+            }
+        }
+        self.max
+    }
+
+    /// Prints min/mean/max and the usual percentiles
+    pub fn print(&self) {
+        println!("Samples: {}", self.count);
+        println!("min: {}", duration_to_string(self.min() as f64));
+        println!("mean: {}", duration_to_string(self.mean()));
+        for percentile in [50.0, 90.0, 99.0, 99.9] {
+            println!(
+                "p{}: {}",
+                percentile,
+                duration_to_string(self.percentile(percentile) as f64)
+            );
+        }
+        println!("max: {}", duration_to_string(self.max() as f64));
+    }
+
+    fn index(value: u64) -> usize {
+        if value < SUB_BUCKET_COUNT {
+            return value as usize;
+        }
+        let msb = 63 - value.leading_zeros();
+        let shift = msb - SUB_BUCKET_BITS + 1;
+        let sub_bucket = value >> shift;
+        (shift as u64 * SUB_BUCKET_HALF + sub_bucket) as usize
+    }
+
+    /// Returns the highest value that falls in the given bucket
+    fn highest_value(index: usize) -> u64 {
+        let index = index as u64;
+        if index < SUB_BUCKET_COUNT {
+            return index;
+        }
+        let shift = index / SUB_BUCKET_HALF - 1;
+        let sub_bucket = index - shift * SUB_BUCKET_HALF;
+        (sub_bucket << shift) + ((1 << shift) - 1)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::Histogram;
+
+    #[test]
+    fn test_empty_histogram() {
+        let histogram = Histogram::new();
+        assert_eq!(histogram.count(), 0);
+        assert_eq!(histogram.min(), 0);
+        assert_eq!(histogram.max(), 0);
+        assert_eq!(histogram.percentile(99.0), 0);
+    }
+
+    #[test]
+    fn test_percentiles() {
+        let mut histogram = Histogram::new();
+        for value in 1..=10_000u64 {
+            histogram.record(value * 1_000);
+        }
+        assert_eq!(histogram.count(), 10_000);
+        assert_eq!(histogram.min(), 1_000);
+        assert_eq!(histogram.max(), 10_000_000);
+        assert!((histogram.mean() - 5_000_500.0).abs() < 1.0);
+
+        for (percentile, expected) in [(50.0, 5_000_000.0), (99.0, 9_900_000.0)] {
+            let value = histogram.percentile(percentile) as f64;
+            assert!((value - expected).abs() / expected < 0.01);
+        }
+        assert_eq!(histogram.percentile(100.0), 10_000_000);
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut first = Histogram::new();
+        let mut second = Histogram::new();
+        first.record(10);
+        second.record(u64::MAX);
+        first.merge(&second);
+        assert_eq!(first.count(), 2);
+        assert_eq!(first.min(), 10);
+        assert_eq!(first.max(), u64::MAX);
+        assert_eq!(first.percentile(50.0), 10);
+    }
+}
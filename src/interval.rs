@@ -0,0 +1,301 @@
+use crate::{
+    results::{Metrics, TcpInfoMetrics},
+    unit::*,
+};
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, RecvTimeoutError},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+/// Samples the kernel statistics of a connection from the reporting thread
+pub type Sampler = Box<dyn Fn() -> Option<TcpInfoMetrics> + Send>;
+
+/// Prints transfer statistics every `interval`, whatever the batch size,
+/// like iperf's `-i`. The clock starts with the first recorded message;
+/// from then on a thread reports on every multiple of `interval`, whether
+/// messages came through or not, so a stalled transfer shows as empty
+/// intervals. Reported intervals are kept for the results output.
+pub struct IntervalReporter {
+    interval: Duration,
+    start: Option<Instant>,
+    counts: Arc<Counts>,
+    total_bytes: u64,
+    total_messages: u64,
+    // Lent to the reporting thread while it runs
+    intervals: Option<Intervals>,
+    sampler: Option<Sampler>,
+    // End of the measured phase, when the run has one
+    deadline: Option<Instant>,
+    reporting: Option<Reporting>,
+}
+
+/// What the current interval transferred so far
+#[derive(Debug, Default)]
+struct Counts {
+    bytes: AtomicU64,
+    messages: AtomicU64,
+}
+
+/// The reporting thread, stopped when `stop` is dropped
+#[derive(Debug)]
+struct Reporting {
+    stop: mpsc::Sender<()>,
+    thread: JoinHandle<Intervals>,
+}
+
+/// Intervals reported so far, and where the next one starts
+#[derive(Debug, Default)]
+struct Intervals {
+    label: String,
+    counts: Arc<Counts>,
+    start: Duration,
+    // Sample of the end of the previous interval
+    tcp_info: Option<TcpInfoMetrics>,
+    reported: Vec<Metrics>,
+}
+
+impl IntervalReporter {
+    pub fn new(interval: Duration) -> IntervalReporter {
+        let counts = Arc::new(Counts::default());
+        Self {
+            interval,
+            start: None,
+            counts: Arc::clone(&counts),
+            total_bytes: 0,
+            total_messages: 0,
+            intervals: Some(Intervals {
+                counts,
+                ..Default::default()
+            }),
+            sampler: None,
+            deadline: None,
+            reporting: None,
+        }
+    }
+
+    /// Prefixes every printed line, to tell concurrent streams apart
+    pub fn with_label(mut self, label: &str) -> IntervalReporter {
+        if let Some(intervals) = self.intervals.as_mut() {
+            intervals.label = label.to_string();
+        }
+        self
+    }
+
+    /// Samples the connection at the end of every interval, when the
+    /// transport can
+    pub fn with_tcp_info(mut self, sampler: Option<Sampler>) -> IntervalReporter {
+        self.sampler = sampler;
+        self
+    }
+
+    /// Stops reporting at `deadline`, when the measured phase ends: no
+    /// interval is reported past it, and the summary ends there however
+    /// late `finish` is called
+    pub fn with_deadline(mut self, deadline: Option<Instant>) -> IntervalReporter {
+        self.deadline = deadline;
+        self
+    }
+
+    /// Records a message of `bytes` bytes, starting the clock on the first
+    /// one
+    pub fn record(&mut self, bytes: usize) {
+        if self.start.is_none() {
+            self.start_reporting();
+        }
+        self.counts.bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+        self.counts.messages.fetch_add(1, Ordering::Relaxed);
+        self.total_bytes += bytes as u64;
+        self.total_messages += 1;
+    }
+
+    /// Starts the clock, and the thread that reports when intervals end
+    fn start_reporting(&mut self) {
+        let start = Instant::now();
+        self.start = Some(start);
+        let (mut intervals, interval) = match self.intervals.take() {
+            Some(intervals) => (intervals, self.interval),
+            None => return,
+        };
+        let sampler = self.sampler.take();
+        let deadline = self.deadline;
+        let (stop, stopped) = mpsc::channel::<()>();
+        let thread = thread::spawn(move || {
+            let mut end = interval;
+            // Wakes up on every boundary before the deadline, until the
+            // sender is dropped
+            while let Some(due) = start
+                .checked_add(end)
+                .filter(|due| deadline.is_none_or(|deadline| *due < deadline))
+            {
+                let timeout = due.saturating_duration_since(Instant::now());
+                match stopped.recv_timeout(timeout) {
+                    Err(RecvTimeoutError::Timeout) => {
+                        intervals.report(end, sampler.as_ref().and_then(|sample| sample()));
+                        end = end.saturating_add(interval);
+                    }
+                    _ => break,
+                }
+            }
+            intervals
+        });
+        self.reporting = Some(Reporting { stop, thread });
+    }
+
+    /// Stops reporting, then prints the last, possibly partial, interval
+    /// and a summary line, up to now or the deadline, whichever is first. `tcp_info` is a sample of the connection taken
+    /// now, when the transport has one: its retransmits are a running
+    /// total, each interval keeps the difference. Returns the summary.
+    pub fn finish(&mut self, tcp_info: Option<TcpInfoMetrics>) -> Metrics {
+        if let Some(Reporting { stop, thread }) = self.reporting.take() {
+            drop(stop);
+            // A reporting thread that panicked loses its intervals only
+            self.intervals = Some(thread.join().unwrap_or_else(|_| Intervals {
+                counts: Arc::clone(&self.counts),
+                ..Default::default()
+            }));
+        }
+        // Once the reporting thread is stopped, no interval ends later
+        let elapsed = match (self.start, self.deadline) {
+            (Some(start), Some(deadline)) => deadline
+                .min(Instant::now())
+                .saturating_duration_since(start),
+            (Some(start), None) => start.elapsed(),
+            (None, _) => Duration::ZERO,
+        };
+        let intervals = self.intervals.get_or_insert_with(Default::default);
+        if self.counts.messages.load(Ordering::Relaxed) > 0 {
+            intervals.report(elapsed, tcp_info);
+        }
+        let metrics = Metrics::new(
+            Duration::ZERO,
+            elapsed,
+            self.total_bytes,
+            self.total_messages,
+            None,
+        )
+        .with_tcp_info(tcp_info, None);
+        print_line(&intervals.label, "Total", &metrics);
+        metrics
+    }
+
+    /// Intervals reported so far, once reporting is finished
+    pub fn intervals(&self) -> &[Metrics] {
+        match &self.intervals {
+            Some(intervals) => &intervals.reported,
+            None => &[],
+        }
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.total_bytes
+    }
+
+    pub fn total_messages(&self) -> u64 {
+        self.total_messages
+    }
+}
+
+impl fmt::Debug for IntervalReporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IntervalReporter")
+            .field("interval", &self.interval)
+            .field("start", &self.start)
+            .field("total_bytes", &self.total_bytes)
+            .field("total_messages", &self.total_messages)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Intervals {
+    /// Prints the interval ending `end` after the clock started, and starts
+    /// the next one
+    fn report(&mut self, end: Duration, tcp_info: Option<TcpInfoMetrics>) {
+        let metrics = Metrics::new(
+            self.start,
+            end,
+            self.counts.bytes.swap(0, Ordering::Relaxed),
+            self.counts.messages.swap(0, Ordering::Relaxed),
+            None,
+        )
+        .with_tcp_info(tcp_info, self.tcp_info.as_ref());
+        print_line(&self.label, "Interval", &metrics);
+        self.reported.push(metrics);
+        self.tcp_info = tcp_info;
+        self.start = end;
+    }
+}
+
+fn print_line(label: &str, kind: &str, metrics: &Metrics) {
+    let retransmits = match metrics.retransmits {
+        Some(retransmits) => format!("; Retransmits: {}", retransmits),
+        None => String::new(),
+    };
+    let tcp_info = match &metrics.tcp_info {
+        Some(tcp_info) => format!("; {}", tcp_info),
+        None => String::new(),
+    };
+    println!(
+        "{}{} {:.2}-{:.2}s: Transferred: {}; Throughput: {}; Messages/s: {:.0}{}{}",
+        label,
+        kind,
+        metrics.start_seconds,
+        metrics.end_seconds,
+        data_to_string(metrics.bytes as f64),
+        throughput_to_string(metrics.bits_per_second),
+        metrics.messages_per_second,
+        retransmits,
+        tcp_info
+    );
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::IntervalReporter;
+    use crate::results::TcpInfoMetrics;
+    use std::{thread, time::Duration};
+
+    #[test]
+    fn test_interval_reporter() {
+        let sample = |retransmits| {
+            Some(TcpInfoMetrics {
+                retransmits,
+                rtt_microseconds: 50,
+                ..Default::default()
+            })
+        };
+        let mut reporter = IntervalReporter::new(Duration::from_millis(100))
+            .with_tcp_info(Some(Box::new(move || sample(3))));
+        reporter.record(100);
+        reporter.record(100);
+        // Nothing comes through during the second interval
+        thread::sleep(Duration::from_millis(250));
+        reporter.record(50);
+        let total = reporter.finish(sample(5));
+        assert_eq!(reporter.total_bytes(), 250);
+        assert_eq!(total.bytes, 250);
+        assert_eq!(total.retransmits, Some(5));
+
+        let intervals = reporter.intervals();
+        assert_eq!(intervals.len(), 3);
+        assert_eq!(intervals[0].bytes, 200);
+        assert_eq!(intervals[0].retransmits, Some(3));
+        assert_eq!(intervals[1].bytes, 0);
+        assert_eq!(intervals[1].messages_per_second, 0.0);
+        // Intervals end on multiples of the interval, the last one when
+        // reporting finishes
+        assert_eq!(intervals[1].start_seconds, 0.1);
+        assert_eq!(intervals[1].end_seconds, 0.2);
+        assert_eq!(intervals[2].bytes, 50);
+        assert_eq!(intervals[2].retransmits, Some(2));
+        assert_eq!(intervals[2].tcp_info, sample(5));
+        assert_eq!(intervals[2].start_seconds, intervals[1].end_seconds);
+        assert_eq!(reporter.total_messages(), 3);
+    }
+}
@@ -1,61 +1,776 @@
-use crate::config::Config;
+use crate::config::{Config, Direction, Mode, Protocol, Role, SendMode};
 pub mod config;
+pub mod error;
+pub mod histogram;
+pub mod interval;
+pub mod load;
 pub mod message;
+pub mod meter;
 pub mod network;
+pub mod pacer;
+pub mod phase;
+pub mod random;
+pub mod results;
 pub mod unit;
+pub mod usage;
 use crate::{
+    error::{Error, Result},
+    histogram::Histogram,
+    interval::IntervalReporter,
     message::{message::Message, mtype::Type},
-    network::{client, io::send},
+    meter::ThroughputMeter,
+    network::{
+        control::{print_combined, Proposal, Report},
+        socket::print_settings,
+        stats::ReceiverStats,
+        transport::{tcp::TcpTransport, Connection, Transport},
+        udp,
+    },
+    pacer::{print_pacing, Pacer},
+    phase::{Phase, Phases},
+    results::{
+        CostMetrics, LatencyMetrics, Metadata, Metrics, OutputFormat, RunResults, StreamResults,
+    },
     unit::*,
+    usage::{print_cost, Usage},
 };
 use network::server;
-use std::time::Instant;
+#[cfg(unix)]
+use network::transport::unix::UnixTransport;
+#[cfg(all(target_os = "linux", feature = "uring"))]
+use network::transport::uring::UringTransport;
+use std::{
+    net::{IpAddr, SocketAddr},
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
 
-pub fn run_server(config: Config, is_limited: Option<usize>) {
+pub fn run_server(config: Config, is_limited: Option<usize>) -> Result<()> {
+    config.validate()?;
+    let started_at = SystemTime::now();
+    let usage = Usage::start();
+    #[cfg(feature = "async")]
+    if config.runtime() == config::Runtime::Async {
+        let streams = network::asynchronous::run_server(&config, is_limited)?;
+        report(&config, started_at, usage, streams);
+        return Ok(());
+    }
     let port = config.port();
-    let pool_size = 1;
-    let queue_size = 1;
-    server::listen_portable(port, pool_size, queue_size, is_limited);
+    let format = config.header_format();
+    match config.protocol() {
+        #[cfg(all(target_os = "linux", feature = "uring"))]
+        Protocol::Tcp if config.runtime() == config::Runtime::Uring => {
+            let transport = UringTransport::new(config.server(), port, format)
+                .with_bind_address(config.bind_address())
+                .with_options(config.socket_options())
+                .with_framing(config.framing());
+            run_server_with(&transport, &config, is_limited)
+        }
+        Protocol::Tcp => {
+            let transport = TcpTransport::new(config.server(), port, format)
+                .with_bind_address(config.bind_address())
+                .with_options(config.socket_options())
+                .with_framing(config.framing());
+            run_server_with(&transport, &config, is_limited)
+        }
+        Protocol::Udp => {
+            let address = SocketAddr::new(config.bind_address(), port);
+            let streams = udp::listen(address, is_limited, config.interval())?;
+            report(&config, started_at, usage, streams);
+            Ok(())
+        }
+        #[cfg(unix)]
+        Protocol::Unix => {
+            let transport =
+                UnixTransport::new(socket_path(&config)?, format).with_framing(config.framing());
+            run_server_with(&transport, &config, is_limited)
+        }
+        #[cfg(not(unix))]
+        Protocol::Unix => Err(Error::Config(
+            "Unix sockets are not supported on this platform".into(),
+        )),
+    }
+}
+
+/// Runs the server over any transport. Without a `workers` count, every
+/// limited client gets a worker; an unlimited server gets one per CPU.
+pub fn run_server_with<T: Transport>(
+    transport: &T,
+    config: &Config,
+    is_limited: Option<usize>,
+) -> Result<()> {
+    config.validate()?;
+    let started_at = SystemTime::now();
+    let usage = Usage::start();
+    let pool_size = config.workers().or(is_limited).unwrap_or_else(|| {
+        thread::available_parallelism()
+            .map(usize::from)
+            .unwrap_or(1)
+    });
+    let listener = transport.listen()?;
+    let interval = config.interval();
+    let streams = server::serve(listener, pool_size, is_limited, interval)?;
+    report(config, started_at, usage, streams);
+    Ok(())
+}
+
+pub fn run_client(config: Config) -> Result<()> {
+    config.validate()?;
+    let started_at = SystemTime::now();
+    let usage = Usage::start();
+    #[cfg(feature = "async")]
+    if config.runtime() == config::Runtime::Async {
+        println!("Connecting to {}...", server_address(&config));
+        let streams = network::asynchronous::run_client(&config)?;
+        report(&config, started_at, usage, streams);
+        return Ok(());
+    }
+    let format = config.header_format();
+    match config.protocol() {
+        #[cfg(all(target_os = "linux", feature = "uring"))]
+        Protocol::Tcp if config.runtime() == config::Runtime::Uring => {
+            println!("Connecting to {}...", server_address(&config));
+            let mut transport = UringTransport::new(config.server(), config.port(), format)
+                .with_options(config.socket_options())
+                .with_framing(config.framing());
+            if let Some(source) = config.source_address() {
+                transport = transport.with_source(source);
+            }
+            run_client_with(&transport, &config)
+        }
+        Protocol::Tcp => {
+            println!("Connecting to {}...", server_address(&config));
+            let mut transport = TcpTransport::new(config.server(), config.port(), format)
+                .with_options(config.socket_options())
+                .with_framing(config.framing());
+            if let Some(source) = config.source_address() {
+                transport = transport.with_source(source);
+            }
+            run_client_with(&transport, &config)
+        }
+        Protocol::Udp => {
+            let streams = run_streams(&config, |label| Ok(vec![run_udp_client(&config, label)?]))?;
+            report(&config, started_at, usage, streams);
+            Ok(())
+        }
+        #[cfg(unix)]
+        Protocol::Unix => {
+            let socket_path = socket_path(&config)?;
+            println!("Connecting to {}...", socket_path.display());
+            let transport = UnixTransport::new(socket_path, format).with_framing(config.framing());
+            run_client_with(&transport, &config)
+        }
+        #[cfg(not(unix))]
+        Protocol::Unix => Err(Error::Config(
+            "Unix sockets are not supported on this platform".into(),
+        )),
+    }
+}
+
+/// Where the client connects to
+fn server_address(config: &Config) -> SocketAddr {
+    SocketAddr::new(config.server(), config.port())
+}
+
+#[cfg(unix)]
+fn socket_path(config: &Config) -> Result<&std::path::Path> {
+    config
+        .socket_path()
+        .map(|socket_path| socket_path.as_path())
+        .ok_or_else(|| Error::Config("Unix protocol requires a socket_path".into()))
+}
+
+/// Runs the client workload over any transport, on `parallel` connections.
+/// When comparing send modes, runs it once per mode on new connections.
+pub fn run_client_with<T: Transport + Sync>(transport: &T, config: &Config) -> Result<()> {
+    config.validate()?;
+    let started_at = SystemTime::now();
+    let usage = Usage::start();
+    let streams = match config.compare_send_modes() {
+        false => run_transport_streams(transport, config, config.send_mode())?,
+        true => compare_send_modes(transport, config)?,
+    };
+    report(config, started_at, usage, streams);
+    Ok(())
+}
+
+fn run_transport_streams<T: Transport + Sync>(
+    transport: &T,
+    config: &Config,
+    send_mode: SendMode,
+) -> Result<Vec<StreamResults>> {
+    run_streams(config, |label| {
+        let mut connection = transport.connect()?;
+        println!("{}Connected to {}", label, connection.peer());
+        let socket = connection.socket();
+        if let Some(socket) = &socket {
+            print_settings(label, socket);
+        }
+        if send_mode == SendMode::ZeroCopy {
+            connection.enable_zerocopy()?;
+        }
+
+        let client_address = source(config, &connection);
+        Proposal::new(config)?.propose(&mut connection, client_address, config.server())?;
+        let (mut streams, report) = match config.direction() {
+            Direction::Forward => {
+                let stream = match config.mode() {
+                    Mode::Throughput => measure_throughput(config, &mut connection, label)?,
+                    Mode::Latency => measure_latency(config, &mut connection, label)?,
+                    Mode::OpenLoop => load::measure_open_loop(config, &mut connection, label)?,
+                };
+                let msg = Message::new(client_address, config.server(), 0, Type::Close);
+                connection.send(&msg)?;
+                (vec![stream], Report::receive(&mut connection)?)
+            }
+            direction => exchange(config, &mut connection, label, direction)?,
+        };
+        // The server reports first, then waits for the client's report
+        Report::from_streams(&streams).send(&mut connection, client_address, config.server())?;
+        report.apply(&mut streams);
+        print_combined(label, &streams, Role::Client);
+
+        let streams = streams
+            .into_iter()
+            .map(|stream| StreamResults {
+                socket: socket.clone(),
+                ..stream
+            })
+            .collect();
+        Ok(streams)
+    })
+}
+
+/// Runs a reverse or bidirectional transfer the server accepted: receives
+/// what the server sends back, while sending on another thread when
+/// bidirectional. Each direction is measured on its own. Returns them with
+/// the report the server sends once both directions stopped.
+fn exchange<C: Connection>(
+    config: &Config,
+    connection: &mut C,
+    label: &str,
+    direction: Direction,
+) -> Result<(Vec<StreamResults>, Report)> {
+    if direction == Direction::Reverse {
+        let received = receive_stream(config, connection, label, true)?;
+        return Ok((vec![received], Report::receive(connection)?));
+    }
+
+    let mut receiving = connection.try_clone()?;
+    let received_label = format!("{}Received ", label);
+    thread::scope(|scope| {
+        let receiver = scope.spawn(|| -> Result<(StreamResults, Report)> {
+            let received = receive_stream(config, &mut receiving, &received_label, false)?;
+            // The report follows the server's Close, on the receiving handle
+            Ok((received, Report::receive(&mut receiving)?))
+        });
+        let sent = measure_throughput(config, connection, &format!("{}Sent ", label));
+        // The server stops sending once it gets Close; without it, the
+        // receiving thread would wait forever
+        let msg = Message::new(source(config, connection), config.server(), 0, Type::Close);
+        let sent = sent.and_then(|sent| connection.send(&msg).map(|_| sent));
+        if sent.is_err() {
+            let _ = connection.close();
+        }
+        let received = receiver
+            .join()
+            .unwrap_or_else(|_| Err(Error::Panicked("Receiving".into())));
+        let sent = StreamResults {
+            direction: Some(Direction::Forward),
+            ..sent?
+        };
+        let (received, report) = received?;
+        Ok((vec![sent, received], report))
+    })
+}
+
+/// Receives the `Benchmark` messages the server sends back until it sends
+/// `Close`. Only the `duration`, or `number_batches` of `batch_size`
+/// messages, are measured. When `is_ending`, the client ends the run: it
+/// sends `Close` once the measure is over, then drains what the server sent
+/// in the meantime.
+fn receive_stream<C: Connection>(
+    config: &Config,
+    connection: &mut C,
+    label: &str,
+    is_ending: bool,
+) -> Result<StreamResults> {
+    let phases = config
+        .duration()
+        .map(|duration| Phases::new(config.warmup(), duration, config.cooldown()));
+    let number_messages = (config.number_batches() * config.batch_size()) as u64;
+    let mut stats = ReceiverStats::new();
+    if let Some(phases) = phases {
+        stats.set_phases(phases);
+    }
+    if config.verify() {
+        stats.verify(config.payload()?);
+    }
+    let mut reporter = config.interval().map(|interval| {
+        IntervalReporter::new(interval)
+            .with_label(label)
+            .with_tcp_info(connection.tcp_info_sampler())
+            .with_deadline(phases.and_then(|phases| phases.measure_end()))
+    });
+    let mut is_closing = false;
+    loop {
+        let message = connection.receive()?;
+        let phase = match phases {
+            Some(phases) => phases.current(),
+            None if stats.messages() == number_messages => Phase::Done,
+            None => Phase::Measure,
+        };
+        match (message.mtype(), phase) {
+            (Type::Close, _) => break,
+            (_, Phase::Measure) => {
+                stats.record(&message);
+                if let Some(reporter) = reporter.as_mut() {
+                    reporter.record(message.body_size());
+                }
+            }
+            (_, Phase::Done) if is_ending && !is_closing => {
+                let msg = Message::new(source(config, connection), config.server(), 0, Type::Close);
+                connection.send(&msg)?;
+                is_closing = true;
+            }
+            _ => (),
+        }
+    }
+
+    if let Some(reporter) = reporter.as_mut() {
+        reporter.finish(connection.tcp_info());
+        stats.set_intervals(reporter.intervals().to_vec());
+    }
+    stats.set_tcp_info(connection.tcp_info());
+    stats.set_syscalls(connection.syscalls());
+    let peer_address = connection.peer();
+    stats.print(&peer_address);
+    Ok(StreamResults {
+        direction: Some(Direction::Reverse),
+        ..stats.to_results(&peer_address)
+    })
+}
+
+/// Runs the workload with copied bodies, then with zero-copy ones, and
+/// prints the throughput and CPU time of both
+fn compare_send_modes<T: Transport + Sync>(
+    transport: &T,
+    config: &Config,
+) -> Result<Vec<StreamResults>> {
+    let mut streams = Vec::new();
+    let mut comparison = Vec::new();
+    for send_mode in [SendMode::Copy, SendMode::ZeroCopy] {
+        println!("Send mode: {:?}", send_mode);
+        let usage = Usage::start();
+        let mode_streams = run_transport_streams(transport, config, send_mode)?;
+        let cost = usage.finish(&mode_streams);
+        let summaries = mode_streams.iter().map(|stream| &stream.summary);
+        comparison.push((send_mode, Metrics::aggregate(summaries), cost));
+        streams.extend(mode_streams.into_iter().map(|stream| StreamResults {
+            send_mode: Some(send_mode),
+            ..stream
+        }));
+    }
+
+    println!("Comparison of send modes:");
+    for (send_mode, metrics, cost) in &comparison {
+        print_metrics(&format!("[{:?}] ", send_mode), None, metrics);
+        print_cost(cost);
+    }
+    Ok(streams)
+}
+
+/// Runs `stream` on `parallel` threads at once, like `iperf -P`, and prints
+/// the sum of their throughput. Each stream is given a label to prefix its
+/// lines with; a single stream has an empty label. A bidirectional stream
+/// has results for each direction. Fails if any stream does, once all of
+/// them are over.
+fn run_streams<F>(config: &Config, stream: F) -> Result<Vec<StreamResults>>
+where
+    F: Fn(&str) -> Result<Vec<StreamResults>> + Sync,
+{
+    let parallel = config.parallel();
+    if parallel == 1 {
+        return stream("");
+    }
+
+    let streams: Vec<Result<Vec<StreamResults>>> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..parallel)
+            .map(|index| {
+                let stream = &stream;
+                scope.spawn(move || stream(&format!("[{}] ", index)))
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|_| Err(Error::Panicked("Stream".into())))
+            })
+            .collect()
+    });
+    let streams: Vec<StreamResults> = streams
+        .into_iter()
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .flatten()
+        .collect();
+
+    print_summary(&streams);
+    Ok(streams)
+}
+
+/// Prints the summary of each of several concurrent streams and their sum,
+/// per direction on reverse and bidirectional runs
+fn print_summary(streams: &[StreamResults]) {
+    println!("Summary of {} streams:", streams.len());
+    for (index, stream) in streams.iter().enumerate() {
+        let label = direction_label(&format!("[{}]", index), stream.direction);
+        print_metrics(&label, stream.direction, &stream.summary);
+    }
+    let mut directions: Vec<Option<Direction>> = Vec::new();
+    for stream in streams {
+        if !directions.contains(&stream.direction) {
+            directions.push(stream.direction);
+        }
+    }
+    for direction in directions {
+        let summaries = streams
+            .iter()
+            .filter(|stream| stream.direction == direction)
+            .map(|stream| &stream.summary);
+        print_metrics(
+            &direction_label("[SUM]", direction),
+            direction,
+            &Metrics::aggregate(summaries),
+        );
+    }
+}
+
+fn direction_label(label: &str, direction: Option<Direction>) -> String {
+    match direction {
+        Some(direction) => format!("{} {:?} ", label, direction),
+        None => format!("{} ", label),
+    }
+}
+
+/// Prints the summary of what the client sent, or received on reverse
+/// streams
+fn print_metrics(label: &str, direction: Option<Direction>, metrics: &Metrics) {
+    let bytes = match direction {
+        Some(Direction::Reverse) => "Received bytes",
+        _ => "Sent bytes",
+    };
+    println!(
+        "{}Throughput: {}; {}: {}; Messages/s: {:.0}; Time elapsed: {:.2}s",
+        label,
+        throughput_to_string(metrics.bits_per_second),
+        bytes,
+        data_to_string(metrics.bytes as f64),
+        metrics.messages_per_second,
+        metrics.elapsed_seconds()
+    );
 }
 
-pub fn run_client(config: Config) {
+/// Prints what the run cost in CPU time and system calls, then writes its
+/// results
+fn report(config: &Config, started_at: SystemTime, usage: Usage, streams: Vec<StreamResults>) {
+    let cost = usage.finish(&streams);
+    print_cost(&cost);
+    write_results(config, started_at, streams, cost);
+}
+
+/// Writes the machine-readable results of a run, unless the output format
+/// is plain text
+fn write_results(
+    config: &Config,
+    started_at: SystemTime,
+    streams: Vec<StreamResults>,
+    cost: CostMetrics,
+) {
+    if config.output_format() == OutputFormat::Text {
+        return;
+    }
+    let metadata = Metadata::new(config.role(), started_at);
+    let results = RunResults::new(metadata, config.clone(), streams, cost);
+    let path = config.output_file().map(|path| path.as_path());
+    if let Err(e) = results.write(config.output_format(), path) {
+        println!("Unable to write results: {}", e);
+    }
+}
+
+/// Sends batches of `Benchmark` messages and prints the throughput of each
+/// batch. With a `duration`, sends until the deadline instead of
+/// `number_batches`, and also prints the throughput of the measured phase.
+/// With an `interval`, prints interval reports instead of batch reports.
+fn measure_throughput<C: Connection>(
+    config: &Config,
+    connection: &mut C,
+    label: &str,
+) -> Result<StreamResults> {
+    let client_address = source(config, connection);
+    let server_address = config.server();
+    let body_size = config.message_size();
+    let mtype = Type::Benchmark;
+    let mut msg = Message::new(client_address, server_address, body_size, mtype);
+    let payload = config.payload()?;
+
+    let mut meter =
+        ThroughputMeter::new(config, label).with_tcp_info(connection.tcp_info_sampler());
+    let mut sequence = 0;
+    while let Some(phase) = meter.next_phase() {
+        if let Some(delay) = meter.delay() {
+            pacer::sleep(delay);
+        }
+        payload.refill(&mut msg, sequence);
+        sequence += 1;
+        check_sent(connection.send(&msg)?, body_size)?;
+        meter.record(phase, || connection.tcp_info());
+    }
+    // The body must outlive zero-copy sends
+    connection.flush()?;
+    let mut results = meter.finish(connection.peer(), connection.tcp_info());
+    results.syscalls = connection.syscalls();
+    Ok(results)
+}
+
+fn print_throughput(label: &str, sent_bytes: f64, elapsed: f64) {
+    let throughput = match elapsed {
+        elapsed if elapsed > 0.0 => sent_bytes * 8.0 / elapsed,
+        _ => 0.0,
+    };
+    println!(
+        "{}Throughput: {}; Sent bytes: {}; Time elapsed: {:.2}s",
+        label,
+        throughput_to_string(throughput),
+        data_to_string(sent_bytes),
+        elapsed
+    );
+}
+
+/// Sends `Ping` messages one at a time, waits for each acknowledgement and
+/// prints the distribution of round-trip times. With a `duration`, pings
+/// until the deadline and only records the measured phase.
+fn measure_latency<C: Connection>(
+    config: &Config,
+    connection: &mut C,
+    label: &str,
+) -> Result<StreamResults> {
+    let client_address = source(config, connection);
+    let server_address = config.server();
+    let body_size = config.message_size();
+
+    // Each ping is a small write that waits for its reply: without this,
+    // Nagle's algorithm can hold it back until the previous reply's
+    // delayed acknowledgement. An explicit nodelay option is left alone.
+    if config.socket_options().nodelay.is_none() {
+        connection.set_nodelay(true)?;
+    }
+
+    let peer_address = connection.peer();
+    let payload = config.payload()?;
+    let mut histogram = Histogram::new();
+    let mut pacer = config.pacing().map(Pacer::new);
+    let mut ping = |record: bool| -> Result<()> {
+        let mut ping = Message::new(client_address, server_address, body_size, Type::Ping);
+        payload.fill(&mut ping);
+        if let Some(pacer) = pacer.as_mut() {
+            pacer::sleep(pacer.delay());
+        }
+
+        let now = Instant::now();
+        let size = connection.send(&ping)?;
+        let reply = connection.receive()?;
+        let elapsed = now.elapsed();
+
+        check_sent(size, body_size)?;
+        check_acknowledgement(&ping, &reply)?;
+        if record {
+            histogram.record(elapsed.as_nanos() as u64);
+        }
+        Ok(())
+    };
+
+    let elapsed = match config.duration() {
+        Some(duration) => {
+            let phases = Phases::new(config.warmup(), duration, config.cooldown());
+            loop {
+                match phases.current() {
+                    Phase::Done => break,
+                    phase => ping(phase == Phase::Measure)?,
+                }
+            }
+            phases.duration()
+        }
+        None => {
+            let start = Instant::now();
+            for _counter in 0..config.number_batches() * config.batch_size() {
+                ping(true)?;
+            }
+            start.elapsed()
+        }
+    };
+
+    // Keeps the lines of concurrent streams together
+    let _stdout = std::io::stdout().lock();
+    println!(
+        "{}Round-trip latency ({} message body):",
+        label,
+        data_to_string(body_size as f64)
+    );
+    histogram.print();
+    let pacing = pacer.map(|pacer| pacer.finish(body_size));
+    if let Some(pacing) = &pacing {
+        print_pacing(label, pacing);
+    }
+
+    let pings = histogram.count();
+    Ok(StreamResults {
+        peer: peer_address,
+        summary: Metrics::new(
+            Duration::ZERO,
+            elapsed,
+            pings * body_size as u64,
+            pings,
+            None,
+        )
+        .with_tcp_info(connection.tcp_info(), None),
+        latency: Some(LatencyMetrics::from(&histogram)),
+        syscalls: connection.syscalls(),
+        pacing,
+        ..Default::default()
+    })
+}
+
+/// Address the messages of a connection come from: the one it is bound to,
+/// or the configured client for connections without an IP address
+fn source<C: Connection>(config: &Config, connection: &C) -> IpAddr {
+    connection
+        .local_address()
+        .unwrap_or_else(|| config.client())
+}
+
+/// Checks that `reply` acknowledges `ping`
+fn check_acknowledgement(ping: &Message, reply: &Message) -> Result<()> {
+    if reply.mtype() != Type::Acknoweldge || reply.id() != ping.id() {
+        return Err(Error::Protocol(format!(
+            "expected an acknowledgement of {}, received {:?} {}",
+            ping.id(),
+            reply.mtype(),
+            reply.id()
+        )));
+    }
+    Ok(())
+}
+
+/// Checks that a whole body of `body_size` bytes was sent
+fn check_sent(size: usize, body_size: usize) -> Result<()> {
+    if size != body_size {
+        return Err(Error::Protocol(format!(
+            "sent a body of {} bytes instead of {}",
+            size, body_size
+        )));
+    }
+    Ok(())
+}
+
+/// Sends batches of `Benchmark` datagrams, each stamped with a sequence
+/// number and a timestamp, and prints the send throughput of each batch,
+/// or of each interval when one is set. Loss, reordering and jitter are
+/// reported by the server.
+fn run_udp_client(config: &Config, label: &str) -> Result<StreamResults> {
     let server_address = config.server();
     let port = config.port();
-    println!("Connecting to {}:{}...", server_address, port);
-    let mut stream = client::connect(server_address, port).expect("Unable to connect");
-    assert_eq!(stream.peer_addr().unwrap().ip(), server_address);
+    println!(
+        "{}Sending datagrams to {}...",
+        label,
+        SocketAddr::new(server_address, port)
+    );
+    let socket = udp::connect(config.source_address(), server_address, port)?;
+    let client_address = socket.local_addr()?.ip();
 
-    let client_address = config.client();
     let body_size = config.message_size();
-    let mtype = Type::Benchmark;
-    let msg = Message::new(client_address, server_address, body_size, mtype);
+    let mut msg = Message::new(client_address, server_address, body_size, Type::Benchmark);
+    let payload = config.payload()?;
+    let mut sequence = 0;
+    // Without a duration, the run ends after number_batches
+    let phases = config
+        .duration()
+        .map(|duration| Phases::new(Duration::ZERO, duration, Duration::ZERO));
     let mut sent_batches = 0;
+    let mut intervals = Vec::new();
+    let mut reporter = config.interval().map(|interval| {
+        IntervalReporter::new(interval)
+            .with_label(label)
+            .with_deadline(phases.and_then(|phases| phases.measure_end()))
+    });
+    let mut pacer = config.pacing().map(Pacer::new);
+    let start = Instant::now();
     loop {
         let now = Instant::now();
 
-        for _counter in 0..config.batch_size() {
-            let size = send(&mut stream, &msg).expect("Unable to send message");
-            assert_eq!(size, body_size);
+        let mut sent_messages = 0;
+        while sent_messages < config.batch_size() {
+            if phases.is_some_and(|phases| phases.current() == Phase::Done) {
+                break;
+            }
+            if let Some(pacer) = pacer.as_mut() {
+                pacer::sleep(pacer.delay());
+            }
+            payload.refill(&mut msg, sequence);
+            msg.header_as_mut().set_timestamp(udp::now_nanos());
+            check_sent(udp::send(&socket, &msg)?, body_size)?;
+            sequence += 1;
+            sent_messages += 1;
+            if let Some(reporter) = reporter.as_mut() {
+                reporter.record(body_size);
+            }
         }
 
-        let elapsed = now.elapsed().as_secs_f64();
-        let sent_bytes = (config.message_size() * config.batch_size()) as f64;
-        let sent_bits = (sent_bytes * 8.0) as f64;
-
-        let throughput = sent_bits / elapsed;
-        println!(
-            "Throughput: {}; Sent bytes: {}; Time elapsed: {:.2}s",
-            throughput_to_string(throughput),
-            data_to_string(sent_bytes),
-            elapsed
-        );
+        if reporter.is_none() {
+            let sent_bytes = body_size * sent_messages;
+            print_throughput(label, sent_bytes as f64, now.elapsed().as_secs_f64());
+            intervals.push(Metrics::new(
+                now - start,
+                start.elapsed(),
+                sent_bytes as u64,
+                sent_messages as u64,
+                None,
+            ));
+        }
         sent_batches += 1;
-        if sent_batches == config.number_batches() {
+        let finished = match phases {
+            Some(phases) => phases.current() == Phase::Done,
+            None => sent_batches == config.number_batches(),
+        };
+        if finished {
             break;
         }
     }
 
-    let msg = Message::new(client_address, server_address, 0, Type::Close);
-    let size = send(&mut stream, &msg).expect("Unable to send message");
-    assert_eq!(size, 0);
+    udp::close(&socket, client_address, server_address, sequence);
+    let summary = match reporter.as_mut() {
+        Some(reporter) => {
+            let summary = reporter.finish(None);
+            intervals = reporter.intervals().to_vec();
+            summary
+        }
+        None => Metrics::aggregate(&intervals),
+    };
+    let pacing = pacer.map(|pacer| pacer.finish(body_size));
+    if let Some(pacing) = &pacing {
+        print_pacing(label, pacing);
+    }
+
+    Ok(StreamResults {
+        peer: socket
+            .peer_addr()
+            .map(|address| address.to_string())
+            .unwrap_or_default(),
+        summary,
+        intervals,
+        pacing,
+        ..Default::default()
+    })
 }
@@ -0,0 +1,305 @@
+use crate::{
+    config::{Arrivals, Config},
+    error::{Error, Result},
+    histogram::Histogram,
+    message::{message::Message, mtype::Type},
+    network::transport::Connection,
+    pacer,
+    phase::{Phase, Phases},
+    random::SplitMix64,
+    results::{LatencyMetrics, Metrics, OpenLoopMetrics, StreamResults},
+    source,
+    unit::*,
+};
+use std::{
+    collections::HashMap,
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+    time::{Duration, Instant},
+};
+use uuid::Uuid;
+
+/// When the requests of an open-loop run are due, relative to its start
+#[derive(Debug, Clone)]
+pub struct Schedule {
+    arrivals: Arrivals,
+    // Mean time between two requests, in seconds
+    period: f64,
+    random: SplitMix64,
+    next: f64,
+}
+
+impl Schedule {
+    /// A schedule of `rate` requests per second on average. The seed only
+    /// matters to Poisson arrivals.
+    pub fn new(arrivals: Arrivals, rate: f64, seed: u64) -> Schedule {
+        Self {
+            arrivals,
+            period: 1.0 / rate,
+            random: SplitMix64::new(seed),
+            next: 0.0,
+        }
+    }
+
+    /// Time the next request is due at, the first one right away
+    pub fn next_due(&mut self) -> Duration {
+        let due = self.next;
+        let gap = match self.arrivals {
+            Arrivals::Constant => self.period,
+            // Inverse transform sampling of the exponential distribution
+            Arrivals::Poisson => -(1.0 - self.random.next_f64()).ln() * self.period,
+        };
+        self.next += gap;
+        Duration::from_secs_f64(due)
+    }
+}
+
+/// A request on its way, as the receiving thread learns of it
+struct Request {
+    id: Uuid,
+    due: Instant,
+    sent: Instant,
+    // Requests due in warm-up or cool-down are answered but not recorded
+    is_measured: bool,
+}
+
+/// What the receiving thread measured
+struct Replies {
+    corrected: Histogram,
+    uncorrected: Histogram,
+    max_outstanding: u64,
+}
+
+/// Sends `Ping` messages when they are due, at the paced rate, while
+/// another handle of the connection matches the acknowledgements to them
+/// by id. A slow reply does not hold the next requests back, and latency is
+/// timed from when a request was due rather than from when it left, so
+/// that the time spent queued behind slow replies is not omitted.
+pub fn measure_open_loop<C: Connection>(
+    config: &Config,
+    connection: &mut C,
+    label: &str,
+) -> Result<StreamResults> {
+    let rate = match config.pacing() {
+        Some(pacing) => pacing.messages_per_second,
+        None => return Err(Error::Config("Open-loop mode requires a rate".into())),
+    };
+    // As for closed-loop latency, an explicit nodelay option is left alone
+    if config.socket_options().nodelay.is_none() {
+        connection.set_nodelay(true)?;
+    }
+    let peer_address = connection.peer();
+    let mut receiving = connection.try_clone()?;
+    let (requests, outstanding) = mpsc::channel();
+
+    let (sent, replies) = thread::scope(|scope| {
+        let receiver = scope.spawn(move || {
+            let replies = receive_replies(&mut receiving, outstanding);
+            // Unblocks the sender, should the server stop reading
+            if replies.is_err() {
+                let _ = receiving.close();
+            }
+            replies
+        });
+        let sent = send_requests(config, connection, rate, requests);
+        // The receiving thread would wait forever for the missing replies
+        if sent.is_err() {
+            let _ = connection.close();
+        }
+        let replies = receiver
+            .join()
+            .unwrap_or_else(|_| Err(Error::Panicked("Receiving".into())));
+        (sent, replies)
+    });
+    let (requests, elapsed, max_send_lag) = sent?;
+    let replies = replies?;
+
+    // Keeps the lines of concurrent streams together
+    let _stdout = std::io::stdout().lock();
+    println!(
+        "{}Open-loop latency ({} message body, {:?} arrivals at {:.0} requests/s), \
+        from when requests were due:",
+        label,
+        data_to_string(config.message_size() as f64),
+        config.arrivals(),
+        rate
+    );
+    replies.corrected.print();
+    let uncorrected = LatencyMetrics::from(&replies.uncorrected);
+    let achieved = match elapsed.as_secs_f64() {
+        elapsed if elapsed > 0.0 => requests as f64 / elapsed,
+        _ => 0.0,
+    };
+    println!(
+        "{}Requests: {}; achieved {:.0} requests/s; at most {} outstanding, {} late",
+        label,
+        requests,
+        achieved,
+        replies.max_outstanding,
+        duration_to_string(max_send_lag.as_nanos() as f64)
+    );
+    println!(
+        "{}Uncorrected, from when requests left: p50: {}; p99: {}; max: {}",
+        label,
+        duration_to_string(uncorrected.p50_nanoseconds as f64),
+        duration_to_string(uncorrected.p99_nanoseconds as f64),
+        duration_to_string(uncorrected.max_nanoseconds as f64)
+    );
+
+    let samples = replies.corrected.count();
+    let body_size = config.message_size() as u64;
+    Ok(StreamResults {
+        peer: peer_address,
+        summary: Metrics::new(Duration::ZERO, elapsed, samples * body_size, samples, None)
+            .with_tcp_info(connection.tcp_info(), None),
+        latency: Some(LatencyMetrics::from(&replies.corrected)),
+        syscalls: connection.syscalls(),
+        open_loop: Some(OpenLoopMetrics {
+            arrivals: config.arrivals(),
+            requests,
+            target_requests_per_second: rate,
+            achieved_requests_per_second: achieved,
+            max_outstanding: replies.max_outstanding,
+            max_send_lag_seconds: max_send_lag.as_secs_f64(),
+            uncorrected_latency: uncorrected,
+        }),
+        ..Default::default()
+    })
+}
+
+/// Sends every request when it is due, or right away when it is late.
+/// Returns how many were sent while measuring, over how long, and how late
+/// the latest of them left: warm-up and cool-down requests do not count.
+fn send_requests<C: Connection>(
+    config: &Config,
+    connection: &mut C,
+    rate: f64,
+    requests: Sender<Request>,
+) -> Result<(u64, Duration, Duration)> {
+    let client_address = source(config, connection);
+    let body_size = config.message_size();
+    let payload = config.payload()?;
+    let number_requests = (config.number_batches() * config.batch_size()) as u64;
+    let mut schedule = Schedule::new(config.arrivals(), rate, Uuid::new_v4().as_u64_pair().0);
+    let phases = config
+        .duration()
+        .map(|duration| Phases::new(config.warmup(), duration, config.cooldown()));
+    let start = Instant::now();
+    let mut sent = 0;
+    let mut measured = 0;
+    let mut max_lag = Duration::ZERO;
+    loop {
+        let offset = schedule.next_due();
+        let phase = match phases {
+            Some(phases) => phases.at(offset),
+            None if sent == number_requests => Phase::Done,
+            None => Phase::Measure,
+        };
+        if phase == Phase::Done {
+            break;
+        }
+        let mut ping = Message::new(client_address, config.server(), body_size, Type::Ping);
+        payload.fill(&mut ping);
+        let due = start + offset;
+        pacer::sleep(due.saturating_duration_since(Instant::now()));
+
+        let now = Instant::now();
+        let is_measured = phase == Phase::Measure;
+        if is_measured {
+            max_lag = max_lag.max(now - due);
+        }
+        // Recorded before it is sent, so that its reply always finds it
+        let request = Request {
+            id: ping.id(),
+            due,
+            sent: now,
+            is_measured,
+        };
+        if requests.send(request).is_err() {
+            // The receiving thread failed: it reports why
+            break;
+        }
+        connection.send(&ping)?;
+        sent += 1;
+        if is_measured {
+            measured += 1;
+        }
+    }
+    let elapsed = match phases {
+        Some(phases) => phases.duration(),
+        None => start.elapsed(),
+    };
+    Ok((measured, elapsed, max_lag))
+}
+
+/// Matches acknowledgements to the requests they answer, until every
+/// request is answered and no more are coming
+fn receive_replies<C: Connection>(
+    connection: &mut C,
+    requests: Receiver<Request>,
+) -> Result<Replies> {
+    let mut outstanding: HashMap<Uuid, Request> = HashMap::new();
+    let mut replies = Replies {
+        corrected: Histogram::new(),
+        uncorrected: Histogram::new(),
+        max_outstanding: 0,
+    };
+    loop {
+        if outstanding.is_empty() {
+            // Nothing to wait for before the next request, if any
+            match requests.recv() {
+                Ok(request) => outstanding.insert(request.id, request),
+                Err(_) => break,
+            };
+        }
+        let reply = connection.receive()?;
+        let received = Instant::now();
+        for request in requests.try_iter() {
+            outstanding.insert(request.id, request);
+        }
+        replies.max_outstanding = replies.max_outstanding.max(outstanding.len() as u64);
+
+        let request = match (reply.mtype(), outstanding.remove(&reply.id())) {
+            (Type::Acknoweldge, Some(request)) => request,
+            _ => {
+                return Err(Error::Protocol(format!(
+                    "expected an acknowledgement of an outstanding request, received {:?} {}",
+                    reply.mtype(),
+                    reply.id()
+                )))
+            }
+        };
+        if request.is_measured {
+            let corrected = received - request.due;
+            let uncorrected = received - request.sent;
+            replies.corrected.record(corrected.as_nanos() as u64);
+            replies.uncorrected.record(uncorrected.as_nanos() as u64);
+        }
+    }
+    Ok(replies)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::Schedule;
+    use crate::config::Arrivals;
+    use std::time::Duration;
+
+    #[test]
+    fn test_schedule() {
+        let mut constant = Schedule::new(Arrivals::Constant, 1000.0, 0);
+        assert_eq!(constant.next_due(), Duration::ZERO);
+        assert_eq!(constant.next_due(), Duration::from_millis(1));
+        assert_eq!(constant.next_due(), Duration::from_millis(2));
+
+        // Poisson arrivals keep the mean rate, with irregular gaps
+        let mut poisson = Schedule::new(Arrivals::Poisson, 1000.0, 42);
+        let dues: Vec<Duration> = (0..10_001).map(|_| poisson.next_due()).collect();
+        let mean = dues[10_000].as_secs_f64() / 10_000.0;
+        assert!((mean - 0.001).abs() < 0.0001);
+        let gaps: Vec<Duration> = dues.windows(2).map(|pair| pair[1] - pair[0]).collect();
+        assert!(gaps.iter().any(|gap| *gap < Duration::from_micros(100)));
+        assert!(gaps.iter().any(|gap| *gap > Duration::from_millis(3)));
+    }
+}
@@ -1,16 +1,20 @@
 use benchmark_network::{
     config::{Config, Role},
+    error::{Error, Result},
     run_client, run_server,
 };
-use std::{env, error::Error};
-pub mod config;
-pub mod unit;
+use std::{env, process};
 
 fn main() {
     let args: Vec<String> = env::args().collect();
     let result = retrieve_args(args);
     match result {
-        Ok(config) => execute(config),
+        Ok(config) => {
+            if let Err(e) = execute(config) {
+                println!("{e}");
+                process::exit(1);
+            }
+        }
         Err(e) => {
             println!("{e}");
             Config::usage();
@@ -23,12 +27,12 @@ fn main() {
 /// Arguments might be of one of two forms
 /// 1. `--arg` such as `--help`
 /// 2. `--arg=value` such as `--message-size=10000`
-pub fn retrieve_args(args: Vec<String>) -> Result<Config, Box<dyn Error>> {
-    let mut config: Config = Config::read_config_file();
+pub fn retrieve_args(args: Vec<String>) -> Result<Config> {
+    let mut config: Config = Config::read_config_file()?;
 
     if args.len() == 1 {
         // If no arguments given, show usage
-        return Err("No arguments provided!")?;
+        return Err(Error::Config("No arguments provided!".into()));
     }
 
     // Skip the first arg (the app name)
@@ -38,27 +42,32 @@ pub fn retrieve_args(args: Vec<String>) -> Result<Config, Box<dyn Error>> {
         if arg.starts_with("--") {
             // Ignore --
             let (_, arg) = arg.split_at(2);
-            let result: Vec<&str> = arg.split(|c| c == '=').collect();
+            let result: Vec<&str> = arg.split('=').collect();
             match result.len() {
                 1 => config.parse_attributes(result[0], None),
                 2 => config.parse_attributes(result[0], Some(result[1])),
-                _ => return Err("Unknown Argument: more than one assignment!")?,
+                _ => Err(Error::Config(
+                    "Unknown Argument: more than one assignment!".into(),
+                )),
             }?;
             config.print();
         } else {
-            return Err("Unknown Argument: args should start with --!")?;
+            return Err(Error::Config(
+                "Unknown Argument: args should start with --!".into(),
+            ));
         }
     }
 
+    config.validate()?;
     Ok(config)
 }
 
-pub fn execute(config: Config) {
+pub fn execute(config: Config) -> Result<()> {
     match config.role() {
         Role::Client => run_client(config),
         Role::Server => {
-            let number_of_clients = config.number_clients();
-            run_server(config, Some(number_of_clients))
+            let is_limited = config.clients_limit();
+            run_server(config, is_limited)
         }
     }
 }
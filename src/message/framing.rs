@@ -0,0 +1,165 @@
+use super::{
+    header::{Header, CHECKSUM_OFFSET, HEADER_SIZE},
+    message::Message,
+    Byte,
+};
+use serde::{Deserialize, Serialize};
+use std::{error::Error, fmt};
+use xxhash_rust::xxh3::Xxh3;
+
+/// Largest body read by default. A corrupted or hostile length is refused
+/// before its body is allocated.
+pub const DEFAULT_MAXIMUM_BODY_SIZE: usize = 256 * 1024 * 1024;
+
+/// Integrity check carried by the header of a message. It covers the
+/// header, up to the checksum itself, and the body.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Checksum {
+    #[default]
+    None,
+    /// CRC-32C (Castagnoli), computed in hardware where available
+    Crc32c,
+    /// 64-bit XXH3, the fastest of the two in software
+    Xxh3,
+}
+
+impl Checksum {
+    /// Code of the checksum on the wire
+    pub fn code(self) -> u8 {
+        match self {
+            Checksum::None => 0,
+            Checksum::Crc32c => 1,
+            Checksum::Xxh3 => 2,
+        }
+    }
+
+    pub fn from_code(code: u8) -> Option<Checksum> {
+        match code {
+            0 => Some(Checksum::None),
+            1 => Some(Checksum::Crc32c),
+            2 => Some(Checksum::Xxh3),
+            _ => None,
+        }
+    }
+
+    /// Checksum of `header` followed by `body`. CRC-32C values are
+    /// zero-extended to 64 bits.
+    pub fn compute(self, header: &[Byte], body: &[Byte]) -> u64 {
+        match self {
+            Checksum::None => 0,
+            Checksum::Crc32c => crc32c::crc32c_append(crc32c::crc32c(header), body) as u64,
+            Checksum::Xxh3 => {
+                let mut hasher = Xxh3::new();
+                hasher.update(header);
+                hasher.update(body);
+                hasher.digest()
+            }
+        }
+    }
+}
+
+/// Errors raised while reading a message whose header decoded fine. The
+/// stream cannot be trusted past them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FramingError {
+    /// The header announces a larger body than the reader accepts
+    BodyTooLarge { size: usize, maximum: usize },
+    /// The message does not match the checksum its header carries
+    ChecksumMismatch {
+        checksum: Checksum,
+        expected: u64,
+        computed: u64,
+    },
+}
+
+impl fmt::Display for FramingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FramingError::BodyTooLarge { size, maximum } => write!(
+                f,
+                "body of {} bytes exceeds the maximum of {} bytes",
+                size, maximum
+            ),
+            FramingError::ChecksumMismatch {
+                checksum,
+                expected,
+                computed,
+            } => write!(
+                f,
+                "{:?} checksum mismatch: header carries {:#018x}, message gives {:#018x}",
+                checksum, expected, computed
+            ),
+        }
+    }
+}
+
+impl Error for FramingError {}
+
+/// How a connection frames messages, besides the header format: the
+/// checksum of the messages it sends, and the largest body it reads.
+/// Received messages are checked against whichever checksum they carry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Framing {
+    pub checksum: Checksum,
+    pub maximum_body_size: usize,
+}
+
+impl Default for Framing {
+    fn default() -> Self {
+        Self {
+            checksum: Checksum::None,
+            maximum_body_size: DEFAULT_MAXIMUM_BODY_SIZE,
+        }
+    }
+}
+
+impl Framing {
+    /// Encodes the header of `msg` in the wire format, with the checksum
+    /// of the whole message
+    pub fn encode(&self, msg: &Message) -> [Byte; HEADER_SIZE] {
+        let mut header = msg.header();
+        header.set_checksum(self.checksum);
+        let mut bytes = header.encode();
+        if self.checksum != Checksum::None {
+            let body: &[Byte] = match msg.body() {
+                Some(body) => body,
+                None => &[],
+            };
+            let value = self.checksum.compute(&bytes[..CHECKSUM_OFFSET], body);
+            bytes[CHECKSUM_OFFSET..].copy_from_slice(&value.to_be_bytes());
+        }
+        bytes
+    }
+
+    /// Refuses a body larger than the maximum, before it is read
+    pub fn check_body_size(&self, header: &Header) -> Result<(), FramingError> {
+        match header.body_size() {
+            size if size > self.maximum_body_size => Err(FramingError::BodyTooLarge {
+                size,
+                maximum: self.maximum_body_size,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Checks a message against the checksum its header carries, if any.
+/// `bytes` is the header as it was read, in the wire format.
+pub fn verify(header: &Header, bytes: &[Byte], body: &[Byte]) -> Result<(), FramingError> {
+    let checksum = header.checksum();
+    if checksum == Checksum::None {
+        return Ok(());
+    }
+    let mut expected = [0u8; 8];
+    expected.copy_from_slice(&bytes[CHECKSUM_OFFSET..HEADER_SIZE]);
+    let expected = u64::from_be_bytes(expected);
+    let computed = checksum.compute(&bytes[..CHECKSUM_OFFSET], body);
+    match computed == expected {
+        true => Ok(()),
+        false => Err(FramingError::ChecksumMismatch {
+            checksum,
+            expected,
+            computed,
+        }),
+    }
+}
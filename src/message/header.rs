@@ -1,7 +1,96 @@
-use super::{mtype::Type, Byte};
-use std::{mem, net::IpAddr};
+use super::{framing::Checksum, mtype::Type, Byte};
+use serde::{Deserialize, Serialize};
+use std::{
+    error::Error,
+    fmt, mem,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    ptr,
+    sync::OnceLock,
+};
 use uuid::Uuid;
 
+/// Magic number opening every encoded header ("BN" in ASCII)
+pub const HEADER_MAGIC: u16 = 0x424E;
+
+/// Version of the header wire format produced by `Header::encode`
+pub const HEADER_VERSION: u8 = 3;
+
+/// Address tags used on the wire to tell IPv4 from IPv6
+const IPV4_TAG: u8 = 4;
+const IPV6_TAG: u8 = 6;
+
+/// An encoded address: one tag byte followed by 16 address bytes.
+/// IPv4 addresses use the first 4 bytes and leave the rest zeroed.
+const ADDRESS_SIZE: usize = 1 + 16;
+
+/// Size in bytes of an encoded header:
+/// magic (2) | version (1) | type (1) | id (16) | source (17) |
+/// destination (17) | body size (8) | sequence (8) | timestamp (8) |
+/// checksum algorithm (1) | checksum (8)
+pub const HEADER_SIZE: usize = 2 + 1 + 1 + 16 + ADDRESS_SIZE + ADDRESS_SIZE + 8 + 8 + 8 + 1 + 8;
+
+/// Offset of the checksum in an encoded header. It covers the bytes before
+/// it, then the body.
+pub const CHECKSUM_OFFSET: usize = HEADER_SIZE - 8;
+
+/// Selects how a header is laid out on the wire
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HeaderFormat {
+    /// Portable, versioned, big-endian encoding (see `Header::encode`)
+    #[default]
+    Wire,
+    /// In-memory layout of `Header`, as sent by versions up to 0.1.1.
+    /// Only works between identical architectures and compilers.
+    Legacy,
+}
+
+/// Errors raised while decoding a header from the wire
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HeaderError {
+    /// Fewer bytes than `HEADER_SIZE` were given
+    Truncated(usize),
+    /// The header does not start with `HEADER_MAGIC`
+    BadMagic(u16),
+    /// The header was encoded with an unknown format version
+    UnsupportedVersion(u8),
+    /// The message type code is unknown
+    UnknownType(u8),
+    /// The address tag is neither IPv4 nor IPv6
+    UnknownAddressTag(u8),
+    /// The body size does not fit in this platform's `usize`
+    BodyTooLarge(u64),
+    /// The checksum algorithm code is unknown
+    UnknownChecksum(u8),
+    /// The legacy layout of this build could not be worked out, so legacy
+    /// headers cannot be checked
+    UnsupportedLegacyLayout,
+}
+
+impl fmt::Display for HeaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HeaderError::Truncated(size) => write!(
+                f,
+                "truncated header: expected {} bytes, got {}",
+                HEADER_SIZE, size
+            ),
+            HeaderError::BadMagic(magic) => write!(f, "bad header magic: {:#06x}", magic),
+            HeaderError::UnsupportedVersion(version) => {
+                write!(f, "unsupported header version: {}", version)
+            }
+            HeaderError::UnknownType(code) => write!(f, "unknown message type: {}", code),
+            HeaderError::UnknownAddressTag(tag) => write!(f, "unknown address tag: {}", tag),
+            HeaderError::BodyTooLarge(size) => write!(f, "body size too large: {}", size),
+            HeaderError::UnknownChecksum(code) => write!(f, "unknown checksum: {}", code),
+            HeaderError::UnsupportedLegacyLayout => {
+                write!(f, "legacy headers are not supported by this build")
+            }
+        }
+    }
+}
+
+impl Error for HeaderError {}
+
 /// Header includes the message metadata: source, destination, type, etc.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Header {
@@ -11,6 +100,27 @@ pub struct Header {
     mtype: Type,
     // Maximum size 4_294_967_295usize
     body_size: usize,
+    // Position of the message in its stream, used to detect loss,
+    // reordering and duplicates on unreliable transports.
+    sequence: u64,
+    // Sender clock when the message was sent, in nanoseconds since the
+    // UNIX epoch. Zero when unset.
+    timestamp: u64,
+    // Algorithm of the checksum following the header on the wire, chosen
+    // by the connection that sends it (see `Framing`)
+    checksum: Checksum,
+}
+
+/// The fields of `Header` as laid out in memory by versions up to 0.1.1.
+/// Kept separate so that adding fields to `Header` does not change the
+/// legacy layout.
+#[derive(Clone, Copy)]
+struct LegacyHeader {
+    id: Uuid,
+    source: IpAddr,
+    destination: IpAddr,
+    mtype: Type,
+    body_size: usize,
 }
 
 impl Header {
@@ -24,6 +134,24 @@ impl Header {
             destination,
             body_size,
             mtype,
+            sequence: 0,
+            timestamp: 0,
+            checksum: Checksum::None,
+        }
+    }
+
+    /// Creates the header of a reply to this header: same id, swapped
+    /// source and destination, no body.
+    pub fn reply(&self, mtype: Type) -> Header {
+        Self {
+            id: self.id,
+            source: self.destination,
+            destination: self.source,
+            body_size: 0,
+            mtype,
+            sequence: self.sequence,
+            timestamp: self.timestamp,
+            checksum: Checksum::None,
         }
     }
 
@@ -47,25 +175,173 @@ impl Header {
         self.body_size
     }
 
-    // TODO: For now, Header works on homogeneous architectures only
-    // Handle heterogeneous by specifying the size of each struct element.
-    // If some architecture for example considers the size of IpAddr
-    // different from another, this leads to inconsistency.
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    pub fn set_sequence(&mut self, sequence: u64) {
+        self.sequence = sequence;
+    }
+
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    pub fn set_timestamp(&mut self, timestamp: u64) {
+        self.timestamp = timestamp;
+    }
+
+    pub fn checksum(&self) -> Checksum {
+        self.checksum
+    }
+
+    pub fn set_checksum(&mut self, checksum: Checksum) {
+        self.checksum = checksum;
+    }
+
+    /// Returns the size of an encoded header on the wire
     pub const fn size() -> usize {
-        mem::size_of::<Header>()
+        HEADER_SIZE
+    }
+
+    /// Returns the size of a header in the legacy (in-memory) layout.
+    /// This size depends on the architecture and the compiler.
+    pub const fn legacy_size() -> usize {
+        mem::size_of::<LegacyHeader>()
     }
 
-    pub unsafe fn to_bytes(&self) -> &[Byte] {
-        ::std::slice::from_raw_parts((self as *const Header) as *const u8, Header::size())
+    /// Encodes the header in the portable wire format, with a zero
+    /// checksum: `Framing::encode` computes it over the whole message.
+    /// All integers are big-endian (network order).
+    pub fn encode(&self) -> [Byte; HEADER_SIZE] {
+        let mut bytes = [0u8; HEADER_SIZE];
+        bytes[0..2].copy_from_slice(&HEADER_MAGIC.to_be_bytes());
+        bytes[2] = HEADER_VERSION;
+        bytes[3] = self.mtype.code();
+        bytes[4..20].copy_from_slice(self.id.as_bytes());
+        encode_address(self.source, &mut bytes[20..37]);
+        encode_address(self.destination, &mut bytes[37..54]);
+        bytes[54..62].copy_from_slice(&(self.body_size as u64).to_be_bytes());
+        bytes[62..70].copy_from_slice(&self.sequence.to_be_bytes());
+        bytes[70..78].copy_from_slice(&self.timestamp.to_be_bytes());
+        bytes[78] = self.checksum.code();
+        bytes
     }
 
-    pub unsafe fn to_mut_bytes(&mut self) -> &mut [Byte] {
-        ::std::slice::from_raw_parts_mut((self as *mut Header) as *mut u8, Header::size())
+    /// Decodes a header encoded with `Header::encode`.
+    /// Only the first `HEADER_SIZE` bytes are read.
+    ///
+    /// # Example
+    /// ```
+    /// # use benchmark_network::message::{header::Header, mtype::Type};
+    /// # use std::net::{IpAddr, Ipv4Addr};
+    /// # const LOCALHOST: IpAddr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+    /// let header = Header::new(LOCALHOST, LOCALHOST, 10, Type::Benchmark);
+    /// let decoded = Header::decode(&header.encode()).expect("Unable to decode header");
+    /// assert_eq!(header, decoded);
+    /// ```
+    pub fn decode(bytes: &[Byte]) -> Result<Header, HeaderError> {
+        if bytes.len() < HEADER_SIZE {
+            return Err(HeaderError::Truncated(bytes.len()));
+        }
+
+        let magic = u16::from_be_bytes([bytes[0], bytes[1]]);
+        if magic != HEADER_MAGIC {
+            return Err(HeaderError::BadMagic(magic));
+        }
+        if bytes[2] != HEADER_VERSION {
+            return Err(HeaderError::UnsupportedVersion(bytes[2]));
+        }
+        let mtype = Type::from_code(bytes[3]).ok_or(HeaderError::UnknownType(bytes[3]))?;
+        let id = Uuid::from_slice(&bytes[4..20]).expect("Slice is exactly 16 bytes");
+        let source = decode_address(&bytes[20..37])?;
+        let destination = decode_address(&bytes[37..54])?;
+        let body_size = decode_u64(&bytes[54..62]);
+        let body_size =
+            usize::try_from(body_size).map_err(|_| HeaderError::BodyTooLarge(body_size))?;
+        let sequence = decode_u64(&bytes[62..70]);
+        let timestamp = decode_u64(&bytes[70..78]);
+        let checksum =
+            Checksum::from_code(bytes[78]).ok_or(HeaderError::UnknownChecksum(bytes[78]))?;
+
+        Ok(Self {
+            id,
+            source,
+            destination,
+            mtype,
+            body_size,
+            sequence,
+            timestamp,
+            checksum,
+        })
+    }
+
+    /// Copies the header in its in-memory layout (legacy format).
+    /// Sequence number and timestamp are not part of that layout.
+    ///
+    /// # Safety
+    /// The returned bytes include padding and are only meaningful to a
+    /// reader built for the same architecture with the same compiler.
+    pub unsafe fn to_bytes(&self) -> Vec<Byte> {
+        let legacy = LegacyHeader {
+            id: self.id,
+            source: self.source,
+            destination: self.destination,
+            mtype: self.mtype,
+            body_size: self.body_size,
+        };
+        ::std::slice::from_raw_parts(
+            (&legacy as *const LegacyHeader) as *const u8,
+            Header::legacy_size(),
+        )
+        .to_vec()
+    }
+
+    /// Decodes a header in its in-memory layout (legacy format), as sent
+    /// by a peer of the same architecture and compiler. The message type
+    /// and the address variants are checked before the header is read, so
+    /// that garbage is an error rather than an invalid value.
+    pub fn decode_legacy(bytes: &[Byte]) -> Result<Header, HeaderError> {
+        if bytes.len() < Header::legacy_size() {
+            return Err(HeaderError::Truncated(bytes.len()));
+        }
+        let code = bytes[mem::offset_of!(LegacyHeader, mtype)];
+        legacy_type(code).ok_or(HeaderError::UnknownType(code))?;
+        let (tag_offset, ipv4_tag, ipv6_tag) =
+            (*legacy_address_tags()).ok_or(HeaderError::UnsupportedLegacyLayout)?;
+        for offset in [
+            mem::offset_of!(LegacyHeader, source),
+            mem::offset_of!(LegacyHeader, destination),
+        ] {
+            let tag = bytes[offset + tag_offset];
+            if tag != ipv4_tag && tag != ipv6_tag {
+                return Err(HeaderError::UnknownAddressTag(tag));
+            }
+        }
+        // Safety: the bytes are initialized and long enough. The type and
+        // the address variants were checked above; any bytes are valid for
+        // the other fields and for the addresses themselves.
+        Ok(unsafe { Header::from_bytes(bytes) })
     }
 
+    /// Rebuilds a header from its in-memory layout (legacy format).
+    ///
+    /// # Safety
+    /// `bytes` must hold at least `Header::legacy_size()` bytes produced by
+    /// `to_bytes` on the same architecture and compiler. Any other input is
+    /// undefined behaviour; prefer `Header::decode_legacy`.
     pub unsafe fn from_bytes(bytes: &[Byte]) -> Header {
-        let p: *const [u8; Header::size()] = bytes.as_ptr() as *const [u8; Header::size()];
-        std::mem::transmute(*p)
+        let legacy: LegacyHeader = std::ptr::read_unaligned(bytes.as_ptr() as *const LegacyHeader);
+        Self {
+            id: legacy.id,
+            source: legacy.source,
+            destination: legacy.destination,
+            mtype: legacy.mtype,
+            body_size: legacy.body_size,
+            sequence: 0,
+            timestamp: 0,
+            checksum: Checksum::None,
+        }
     }
 
     pub fn print(&self) {
@@ -74,5 +350,81 @@ impl Header {
         println!("destination IP: {}", self.destination);
         println!("body_size: {}", self.body_size);
         println!("message type: {:?}", self.mtype);
+        println!("sequence: {}", self.sequence);
+        println!("timestamp: {}", self.timestamp);
+        println!("checksum: {:?}", self.checksum);
+    }
+}
+
+/// The message type whose in-memory representation is `byte`, if any
+fn legacy_type(byte: Byte) -> Option<Type> {
+    if mem::size_of::<Type>() != 1 {
+        return None;
+    }
+    (0..=u8::MAX)
+        .filter_map(Type::from_code)
+        // Safety: a fieldless enum of one byte has no padding
+        .find(|mtype| unsafe { mem::transmute_copy::<Type, Byte>(mtype) } == byte)
+}
+
+/// Offset of the variant tag in the in-memory layout of `IpAddr`, and its
+/// IPv4 and IPv6 values. That layout is not specified: it is learned once
+/// from known addresses.
+fn legacy_address_tags() -> &'static Option<(usize, Byte, Byte)> {
+    static TAGS: OnceLock<Option<(usize, Byte, Byte)>> = OnceLock::new();
+    TAGS.get_or_init(|| {
+        // Only then does an IPv6 address fill every byte of an `IpAddr`
+        if mem::size_of::<IpAddr>() != ADDRESS_SIZE {
+            return None;
+        }
+        // Safety: an IPv6 `IpAddr` of `ADDRESS_SIZE` bytes has no padding
+        let layout = |address: Ipv6Addr| unsafe {
+            mem::transmute_copy::<IpAddr, [Byte; ADDRESS_SIZE]>(&IpAddr::V6(address))
+        };
+        let zeros = layout(Ipv6Addr::UNSPECIFIED);
+        let ones = layout(Ipv6Addr::from([u8::MAX; 16]));
+        // The tag is the one byte both addresses share
+        let mut shared = (0..ADDRESS_SIZE).filter(|&index| zeros[index] == ones[index]);
+        let (Some(offset), None) = (shared.next(), shared.next()) else {
+            return None;
+        };
+        let ipv4 = IpAddr::V4(Ipv4Addr::UNSPECIFIED);
+        // Safety: the tag is at the same offset, and initialized, whatever
+        // the variant
+        let ipv4_tag = unsafe { ptr::read((&ipv4 as *const IpAddr as *const Byte).add(offset)) };
+        (ipv4_tag != zeros[offset]).then_some((offset, ipv4_tag, zeros[offset]))
+    })
+}
+
+fn decode_u64(bytes: &[Byte]) -> u64 {
+    let mut buffer = [0u8; 8];
+    buffer.copy_from_slice(bytes);
+    u64::from_be_bytes(buffer)
+}
+
+fn encode_address(address: IpAddr, bytes: &mut [Byte]) {
+    match address {
+        IpAddr::V4(address) => {
+            bytes[0] = IPV4_TAG;
+            bytes[1..5].copy_from_slice(&address.octets());
+        }
+        IpAddr::V6(address) => {
+            bytes[0] = IPV6_TAG;
+            bytes[1..17].copy_from_slice(&address.octets());
+        }
+    }
+}
+
+fn decode_address(bytes: &[Byte]) -> Result<IpAddr, HeaderError> {
+    match bytes[0] {
+        IPV4_TAG => Ok(IpAddr::V4(Ipv4Addr::new(
+            bytes[1], bytes[2], bytes[3], bytes[4],
+        ))),
+        IPV6_TAG => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(&bytes[1..17]);
+            Ok(IpAddr::V6(Ipv6Addr::from(octets)))
+        }
+        tag => Err(HeaderError::UnknownAddressTag(tag)),
     }
 }
@@ -34,11 +34,25 @@ impl Message {
         Self { header, body }
     }
 
+    /// Creates a reply to this message (an Acknowledge for instance). The
+    /// reply carries the same message id and has no body.
+    pub fn reply(&self, mtype: Type) -> Message {
+        Self {
+            header: self.header.reply(mtype),
+            body: None,
+        }
+    }
+
     /// Returns the message Header
     pub fn header(&self) -> Header {
         self.header
     }
 
+    /// Returns the message Header as mutable, e.g. to stamp a sequence number
+    pub fn header_as_mut(&mut self) -> &mut Header {
+        &mut self.header
+    }
+
     /// Returns the message id
     pub fn id(&self) -> Uuid {
         self.header.id()
@@ -65,6 +79,7 @@ impl Message {
     }
 
     /// Returns the message body. This is an optional field of the message
+    #[allow(clippy::borrowed_box)]
     pub fn body(&self) -> Option<&Box<Vec<Byte>>> {
         self.body.as_ref()
     }
@@ -76,15 +91,15 @@ impl Message {
 
     /// Checks if a message has a body or only a header
     pub fn has_body(&self) -> bool {
-        matches!(self.body, Some(_))
+        self.body.is_some()
     }
 
     /// Checks if the message has no body (only a header)
     pub fn without_body(&self) -> bool {
-        matches!(self.body, None)
+        self.body.is_none()
     }
 
-    /// Returns the Header size
+    /// Returns the Header size on the wire
     pub fn header_size() -> usize {
         Header::size()
     }
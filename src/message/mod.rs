@@ -1,10 +1,14 @@
 pub mod body;
+pub mod framing;
 pub mod header;
+#[allow(clippy::module_inception)]
 pub mod message;
 pub mod mtype;
+pub mod payload;
 
 pub type Byte = u8;
 pub const EMPTY_BYTE: Byte = u8::MIN;
 
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests;
@@ -13,4 +13,44 @@ pub enum Type {
     PreCommit,
     Propose,
     Vote,
+    /// Refuses a proposal, with the reason in the body
+    Reject,
+    /// What one side measured, sent to the other at the end of a run
+    Report,
+}
+
+impl Type {
+    /// Returns the code used to represent the Type on the wire.
+    /// Codes are fixed and must never be reassigned.
+    pub fn code(&self) -> u8 {
+        match self {
+            Type::Acknoweldge => 0,
+            Type::Benchmark => 1,
+            Type::Close => 2,
+            Type::Commit => 3,
+            Type::Ping => 4,
+            Type::PreCommit => 5,
+            Type::Propose => 6,
+            Type::Vote => 7,
+            Type::Reject => 8,
+            Type::Report => 9,
+        }
+    }
+
+    /// Returns the Type represented by a wire code, if any
+    pub fn from_code(code: u8) -> Option<Type> {
+        match code {
+            0 => Some(Type::Acknoweldge),
+            1 => Some(Type::Benchmark),
+            2 => Some(Type::Close),
+            3 => Some(Type::Commit),
+            4 => Some(Type::Ping),
+            5 => Some(Type::PreCommit),
+            6 => Some(Type::Propose),
+            7 => Some(Type::Vote),
+            8 => Some(Type::Reject),
+            9 => Some(Type::Report),
+            _ => None,
+        }
+    }
 }
@@ -0,0 +1,177 @@
+use super::{header::Header, message::Message, mtype::Type, Byte, EMPTY_BYTE};
+use crate::random::SplitMix64;
+use crate::results::VerificationMetrics;
+use serde::{Deserialize, Serialize};
+
+/// Largest content of the file pattern. It is repeated over bodies of any
+/// size, and travels to the server in the proposal.
+pub const MAXIMUM_PATTERN_SIZE: usize = 64 * 1024;
+
+// Corrupted messages whose first mismatch is reported, the first ones
+const MAXIMUM_OFFSETS: usize = 16;
+
+/// What message bodies are filled with. The random and counter patterns
+/// depend on the message id and sequence number, so that no two bodies of
+/// a stream are alike, each message can be checked on its own, and a body
+/// delivered under the wrong header does not pass.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Pattern {
+    /// Zero bytes, as `Message::new` leaves them. Compresses to nothing on
+    /// a compressing link.
+    #[default]
+    Zeros,
+    /// SplitMix64 output seeded from the message header; does not compress
+    Random,
+    /// Big-endian 64-bit words counting up from a value taken from the
+    /// message header
+    Counter,
+    /// The content of a file, repeated over the body
+    File,
+}
+
+/// A pattern with what the receiver needs to generate it again
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+pub struct Payload {
+    pub pattern: Pattern,
+    /// Repeated over the body by the file pattern, empty otherwise
+    #[serde(default)]
+    pub content: Vec<Byte>,
+}
+
+impl Payload {
+    pub fn new(pattern: Pattern, content: Vec<Byte>) -> Payload {
+        Self { pattern, content }
+    }
+
+    /// Fills the body of `msg`, if any, with the pattern of its header
+    pub fn fill(&self, msg: &mut Message) {
+        let header = msg.header();
+        if let Some(body) = msg.body_as_mut() {
+            self.generate(&header, body);
+        }
+    }
+
+    /// Whether bodies change with their header, and must be filled again
+    /// for every message
+    pub fn varies(&self) -> bool {
+        matches!(self.pattern, Pattern::Random | Pattern::Counter)
+    }
+
+    /// Numbers `msg`, sent over and over, as message `sequence` of its
+    /// stream, and fills its body again when the pattern depends on it
+    pub fn refill(&self, msg: &mut Message, sequence: u64) {
+        msg.header_as_mut().set_sequence(sequence);
+        if self.varies() {
+            self.fill(msg);
+        }
+    }
+
+    /// Writes the pattern of the message with `header` to `body`
+    fn generate(&self, header: &Header, body: &mut [Byte]) {
+        let seed = seed(header);
+        match self.pattern {
+            Pattern::Zeros => body.fill(EMPTY_BYTE),
+            Pattern::Random => {
+                let mut random = SplitMix64::new(seed);
+                for chunk in body.chunks_mut(8) {
+                    let word = random.next_u64().to_be_bytes();
+                    chunk.copy_from_slice(&word[..chunk.len()]);
+                }
+            }
+            Pattern::Counter => {
+                for (index, chunk) in body.chunks_mut(8).enumerate() {
+                    let word = seed.wrapping_add(index as u64).to_be_bytes();
+                    chunk.copy_from_slice(&word[..chunk.len()]);
+                }
+            }
+            Pattern::File => {
+                for (byte, value) in body.iter_mut().zip(self.content.iter().cycle()) {
+                    *byte = *value;
+                }
+            }
+        }
+    }
+}
+
+/// Where the patterns of a message start, from its id and sequence number.
+/// Consecutive sequence numbers are spread over the whole range.
+fn seed(header: &Header) -> u64 {
+    let (high, low) = header.id().as_u64_pair();
+    high ^ low ^ header.sequence().wrapping_mul(0x9E37_79B9_7F4A_7C15)
+}
+
+/// Checks the bodies of received messages against the pattern they were
+/// sent with, generated again from their header
+#[derive(Debug, Clone, Default)]
+pub struct Verifier {
+    payload: Payload,
+    // Expected body, reused from one message to the next
+    expected: Vec<Byte>,
+    metrics: VerificationMetrics,
+}
+
+impl Verifier {
+    pub fn new(payload: Payload) -> Verifier {
+        Self {
+            metrics: VerificationMetrics {
+                pattern: payload.pattern,
+                ..Default::default()
+            },
+            payload,
+            expected: Vec::new(),
+        }
+    }
+
+    /// Checks the body of a `Benchmark` or `Ping` message. Control
+    /// messages carry their own content and are left alone.
+    pub fn verify(&mut self, msg: &Message) {
+        if !matches!(msg.mtype(), Type::Benchmark | Type::Ping) {
+            return;
+        }
+        let Some(body) = msg.body() else {
+            return;
+        };
+        self.expected.resize(body.len(), EMPTY_BYTE);
+        self.payload.generate(&msg.header(), &mut self.expected);
+        self.metrics.messages += 1;
+        if **body == self.expected {
+            return;
+        }
+
+        let mut mismatches = body
+            .iter()
+            .zip(&self.expected)
+            .enumerate()
+            .filter(|(_, (received, expected))| received != expected)
+            .map(|(offset, _)| offset as u64);
+        if let Some(first) = mismatches.next() {
+            self.metrics.corrupted_messages += 1;
+            self.metrics.corrupted_bytes += 1 + mismatches.count() as u64;
+            if self.metrics.mismatch_offsets.len() < MAXIMUM_OFFSETS {
+                self.metrics.mismatch_offsets.push(first);
+            }
+        }
+    }
+
+    pub fn metrics(&self) -> VerificationMetrics {
+        self.metrics.clone()
+    }
+}
+
+/// Prints how many bodies were checked and how many of them were corrupted
+pub fn print_verification(label: &str, verification: &VerificationMetrics) {
+    println!(
+        "{}Verified {} bodies ({:?} pattern): {} corrupted, {} bytes",
+        label,
+        verification.messages,
+        verification.pattern,
+        verification.corrupted_messages,
+        verification.corrupted_bytes
+    );
+    if !verification.mismatch_offsets.is_empty() {
+        println!(
+            "{}First mismatch of the first corrupted bodies at offsets {:?}",
+            label, verification.mismatch_offsets
+        );
+    }
+}
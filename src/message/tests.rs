@@ -1,7 +1,11 @@
 mod tests {
-    use crate::message::{header::Header, message::Message, mtype::Type};
+    use crate::message::{
+        header::{Header, HeaderError},
+        message::Message,
+        mtype::Type,
+    };
     use std::{
-        net::{IpAddr, Ipv4Addr},
+        net::{IpAddr, Ipv4Addr, Ipv6Addr},
         ops::DerefMut,
     };
 
@@ -44,24 +48,239 @@ mod tests {
         assert_eq!(**(msg_clone.body().unwrap()), vec![EMPTY_BYTE; BODY_SIZE]);
     }
 
+    #[test]
+    fn test_reply_message() {
+        let msg = Message::new(
+            SOURCE,
+            IpAddr::V6(Ipv6Addr::LOCALHOST),
+            BODY_SIZE,
+            Type::Ping,
+        );
+        let reply = msg.reply(Type::Acknoweldge);
+        assert_eq!(reply.id(), msg.id());
+        assert_eq!(reply.source(), msg.destination());
+        assert_eq!(reply.destination(), msg.source());
+        assert_eq!(reply.mtype(), Type::Acknoweldge);
+        assert_eq!(reply.body_size(), 0);
+        assert!(reply.without_body());
+    }
+
     #[test]
     fn test_message_serialization() {
-        // Serialize and Deserialize Header
+        // Encode and Decode Header
         let msg = Message::new(SOURCE, DESTINATION, BODY_SIZE, MTYPE);
         let header = msg.header();
-        let bytes = unsafe { header.to_bytes() };
-        assert_eq!(bytes[25..29], [127u8, 0u8, 0u8, 1u8]);
-        let deserialized_header = unsafe { Header::from_bytes(&bytes) };
-        assert_eq!(header, deserialized_header);
+        let bytes = header.encode();
+        assert_eq!(bytes.len(), Header::size());
+        assert_eq!(bytes[20], 4u8);
+        assert_eq!(bytes[21..25], [127u8, 0u8, 0u8, 1u8]);
+        assert_eq!(bytes[54..62], (BODY_SIZE as u64).to_be_bytes());
+        assert_eq!(bytes[62..78], [EMPTY_BYTE; 16]);
+        // No checksum unless the framing sets one
+        assert_eq!(bytes[78..], [EMPTY_BYTE; 9]);
+        let decoded_header = Header::decode(&bytes).expect("Unable to decode header");
+        assert_eq!(header, decoded_header);
 
-        // Serialize, modify source IP and Deserialize
-        let msg = Message::new(SOURCE, DESTINATION, BODY_SIZE, MTYPE);
+        // Encode, modify source IP and Decode
+        let mut bytes = header.encode();
+        bytes[24] = FULL_BYTE;
+        let decoded_header = Header::decode(&bytes).expect("Unable to decode header");
+        assert_eq!(decoded_header.source().to_string(), "127.0.0.255");
+
+        // Sequence number and timestamp survive the round trip
         let mut header = msg.header();
-        let bytes = unsafe { header.to_mut_bytes() };
-        assert_eq!(bytes[25..29], [127u8, 0u8, 0u8, 1u8]);
-        bytes[28] = FULL_BYTE;
+        header.set_sequence(u64::MAX - 1);
+        header.set_timestamp(42);
+        let decoded_header = Header::decode(&header.encode()).expect("Unable to decode header");
+        assert_eq!(decoded_header.sequence(), u64::MAX - 1);
+        assert_eq!(decoded_header.timestamp(), 42);
+
+        // IPv6 addresses are tagged and kept whole
+        let header = Header::new(SOURCE, IpAddr::V6(Ipv6Addr::LOCALHOST), 0, Type::Close);
+        let bytes = header.encode();
+        assert_eq!(bytes[37], 6u8);
+        assert_eq!(Header::decode(&bytes), Ok(header));
+    }
+
+    #[test]
+    fn test_header_decode_errors() {
+        let header = Header::new(SOURCE, DESTINATION, BODY_SIZE, MTYPE);
+        let bytes = header.encode();
+
+        assert_eq!(
+            Header::decode(&bytes[..10]),
+            Err(HeaderError::Truncated(10))
+        );
+
+        let mut corrupted = bytes;
+        corrupted[0] = EMPTY_BYTE;
+        assert!(matches!(
+            Header::decode(&corrupted),
+            Err(HeaderError::BadMagic(_))
+        ));
+
+        let mut corrupted = bytes;
+        corrupted[2] = FULL_BYTE;
+        assert_eq!(
+            Header::decode(&corrupted),
+            Err(HeaderError::UnsupportedVersion(FULL_BYTE))
+        );
+
+        let mut corrupted = bytes;
+        corrupted[3] = FULL_BYTE;
+        assert_eq!(
+            Header::decode(&corrupted),
+            Err(HeaderError::UnknownType(FULL_BYTE))
+        );
+
+        let mut corrupted = bytes;
+        corrupted[37] = 5u8;
+        assert_eq!(
+            Header::decode(&corrupted),
+            Err(HeaderError::UnknownAddressTag(5u8))
+        );
+
+        let mut corrupted = bytes;
+        corrupted[78] = FULL_BYTE;
+        assert_eq!(
+            Header::decode(&corrupted),
+            Err(HeaderError::UnknownChecksum(FULL_BYTE))
+        );
+    }
+
+    #[test]
+    fn test_checksum() {
+        use crate::message::framing::{self, Checksum, Framing, FramingError};
+
+        let msg = Message::new(SOURCE, DESTINATION, BODY_SIZE, MTYPE);
+        for checksum in [Checksum::Crc32c, Checksum::Xxh3] {
+            let framing = Framing {
+                checksum,
+                ..Default::default()
+            };
+            let bytes = framing.encode(&msg);
+            let header = Header::decode(&bytes).expect("Unable to decode header");
+            assert_eq!(header.checksum(), checksum);
+            let body = msg.body().unwrap();
+            assert_eq!(framing::verify(&header, &bytes, body), Ok(()));
+
+            // A flipped bit in the body or in the header is caught
+            let mut corrupted = (**body).clone();
+            corrupted[0] ^= 1;
+            assert!(matches!(
+                framing::verify(&header, &bytes, &corrupted),
+                Err(FramingError::ChecksumMismatch { checksum: c, .. }) if c == checksum
+            ));
+            let mut corrupted = bytes;
+            corrupted[60] ^= 1;
+            let header = Header::decode(&corrupted).expect("Unable to decode header");
+            assert!(framing::verify(&header, &corrupted, body).is_err());
+        }
+
+        // CRC-32C check value of the standard "123456789" input
+        assert_eq!(Checksum::Crc32c.compute(b"1234", b"56789"), 0xe3069283);
+
+        let framing = Framing {
+            maximum_body_size: BODY_SIZE - 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            framing.check_body_size(&msg.header()),
+            Err(FramingError::BodyTooLarge {
+                size: BODY_SIZE,
+                maximum: BODY_SIZE - 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_legacy_serialization() {
+        // Legacy layout round trip, on the same architecture only
+        let msg = Message::new(SOURCE, DESTINATION, BODY_SIZE, MTYPE);
+        let header = msg.header();
+        let bytes = unsafe { header.to_bytes() };
+        assert_eq!(bytes.len(), Header::legacy_size());
         let deserialized_header = unsafe { Header::from_bytes(&bytes) };
         assert_eq!(header, deserialized_header);
-        assert_eq!(deserialized_header.source().to_string(), "127.0.0.255");
+        assert_eq!(Header::decode_legacy(&bytes), Ok(header));
+        let v6 = Header::new(SOURCE, IpAddr::V6(Ipv6Addr::LOCALHOST), 0, Type::Close);
+        assert_eq!(Header::decode_legacy(&unsafe { v6.to_bytes() }), Ok(v6));
+
+        // Garbage is refused instead of read as an invalid value
+        assert_eq!(
+            Header::decode_legacy(&bytes[..10]),
+            Err(HeaderError::Truncated(10))
+        );
+        let garbage = vec![FULL_BYTE; Header::legacy_size()];
+        assert!(matches!(
+            Header::decode_legacy(&garbage),
+            Err(HeaderError::UnknownType(_) | HeaderError::UnknownAddressTag(_))
+        ));
+    }
+
+    #[test]
+    fn test_payload() {
+        use crate::message::payload::{Pattern, Payload, Verifier};
+
+        let file = Payload::new(Pattern::File, vec![1, 2, 3]);
+        let mut msg = Message::new(SOURCE, DESTINATION, BODY_SIZE, MTYPE);
+        file.fill(&mut msg);
+        assert_eq!(**msg.body().unwrap(), vec![1, 2, 3, 1, 2]);
+
+        // Counter words start from the id, so they differ between messages
+        let counter = Payload::new(Pattern::Counter, Vec::new());
+        let mut msg = Message::new(SOURCE, DESTINATION, 16, MTYPE);
+        counter.fill(&mut msg);
+        let body = msg.body().unwrap();
+        let first = u64::from_be_bytes(body[0..8].try_into().unwrap());
+        let second = u64::from_be_bytes(body[8..16].try_into().unwrap());
+        assert_eq!(second, first.wrapping_add(1));
+
+        for pattern in [Pattern::Zeros, Pattern::Random, Pattern::Counter] {
+            let payload = Payload::new(pattern, Vec::new());
+            let mut verifier = Verifier::new(payload.clone());
+            let mut msg = Message::new(SOURCE, DESTINATION, 100, MTYPE);
+            payload.fill(&mut msg);
+            verifier.verify(&msg);
+            msg.body_as_mut().unwrap().deref_mut()[42] ^= FULL_BYTE;
+            verifier.verify(&msg);
+            // Control messages are not checked
+            verifier.verify(&Message::new(SOURCE, DESTINATION, 100, Type::Report));
+
+            let metrics = verifier.metrics();
+            assert_eq!(metrics.pattern, pattern);
+            assert_eq!(metrics.messages, 2);
+            assert_eq!(metrics.corrupted_messages, 1);
+            assert_eq!(metrics.corrupted_bytes, 1);
+            assert_eq!(metrics.mismatch_offsets, vec![42]);
+        }
+
+        // A body delivered under another header does not pass
+        let random = Payload::new(Pattern::Random, Vec::new());
+        let mut verifier = Verifier::new(random.clone());
+        let mut msg = Message::new(SOURCE, DESTINATION, 100, MTYPE);
+        random.fill(&mut msg);
+        let other = Message::compose(
+            Message::new(SOURCE, DESTINATION, 100, MTYPE).header(),
+            msg.body().cloned(),
+        );
+        verifier.verify(&other);
+        assert_eq!(verifier.metrics().corrupted_messages, 1);
+
+        // A message sent again gets a new body for every sequence number
+        for pattern in [Pattern::Random, Pattern::Counter] {
+            let payload = Payload::new(pattern, Vec::new());
+            let mut verifier = Verifier::new(payload.clone());
+            let mut msg = Message::new(SOURCE, DESTINATION, 100, MTYPE);
+            payload.refill(&mut msg, 0);
+            let first = msg.body().cloned();
+            verifier.verify(&msg);
+            payload.refill(&mut msg, 1);
+            assert_eq!(msg.header().sequence(), 1);
+            assert_ne!(msg.body().cloned(), first);
+            verifier.verify(&msg);
+            assert_eq!(verifier.metrics().corrupted_messages, 0);
+        }
+        assert!(!Payload::new(Pattern::Zeros, Vec::new()).varies());
     }
 }
@@ -0,0 +1,183 @@
+use crate::{
+    config::Config,
+    interval::{IntervalReporter, Sampler},
+    pacer::{print_pacing, Pacer},
+    phase::{Phase, Phases},
+    print_throughput,
+    results::{Metrics, StreamResults, TcpInfoMetrics},
+};
+use std::time::{Duration, Instant};
+
+/// Bookkeeping of a throughput run, whatever sends the messages: tells the
+/// sender which phase it is in and accounts for every message sent, per
+/// batch or per interval. The sending loop is then
+/// `while let Some(phase) = meter.next_phase() { send; meter.record(phase, ..) }`.
+/// A paced sender also waits for `delay` before each send.
+#[derive(Debug)]
+pub struct ThroughputMeter {
+    label: String,
+    body_size: usize,
+    batch_size: usize,
+    number_batches: usize,
+    // Without a duration, every message is measured
+    phases: Option<Phases>,
+    reporter: Option<IntervalReporter>,
+    pacer: Option<Pacer>,
+    // Results are timed from the first measured message
+    measure_start: Option<Instant>,
+    intervals: Vec<Metrics>,
+    batch_start: Instant,
+    batch_messages: usize,
+    sent_batches: usize,
+    measured_messages: usize,
+    // Sample of the end of the previous batch
+    tcp_info: Option<TcpInfoMetrics>,
+}
+
+impl ThroughputMeter {
+    /// Starts the clock of a run of `config`, printing lines prefixed with
+    /// `label`
+    pub fn new(config: &Config, label: &str) -> ThroughputMeter {
+        let phases = config
+            .duration()
+            .map(|duration| Phases::new(config.warmup(), duration, config.cooldown()));
+        Self {
+            label: label.to_string(),
+            body_size: config.message_size(),
+            batch_size: config.batch_size(),
+            number_batches: config.number_batches(),
+            phases,
+            reporter: config.interval().map(|interval| {
+                IntervalReporter::new(interval)
+                    .with_label(label)
+                    .with_deadline(phases.and_then(|phases| phases.measure_end()))
+            }),
+            pacer: config.pacing().map(Pacer::new),
+            measure_start: None,
+            intervals: Vec::new(),
+            batch_start: Instant::now(),
+            batch_messages: 0,
+            sent_batches: 0,
+            measured_messages: 0,
+            tcp_info: None,
+        }
+    }
+
+    /// Returns the phase the next message is sent in, or None once the run
+    /// is over
+    pub fn next_phase(&mut self) -> Option<Phase> {
+        let phase = match self.phases {
+            Some(phases) => phases.current(),
+            None if self.sent_batches == self.number_batches => Phase::Done,
+            None => Phase::Measure,
+        };
+        if phase == Phase::Measure && self.batch_messages == 0 {
+            self.batch_start = Instant::now();
+        }
+        match phase {
+            Phase::Done => None,
+            phase => Some(phase),
+        }
+    }
+
+    /// Samples the connection for interval reports, from the thread that
+    /// prints them
+    pub fn with_tcp_info(mut self, sampler: Option<Sampler>) -> ThroughputMeter {
+        self.reporter = self
+            .reporter
+            .map(|reporter| reporter.with_tcp_info(sampler));
+        self
+    }
+
+    /// How long to wait before sending the next message, on paced runs
+    pub fn delay(&mut self) -> Option<Duration> {
+        self.pacer.as_mut().map(Pacer::delay)
+    }
+
+    /// Accounts for a message sent in `phase`. `tcp_info` samples the
+    /// connection, it is only called when a batch is reported.
+    pub fn record<F: Fn() -> Option<TcpInfoMetrics>>(&mut self, phase: Phase, tcp_info: F) {
+        // Warm-up and cool-down traffic is left out
+        if phase != Phase::Measure {
+            return;
+        }
+        let start = *self.measure_start.get_or_insert(self.batch_start);
+        self.measured_messages += 1;
+        self.batch_messages += 1;
+        if let Some(reporter) = self.reporter.as_mut() {
+            reporter.record(self.body_size);
+        }
+        if self.batch_messages == self.batch_size {
+            if self.reporter.is_none() {
+                let sent_bytes = self.body_size * self.batch_messages;
+                let now = Instant::now();
+                let elapsed = (now - self.batch_start).as_secs_f64();
+                print_throughput(&self.label, sent_bytes as f64, elapsed);
+                let tcp_info = tcp_info();
+                let metrics = Metrics::new(
+                    self.batch_start - start,
+                    now - start,
+                    sent_bytes as u64,
+                    self.batch_messages as u64,
+                    None,
+                )
+                .with_tcp_info(tcp_info, self.tcp_info.as_ref());
+                print_tcp_info(&self.label, &metrics);
+                self.intervals.push(metrics);
+                self.tcp_info = tcp_info;
+            }
+            self.batch_messages = 0;
+            self.sent_batches += 1;
+        }
+    }
+
+    /// Prints the summary of the run and returns its results. `tcp_info`
+    /// is a sample of the connection at the end of the run.
+    pub fn finish(
+        mut self,
+        peer_address: String,
+        tcp_info: Option<TcpInfoMetrics>,
+    ) -> StreamResults {
+        let label = &self.label;
+        let sent_bytes = (self.body_size * self.measured_messages) as u64;
+        let summary = if let Some(reporter) = self.reporter.as_mut() {
+            let summary = reporter.finish(tcp_info);
+            self.intervals = reporter.intervals().to_vec();
+            summary
+        } else if let Some(phases) = self.phases {
+            let elapsed = phases.duration().as_secs_f64();
+            println!("{}Measured over {:.2}s:", label, elapsed);
+            print_throughput(label, sent_bytes as f64, elapsed);
+            let summary = Metrics::new(
+                Duration::ZERO,
+                phases.duration(),
+                sent_bytes,
+                self.measured_messages as u64,
+                None,
+            )
+            .with_tcp_info(tcp_info, None);
+            print_tcp_info(label, &summary);
+            summary
+        } else {
+            Metrics::aggregate(&self.intervals).with_tcp_info(tcp_info, None)
+        };
+        let pacing = self.pacer.map(|pacer| pacer.finish(self.body_size));
+        if let Some(pacing) = &pacing {
+            print_pacing(label, pacing);
+        }
+        StreamResults {
+            peer: peer_address,
+            intervals: self.intervals,
+            summary,
+            pacing,
+            ..Default::default()
+        }
+    }
+}
+
+/// Prints the kernel view of the connection under a throughput line
+fn print_tcp_info(label: &str, metrics: &Metrics) {
+    if let (Some(tcp_info), Some(retransmits)) = (&metrics.tcp_info, metrics.retransmits) {
+        println!("{}{}; Retransmits: {}", label, tcp_info, retransmits);
+    }
+}
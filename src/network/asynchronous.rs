@@ -0,0 +1,543 @@
+use super::{
+    control::{self, print_combined, Proposal, Report},
+    socket::{self, print_settings},
+    stats::ReceiverStats,
+    util,
+};
+use crate::{
+    check_acknowledgement, check_sent,
+    config::{Config, Direction, Mode, Role},
+    error::{Error, Result},
+    histogram::Histogram,
+    interval::{IntervalReporter, Sampler},
+    message::{
+        framing::{self, Framing},
+        header::{Header, HeaderFormat},
+        message::Message,
+        mtype::Type,
+        Byte,
+    },
+    meter::ThroughputMeter,
+    pacer::{print_pacing, Pacer},
+    phase::{Phase, Phases},
+    print_summary,
+    results::{LatencyMetrics, Metrics, StreamResults, TcpInfoMetrics},
+    unit::*,
+};
+use socket2::SockRef;
+use std::{
+    io,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::{TcpListener, TcpSocket, TcpStream},
+    runtime::{Builder, Runtime},
+    task::JoinSet,
+};
+
+/// Async counterpart of `io::send`: writes the encoded header, then the body.
+/// Returns the body size.
+pub async fn send<W: AsyncWrite + Unpin>(
+    stream: &mut W,
+    msg: &Message,
+    framing: &Framing,
+) -> Result<usize> {
+    stream.write_all(&framing.encode(msg)).await?;
+    match msg.body() {
+        Some(body) => {
+            stream.write_all(body).await?;
+            Ok(body.len())
+        }
+        None => Ok(0),
+    }
+}
+
+/// Async counterpart of `io::read_with`
+pub async fn read<R: AsyncRead + Unpin>(
+    stream: &mut R,
+    format: HeaderFormat,
+    framing: &Framing,
+) -> Result<Message> {
+    let mut header_buffer = [0u8; Header::size()];
+    let header = match format {
+        HeaderFormat::Wire => {
+            stream.read_exact(&mut header_buffer).await?;
+            Header::decode(&header_buffer)?
+        }
+        HeaderFormat::Legacy => {
+            let mut header_buffer = [0u8; Header::legacy_size()];
+            stream.read_exact(&mut header_buffer).await?;
+            Header::decode_legacy(&header_buffer)?
+        }
+    };
+    framing.check_body_size(&header)?;
+
+    let body: Option<Box<Vec<Byte>>> = match header.body_size() {
+        0 => None,
+        _ => {
+            let mut buffer: Vec<Byte> = vec![0u8; header.body_size()];
+            stream.read_exact(&mut buffer).await?;
+            Some(Box::new(buffer))
+        }
+    };
+    // Legacy headers carry no checksum
+    let body_bytes: &[Byte] = body.as_deref().map_or(&[], |body| body);
+    framing::verify(&header, &header_buffer, body_bytes)?;
+
+    Ok(Message::compose(header, body))
+}
+
+/// Samples the kernel statistics of a connection, on Linux
+fn tcp_info(stream: &TcpStream) -> Option<TcpInfoMetrics> {
+    #[cfg(target_os = "linux")]
+    return super::tcp_info::sample(stream).ok();
+    #[cfg(not(target_os = "linux"))]
+    None
+}
+
+/// Samples `stream` from the reporting thread of interval reports
+fn tcp_info_sampler(stream: &TcpStream) -> Option<Sampler> {
+    #[cfg(target_os = "linux")]
+    return super::tcp_info::sampler(stream);
+    #[cfg(not(target_os = "linux"))]
+    None
+}
+
+/// Builds a runtime of `workers` threads, one per CPU by default
+fn runtime(workers: Option<usize>) -> io::Result<Runtime> {
+    let mut builder = Builder::new_multi_thread();
+    if let Some(workers) = workers {
+        builder.worker_threads(workers);
+    }
+    builder.enable_all().build()
+}
+
+/// Runs the async server: every connection is a task, so thousands of
+/// clients are served by a handful of threads
+pub fn run_server(config: &Config, is_limited: Option<usize>) -> Result<Vec<StreamResults>> {
+    config.validate()?;
+    let runtime = runtime(config.workers())?;
+    runtime.block_on(async {
+        let address = SocketAddr::new(config.bind_address(), config.port());
+        let listener = config.socket_options().listen(address)?;
+        listener.set_nonblocking(true)?;
+        let listener = TcpListener::from_std(listener)?;
+        let format = config.header_format();
+        Ok(serve(
+            listener,
+            format,
+            config.framing(),
+            is_limited,
+            config.interval(),
+        )
+        .await)
+    })
+}
+
+/// Accepts clients and handles each one in its own task. Stops accepting
+/// after `is_limited` connections, then waits for all of them to close.
+/// Returns the results of every connection.
+pub async fn serve(
+    listener: TcpListener,
+    format: HeaderFormat,
+    framing: Framing,
+    is_limited: Option<usize>,
+    interval: Option<Duration>,
+) -> Vec<StreamResults> {
+    match listener.local_addr() {
+        Ok(address) => println!("Async server listening on {}", address),
+        Err(_) => println!("Async server listening"),
+    }
+
+    let mut tasks = JoinSet::new();
+    let mut results = Vec::new();
+    let mut accepted = 0;
+    while is_limited.is_none_or(|limit| accepted < limit) {
+        match listener.accept().await {
+            Ok((stream, peer_address)) => {
+                let peer_address = util::canonical(peer_address).to_string();
+                println!("New connection: {}", peer_address);
+                if let Ok(socket) = socket::settings(SockRef::from(&stream)) {
+                    print_settings("", &socket);
+                }
+                tasks.spawn(async move {
+                    let stats = handle_client(stream, format, framing, interval).await;
+                    stats.to_streams(&peer_address)
+                });
+                accepted += 1;
+            }
+            Err(e) => println!("Connection failed: {}", e),
+        }
+        // Collects finished connections, so an unlimited server does not
+        // keep them all
+        while let Some(result) = tasks.try_join_next() {
+            collect(&mut results, result);
+        }
+    }
+
+    while let Some(result) = tasks.join_next().await {
+        collect(&mut results, result);
+    }
+    results
+}
+
+fn collect(
+    results: &mut Vec<StreamResults>,
+    result: std::result::Result<Vec<StreamResults>, tokio::task::JoinError>,
+) {
+    match result {
+        Ok(streams) => results.extend(streams),
+        Err(e) => println!("Connection task failed: {}", e),
+    }
+}
+
+/// Async counterpart of `server::handle_client`. Only forward runs are
+/// accepted: a client proposing another direction is rejected.
+pub async fn handle_client(
+    mut stream: TcpStream,
+    format: HeaderFormat,
+    framing: Framing,
+    interval: Option<Duration>,
+) -> ReceiverStats {
+    let peer_address = match stream.peer_addr() {
+        Ok(address) => util::canonical(address).to_string(),
+        Err(_) => String::from("unknown"),
+    };
+    let mut stats = ReceiverStats::new();
+    stats.set_socket(socket::settings(SockRef::from(&stream)).ok());
+    let mut reporter = interval
+        .map(|interval| IntervalReporter::new(interval).with_tcp_info(tcp_info_sampler(&stream)));
+    let mut client = None;
+    let mut is_closed = false;
+    loop {
+        let (message, is_recorded) = match read(&mut stream, format, &framing).await {
+            Ok(message) if message.mtype() == Type::Propose => (message, false),
+            Ok(message) => {
+                let is_recorded = stats.record(&message);
+                (message, is_recorded)
+            }
+            Err(e) => {
+                println!(
+                    "An error occurred, terminating connection with {}\n {}",
+                    peer_address, e
+                );
+                if let Err(e) = stream.shutdown().await {
+                    println!("Unable to shut connection down: {}", e);
+                }
+                break;
+            }
+        };
+        if message.mtype() == Type::Benchmark && is_recorded {
+            if stats.messages() == 1 {
+                reporter = reporter.map(|reporter| reporter.with_deadline(stats.measure_end()));
+            }
+            if let Some(reporter) = reporter.as_mut() {
+                reporter.record(message.body_size());
+            }
+        }
+        match message.mtype() {
+            Type::Close => {
+                is_closed = true;
+                break;
+            }
+            Type::Ping => {
+                let reply = message.reply(Type::Acknoweldge);
+                if let Err(e) = send(&mut stream, &reply, &framing).await {
+                    println!("Unable to acknowledge ping from {}\n {}", peer_address, e);
+                    break;
+                }
+            }
+            Type::Propose => match accept_proposal(&mut stream, &message, &framing).await {
+                Ok(proposal) => {
+                    if let Some(phases) = proposal.phases() {
+                        stats.set_phases(phases);
+                    }
+                    client = Some(message.source());
+                }
+                Err(e) => {
+                    println!("Rejected proposal from {}\n {}", peer_address, e);
+                    break;
+                }
+            },
+            _ => (),
+        }
+    }
+
+    if let Some(reporter) = reporter.as_mut() {
+        reporter.finish(tcp_info(&stream));
+        stats.set_intervals(reporter.intervals().to_vec());
+    }
+    stats.set_tcp_info(tcp_info(&stream));
+    if let (Some(client), true) = (client, is_closed) {
+        if let Err(e) = exchange_reports(&mut stream, format, &framing, &mut stats, client).await {
+            println!("Unable to exchange reports with {}\n {}", peer_address, e);
+        }
+    }
+    println!("Closed connection to {}", peer_address);
+    stats.print(&peer_address);
+    print_combined("", &stats.to_streams(&peer_address), Role::Server);
+    stats
+}
+
+/// Acknowledges a forward proposal, rejects any other. Returns the
+/// accepted proposal.
+async fn accept_proposal(
+    stream: &mut TcpStream,
+    message: &Message,
+    framing: &Framing,
+) -> Result<Proposal> {
+    let proposal = Proposal::from_message(message).and_then(|proposal| {
+        proposal.check(framing.maximum_body_size)?;
+        match proposal.direction {
+            _ if proposal.verify => Err(Error::Protocol(
+                "payload verification is not supported by the async server".into(),
+            )),
+            Direction::Forward => Ok(proposal),
+            direction => Err(Error::Protocol(format!(
+                "{:?} runs are not supported by the async server",
+                direction
+            ))),
+        }
+    });
+    match proposal {
+        Ok(proposal) => {
+            send(stream, &message.reply(Type::Acknoweldge), framing).await?;
+            if let Ok(address) = stream.peer_addr() {
+                println!("Accepted from {}: {}", util::canonical(address), proposal);
+            }
+            Ok(proposal)
+        }
+        Err(e) => {
+            send(stream, &control::reject(message, &e), framing).await?;
+            Err(e)
+        }
+    }
+}
+
+/// Async counterpart of `server::exchange_reports`
+async fn exchange_reports(
+    stream: &mut TcpStream,
+    format: HeaderFormat,
+    framing: &Framing,
+    stats: &mut ReceiverStats,
+    client: IpAddr,
+) -> Result<()> {
+    let peer_address = util::canonical(stream.peer_addr()?).to_string();
+    let report = Report::from_streams(&stats.to_streams(&peer_address));
+    send(
+        stream,
+        &report.to_message(source(stream)?, client)?,
+        framing,
+    )
+    .await?;
+    let message = read(stream, format, framing).await?;
+    stats.set_peer_report(Report::from_message(&message)?);
+    Ok(())
+}
+
+/// Runs the client workload on `parallel` tasks at once and prints the
+/// sum of their throughput. Fails if any task does, once all are over.
+pub fn run_client(config: &Config) -> Result<Vec<StreamResults>> {
+    config.validate()?;
+    let runtime = runtime(config.workers())?;
+    let parallel = config.parallel();
+    let config = Arc::new(config.clone());
+    let streams = runtime.block_on(async {
+        let handles: Vec<_> = (0..parallel)
+            .map(|index| {
+                let config = Arc::clone(&config);
+                let label = match parallel {
+                    1 => String::new(),
+                    _ => format!("[{}] ", index),
+                };
+                tokio::spawn(async move { run_stream(&config, &label).await })
+            })
+            .collect();
+        let mut streams = Vec::with_capacity(parallel);
+        for handle in handles {
+            streams.push(
+                handle
+                    .await
+                    .unwrap_or_else(|_| Err(Error::Panicked("Stream".into()))),
+            );
+        }
+        streams
+    });
+    let streams = streams.into_iter().collect::<Result<Vec<_>>>()?;
+    if parallel > 1 {
+        print_summary(&streams);
+    }
+    Ok(streams)
+}
+
+async fn run_stream(config: &Config, label: &str) -> Result<StreamResults> {
+    // The options are set before connecting, as for blocking clients
+    let address = SocketAddr::new(config.server(), config.port());
+    let socket = config
+        .socket_options()
+        .unconnected(config.source_address(), address)?;
+    socket.set_nonblocking(true)?;
+    let mut stream = TcpSocket::from_std_stream(socket).connect(address).await?;
+    let peer_address = match stream.peer_addr() {
+        Ok(address) => address.to_string(),
+        Err(_) => String::from("unknown"),
+    };
+    println!("{}Connected to {}", label, peer_address);
+    let socket = socket::settings(SockRef::from(&stream)).ok();
+    if let Some(socket) = &socket {
+        print_settings(label, socket);
+    }
+
+    let client_address = source(&stream)?;
+    propose(config, &mut stream, client_address).await?;
+    let results = match config.mode() {
+        Mode::Throughput => measure_throughput(config, &mut stream, label, peer_address).await?,
+        Mode::Latency => measure_latency(config, &mut stream, label, peer_address).await?,
+        Mode::OpenLoop => {
+            return Err(Error::Config(
+                "Open-loop mode requires the blocking or uring runtime".into(),
+            ))
+        }
+    };
+    let mut results = vec![StreamResults { socket, ..results }];
+
+    let msg = Message::new(client_address, config.server(), 0, Type::Close);
+    send(&mut stream, &msg, &config.framing()).await?;
+    // The server reports first, then waits for the client's report
+    let message = read(&mut stream, config.header_format(), &config.framing()).await?;
+    let report = Report::from_message(&message)?;
+    let own = Report::from_streams(&results).to_message(client_address, config.server())?;
+    send(&mut stream, &own, &config.framing()).await?;
+    report.apply(&mut results);
+    print_combined(label, &results, Role::Client);
+    Ok(results.remove(0))
+}
+
+/// Async counterpart of `Proposal::propose`
+async fn propose(config: &Config, stream: &mut TcpStream, client_address: IpAddr) -> Result<()> {
+    let msg = Proposal::new(config)?.to_message(client_address, config.server())?;
+    send(stream, &msg, &config.framing()).await?;
+    let reply = read(stream, config.header_format(), &config.framing()).await?;
+    match reply.mtype() {
+        Type::Reject => Err(Error::Rejected(control::reason(&reply))),
+        _ => check_acknowledgement(&msg, &reply),
+    }
+}
+
+/// Address the messages of a stream come from
+fn source(stream: &TcpStream) -> io::Result<IpAddr> {
+    Ok(stream.local_addr()?.ip().to_canonical())
+}
+
+async fn measure_throughput(
+    config: &Config,
+    stream: &mut TcpStream,
+    label: &str,
+    peer_address: String,
+) -> Result<StreamResults> {
+    let body_size = config.message_size();
+    let mut msg = Message::new(source(stream)?, config.server(), body_size, Type::Benchmark);
+    let payload = config.payload()?;
+    let framing = config.framing();
+
+    let mut meter = ThroughputMeter::new(config, label).with_tcp_info(tcp_info_sampler(stream));
+    let mut sequence = 0;
+    while let Some(phase) = meter.next_phase() {
+        // Timers have a millisecond resolution: a larger burst makes up for it
+        if let Some(delay) = meter.delay().filter(|delay| !delay.is_zero()) {
+            tokio::time::sleep(delay).await;
+        }
+        payload.refill(&mut msg, sequence);
+        sequence += 1;
+        check_sent(send(stream, &msg, &framing).await?, body_size)?;
+        meter.record(phase, || tcp_info(stream));
+    }
+    Ok(meter.finish(peer_address, tcp_info(stream)))
+}
+
+async fn measure_latency(
+    config: &Config,
+    stream: &mut TcpStream,
+    label: &str,
+    peer_address: String,
+) -> Result<StreamResults> {
+    let body_size = config.message_size();
+    if config.socket_options().nodelay.is_none() {
+        stream.set_nodelay(true)?;
+    }
+
+    let client_address = source(stream)?;
+    let payload = config.payload()?;
+    let framing = config.framing();
+    let mut histogram = Histogram::new();
+    let phases = config
+        .duration()
+        .map(|duration| Phases::new(config.warmup(), duration, config.cooldown()));
+    let mut pacer = config.pacing().map(Pacer::new);
+    let start = Instant::now();
+    let mut pings = 0;
+    loop {
+        let record = match phases {
+            Some(phases) => match phases.current() {
+                Phase::Done => break,
+                phase => phase == Phase::Measure,
+            },
+            None if pings == config.number_batches() * config.batch_size() => break,
+            None => true,
+        };
+        let mut ping = Message::new(client_address, config.server(), body_size, Type::Ping);
+        payload.fill(&mut ping);
+        if let Some(delay) = pacer.as_mut().map(Pacer::delay) {
+            tokio::time::sleep(delay).await;
+        }
+
+        let now = Instant::now();
+        send(stream, &ping, &framing).await?;
+        let reply = read(stream, config.header_format(), &framing).await?;
+        let elapsed = now.elapsed();
+
+        check_acknowledgement(&ping, &reply)?;
+        if record {
+            histogram.record(elapsed.as_nanos() as u64);
+        }
+        pings += 1;
+    }
+    let elapsed = match phases {
+        Some(phases) => phases.duration(),
+        None => start.elapsed(),
+    };
+
+    {
+        // Keeps the lines of concurrent streams together
+        let _stdout = std::io::stdout().lock();
+        println!(
+            "{}Round-trip latency ({} message body):",
+            label,
+            data_to_string(body_size as f64)
+        );
+        histogram.print();
+    }
+    let pacing = pacer.map(|pacer| pacer.finish(body_size));
+    if let Some(pacing) = &pacing {
+        print_pacing(label, pacing);
+    }
+
+    let pings = histogram.count();
+    Ok(StreamResults {
+        peer: peer_address,
+        summary: Metrics::new(
+            Duration::ZERO,
+            elapsed,
+            pings * body_size as u64,
+            pings,
+            None,
+        )
+        .with_tcp_info(tcp_info(stream), None),
+        latency: Some(LatencyMetrics::from(&histogram)),
+        pacing,
+        ..Default::default()
+    })
+}
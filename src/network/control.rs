@@ -0,0 +1,367 @@
+use super::transport::Connection;
+use crate::check_acknowledgement;
+use crate::config::{seconds, Config, Direction, Mode, Role};
+use crate::error::{Error, Result};
+use crate::message::{
+    message::Message,
+    mtype::Type,
+    payload::{Pattern, Payload, MAXIMUM_PATTERN_SIZE},
+};
+use crate::pacer::Pacing;
+use crate::phase::Phases;
+use crate::results::{Metrics, StreamResults};
+use crate::unit::{data_to_string, throughput_to_string};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{net::IpAddr, time::Duration};
+
+/// Version of the control handshake. Bumped whenever the proposal or the
+/// report change in a way the other side cannot read.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// The test a client asks for when its connection opens. It travels as
+/// JSON in the body of a `Propose` message; the server answers
+/// `Acknoweldge` to accept it, or `Reject` with the reason. A client that
+/// proposes nothing only sends, and gets no report at the end.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Proposal {
+    pub version: u32,
+    pub mode: Mode,
+    pub direction: Direction,
+    /// Body size of the messages, whichever side sends them
+    pub message_size: usize,
+    pub batch_size: usize,
+    pub number_batches: usize,
+    /// Measured time of a duration-based run, batches are counted otherwise
+    pub duration_seconds: Option<f64>,
+    pub warmup_seconds: f64,
+    pub cooldown_seconds: f64,
+    /// Connections the client opens at once, this one included
+    pub parallel: usize,
+    /// Target rate of the side that sends, when it is paced
+    #[serde(default)]
+    pub pacing: Option<Pacing>,
+    /// What the side that sends fills bodies with
+    #[serde(default)]
+    pub payload: Payload,
+    /// Whether the side that receives checks bodies against the payload
+    #[serde(default)]
+    pub verify: bool,
+}
+
+/// Only the version of a proposal, read before the rest so that another
+/// version is told apart from a malformed proposal
+#[derive(Deserialize)]
+struct Versioned {
+    version: u32,
+}
+
+impl Proposal {
+    /// The test a client runs with `config`. Reads the pattern file, if
+    /// any.
+    pub fn new(config: &Config) -> Result<Proposal> {
+        Ok(Proposal {
+            version: PROTOCOL_VERSION,
+            mode: config.mode(),
+            direction: config.direction(),
+            message_size: config.message_size(),
+            batch_size: config.batch_size(),
+            number_batches: config.number_batches(),
+            duration_seconds: config.duration().map(|duration| duration.as_secs_f64()),
+            warmup_seconds: config.warmup().as_secs_f64(),
+            cooldown_seconds: config.cooldown().as_secs_f64(),
+            parallel: config.parallel(),
+            pacing: config.pacing(),
+            payload: config.payload()?,
+            verify: config.verify(),
+        })
+    }
+
+    /// Encodes the proposal in a `Propose` message
+    pub fn to_message(&self, source: IpAddr, destination: IpAddr) -> Result<Message> {
+        encode(self, source, destination, Type::Propose)
+    }
+
+    /// Decodes the proposal carried by a `Propose` message
+    pub fn from_message(msg: &Message) -> Result<Proposal> {
+        let versioned: Versioned = decode(msg, Type::Propose)?;
+        if versioned.version != PROTOCOL_VERSION {
+            return Err(Error::Protocol(format!(
+                "control protocol version {} is not supported, this side speaks version {}",
+                versioned.version, PROTOCOL_VERSION
+            )));
+        }
+        decode(msg, Type::Propose)
+    }
+
+    /// Checks that the proposed test can be run by a server that reads
+    /// bodies of up to `maximum_body_size` bytes, and sends them too. The
+    /// client validates its own configuration, but the server may run
+    /// another build, or be sent anything.
+    pub fn check(&self, maximum_body_size: usize) -> Result<()> {
+        if self.mode != Mode::Throughput && self.direction != Direction::Forward {
+            return Err(Error::Protocol(format!(
+                "{:?} runs require the throughput mode",
+                self.direction
+            )));
+        }
+        if let Some(pacing) = &self.pacing {
+            if !(pacing.messages_per_second.is_finite() && pacing.messages_per_second > 0.0)
+                || pacing.period().is_none()
+                || pacing.burst == 0
+            {
+                return Err(Error::Protocol(format!("invalid pacing: {:?}", pacing)));
+            }
+        }
+        if self.message_size > maximum_body_size {
+            return Err(Error::Protocol(format!(
+                "messages of {} bytes exceed the maximum body size of {} bytes",
+                self.message_size, maximum_body_size
+            )));
+        }
+        let content = self.payload.content.len();
+        if self.payload.pattern == Pattern::File && !(1..=MAXIMUM_PATTERN_SIZE).contains(&content) {
+            return Err(Error::Protocol(format!(
+                "the file pattern must hold 1 to {} bytes, got {}",
+                MAXIMUM_PATTERN_SIZE, content
+            )));
+        }
+        if self.parallel == 0 {
+            return Err(Error::Protocol("parallel must be at least 1".into()));
+        }
+        let duration = self.duration_seconds.unwrap_or_default();
+        for (name, value) in [
+            ("duration", duration),
+            ("warmup", self.warmup_seconds),
+            ("cooldown", self.cooldown_seconds),
+        ] {
+            if !(value.is_finite() && value >= 0.0) {
+                return Err(Error::Protocol(format!("invalid {}: {}", name, value)));
+            }
+        }
+        if Duration::try_from_secs_f64(self.warmup_seconds + duration + self.cooldown_seconds)
+            .is_err()
+        {
+            return Err(Error::Protocol("the run is too long".into()));
+        }
+        Ok(())
+    }
+
+    /// Schedule of a duration-based run, starting now: the side that
+    /// receives starts it again at the first `Benchmark` message, and only
+    /// measures the traffic of its measured phase. Counted
+    /// runs have no warm-up or cool-down.
+    pub fn phases(&self) -> Option<Phases> {
+        let duration = self.duration_seconds?;
+        Some(Phases::new(
+            seconds(self.warmup_seconds),
+            seconds(duration),
+            seconds(self.cooldown_seconds),
+        ))
+    }
+
+    /// Sends the proposal and waits for the server to accept it. A refusal
+    /// is `Error::Rejected` with the reason the server gave.
+    pub fn propose<C: Connection>(
+        &self,
+        connection: &mut C,
+        source: IpAddr,
+        destination: IpAddr,
+    ) -> Result<()> {
+        let msg = self.to_message(source, destination)?;
+        connection.send(&msg)?;
+        let reply = connection.receive()?;
+        match reply.mtype() {
+            Type::Reject => Err(Error::Rejected(reason(&reply))),
+            _ => check_acknowledgement(&msg, &reply),
+        }
+    }
+}
+
+impl std::fmt::Display for Proposal {
+    /// One line, e.g. `Throughput Forward; 8.00KB messages; 10.00s`
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:?} {:?}; {} messages; ",
+            self.mode,
+            self.direction,
+            data_to_string(self.message_size as f64)
+        )?;
+        match self.duration_seconds {
+            Some(duration) => write!(f, "{:.2}s", duration)?,
+            None => write!(f, "{} batches of {}", self.number_batches, self.batch_size)?,
+        }
+        if self.parallel > 1 {
+            write!(f, "; {} parallel streams", self.parallel)?;
+        }
+        if let Some(pacing) = &self.pacing {
+            write!(f, "; paced at {:.0} messages/s", pacing.messages_per_second)?;
+        }
+        if self.payload.pattern != Pattern::Zeros {
+            write!(f, "; {:?} bodies", self.payload.pattern)?;
+        }
+        if self.verify {
+            write!(f, "; verified")?;
+        }
+        Ok(())
+    }
+}
+
+/// Refuses a proposal for the given `error`: the reason travels as UTF-8
+/// in the body
+pub fn reject(proposal: &Message, error: &Error) -> Message {
+    let reason = match error {
+        Error::Protocol(reason) => reason.clone(),
+        error => error.to_string(),
+    };
+    let mut msg = Message::new(
+        proposal.destination(),
+        proposal.source(),
+        reason.len(),
+        Type::Reject,
+    );
+    if let Some(body) = msg.body_as_mut() {
+        body.copy_from_slice(reason.as_bytes());
+    }
+    msg
+}
+
+/// The reason carried by a `Reject` message
+pub fn reason(msg: &Message) -> String {
+    match msg.body() {
+        Some(body) => String::from_utf8_lossy(body).into_owned(),
+        None => String::from("no reason given"),
+    }
+}
+
+/// What one side of a negotiated connection measured, per direction. Once
+/// both directions stopped, the server sends its report first and the
+/// client answers with its own, so that each side prints what the other
+/// one saw next to its own view.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub struct Report {
+    /// Messages from the client to the server
+    pub forward: Option<Metrics>,
+    /// Messages from the server to the client
+    pub reverse: Option<Metrics>,
+}
+
+impl Report {
+    /// Summaries of the streams of a connection. Streams without a
+    /// direction went forward.
+    pub fn from_streams(streams: &[StreamResults]) -> Report {
+        let mut report = Report::default();
+        for stream in streams {
+            match stream.direction {
+                Some(Direction::Reverse) => report.reverse = Some(stream.summary),
+                _ => report.forward = Some(stream.summary),
+            }
+        }
+        report
+    }
+
+    /// The summary of the messages going `direction`
+    pub fn summary(&self, direction: Option<Direction>) -> Option<Metrics> {
+        match direction {
+            Some(Direction::Reverse) => self.reverse,
+            _ => self.forward,
+        }
+    }
+
+    /// Sets the view of the peer on each of the streams
+    pub fn apply(&self, streams: &mut [StreamResults]) {
+        for stream in streams {
+            stream.peer_summary = self.summary(stream.direction);
+        }
+    }
+
+    /// Encodes the report in a `Report` message
+    pub fn to_message(&self, source: IpAddr, destination: IpAddr) -> Result<Message> {
+        encode(self, source, destination, Type::Report)
+    }
+
+    /// Decodes the report carried by a `Report` message
+    pub fn from_message(msg: &Message) -> Result<Report> {
+        decode(msg, Type::Report)
+    }
+
+    pub fn send<C: Connection>(
+        &self,
+        connection: &mut C,
+        source: IpAddr,
+        destination: IpAddr,
+    ) -> Result<()> {
+        connection.send(&self.to_message(source, destination)?)?;
+        Ok(())
+    }
+
+    pub fn receive<C: Connection>(connection: &mut C) -> Result<Report> {
+        Report::from_message(&connection.receive()?)
+    }
+}
+
+/// Prints, for each stream the peer reported on, what the sender and the
+/// receiver measured. `role` is the local side.
+pub fn print_combined(label: &str, streams: &[StreamResults], role: Role) {
+    // Keeps the lines of concurrent streams together
+    let _stdout = std::io::stdout().lock();
+    for stream in streams {
+        let Some(peer_summary) = &stream.peer_summary else {
+            continue;
+        };
+        let direction = stream.direction.unwrap_or(Direction::Forward);
+        // The client sends forward, the server in reverse
+        let (sender, receiver) =
+            match (direction == Direction::Forward) == matches!(role, Role::Client) {
+                true => (&stream.summary, peer_summary),
+                false => (peer_summary, &stream.summary),
+            };
+        println!(
+            "{}Combined {:?} report with {}:",
+            label, direction, stream.peer
+        );
+        print_side(label, "Sender", sender);
+        print_side(label, "Receiver", receiver);
+    }
+}
+
+fn print_side(label: &str, side: &str, metrics: &Metrics) {
+    println!(
+        "{}{}: Throughput: {}; Bytes: {}; Messages: {}; Time elapsed: {:.2}s",
+        label,
+        side,
+        throughput_to_string(metrics.bits_per_second),
+        data_to_string(metrics.bytes as f64),
+        metrics.messages,
+        metrics.elapsed_seconds()
+    );
+}
+
+/// A message of type `mtype` with `value` as JSON in its body
+fn encode<T: Serialize>(
+    value: &T,
+    source: IpAddr,
+    destination: IpAddr,
+    mtype: Type,
+) -> Result<Message> {
+    let bytes = serde_json::to_vec(value)?;
+    let mut msg = Message::new(source, destination, bytes.len(), mtype);
+    if let Some(body) = msg.body_as_mut() {
+        body.copy_from_slice(&bytes);
+    }
+    Ok(msg)
+}
+
+/// The JSON body of a message of type `mtype`
+fn decode<T: DeserializeOwned>(msg: &Message, mtype: Type) -> Result<T> {
+    match msg.body() {
+        Some(body) if msg.mtype() == mtype => serde_json::from_slice(body)
+            .map_err(|e| Error::Protocol(format!("malformed {:?} message: {}", mtype, e))),
+        _ => Err(Error::Protocol(format!(
+            "expected a {:?} message, received {:?} of {} bytes",
+            mtype,
+            msg.mtype(),
+            msg.body_size()
+        ))),
+    }
+}
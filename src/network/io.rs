@@ -1,12 +1,63 @@
-use crate::message::{header::Header, message::Message, Byte};
-use std::error::Error;
-use std::io::{Read, Write};
-use std::net::TcpStream;
+use crate::error::Result;
+use crate::message::{
+    framing::{self, Framing},
+    header::{Header, HeaderFormat},
+    message::Message,
+    Byte,
+};
+use std::io::{self, ErrorKind, IoSlice, Read, Write};
 
-/// Writes a Message ot the buffer. The message is written in two steps.
-///  1. The message is serialized into a buffer. The buffer size is
-///     calculated and sent to the receiver.
-///  2. The buffer itself is sent. A maximum buffer size is supposed to
+/// Counts the read and write calls made on a stream: on a socket, each one
+/// is a system call
+#[derive(Debug)]
+pub struct CountingStream<S> {
+    inner: S,
+    calls: u64,
+}
+
+impl<S> CountingStream<S> {
+    pub fn new(inner: S) -> CountingStream<S> {
+        Self { inner, calls: 0 }
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    /// Read and write calls so far
+    pub fn calls(&self) -> u64 {
+        self.calls
+    }
+}
+
+impl<S: Read> Read for CountingStream<S> {
+    fn read(&mut self, buf: &mut [Byte]) -> io::Result<usize> {
+        self.calls += 1;
+        self.inner.read(buf)
+    }
+}
+
+impl<S: Write> Write for CountingStream<S> {
+    fn write(&mut self, buf: &[Byte]) -> io::Result<usize> {
+        self.calls += 1;
+        self.inner.write(buf)
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        self.calls += 1;
+        self.inner.write_vectored(bufs)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Writes a Message ot the buffer. The message is made of two parts,
+/// written together with vectored writes until both are complete.
+///  1. The header is encoded in the portable wire format (see
+///     `Header::encode`), it tells the receiver how much bytes to expect.
+///  2. The buffer itself. A maximum buffer size is supposed to
 ///     be no more than u128
 ///
 /// # Example
@@ -37,22 +88,42 @@ use std::net::TcpStream;
 ///   assert_eq!(size, 0);
 /// ```
 /// In order to run this example, a server should be running first.
-pub fn send(stream: &mut TcpStream, msg: &Message) -> Result<usize, Box<dyn Error>> {
-    // Write buffer size so that the other end expects how much bytes to read.
-    unsafe { stream.write(msg.header().to_bytes())? };
+pub fn send<W: Write>(stream: &mut W, msg: &Message) -> Result<usize> {
+    send_with(stream, msg, &Framing::default())
+}
+
+/// Writes a Message like `send`, with the checksum of the given `framing`
+pub fn send_with<W: Write>(stream: &mut W, msg: &Message, framing: &Framing) -> Result<usize> {
+    // The header goes first so that the other end expects how much bytes to read.
+    let header = framing.encode(msg);
+    let body: &[Byte] = match msg.body() {
+        Some(body) => body,
+        None => &[],
+    };
+    write_all_vectored(stream, &mut [IoSlice::new(&header), IoSlice::new(body)])?;
+    Ok(body.len())
+}
 
-    // Write buffer
-    match msg.body() {
-        Some(body) => Ok(stream.write(body)?),
-        None => Ok(0),
+/// Writes all of `buffers`, in as few calls as the stream allows: a short
+/// write resumes where it stopped instead of dropping the rest.
+pub fn write_all_vectored<W: Write>(stream: &mut W, mut buffers: &mut [IoSlice]) -> io::Result<()> {
+    IoSlice::advance_slices(&mut buffers, 0);
+    while !buffers.is_empty() {
+        match stream.write_vectored(buffers) {
+            Ok(0) => return Err(ErrorKind::WriteZero.into()),
+            Ok(size) => IoSlice::advance_slices(&mut buffers, size),
+            Err(e) if e.kind() == ErrorKind::Interrupted => (),
+            Err(e) => return Err(e),
+        }
     }
+    Ok(())
 }
 
-/// Reads Result<Message> from a TcpStream
+/// Reads Result<Message> from a stream (a TcpStream for instance)
 ///
 /// # Example
 /// ```no_run
-/// # use benchmark_network::network::{client, io::read, server::{self, close}};
+/// # use benchmark_network::network::io::read;
 /// # use benchmark_network::message::{message::Message, mtype::Type};
 /// # use std::thread;
 /// # use std::net::{Shutdown, TcpListener, TcpStream, IpAddr, Ipv4Addr};
@@ -107,24 +178,50 @@ pub fn send(stream: &mut TcpStream, msg: &Message) -> Result<usize, Box<dyn Erro
 /// #             assert_eq!(message.body_size(), 0);
 /// #         }
 /// #     }
-/// #     close(stream);
+/// #     println!("Closed connection to {}", stream.peer_addr().unwrap());
 /// # }
 /// ```
-pub fn read(stream: &mut TcpStream) -> Result<Message, Box<dyn Error>> {
+pub fn read<R: Read>(stream: &mut R) -> Result<Message> {
+    read_with(stream, HeaderFormat::Wire, &Framing::default())
+}
+
+/// Reads Result<Message> from a stream, expecting headers in the given
+/// format. `HeaderFormat::Legacy` accepts peers running versions up to
+/// 0.1.1, as long as they share this machine's architecture.
+/// Bodies larger than the maximum of `framing` are refused before they
+/// are read, and messages are checked against the checksum they carry.
+pub fn read_with<R: Read>(
+    stream: &mut R,
+    format: HeaderFormat,
+    framing: &Framing,
+) -> Result<Message> {
     // Read expected message header
     let mut header_buffer = [0u8; Header::size()];
-    stream.read_exact(&mut header_buffer)?;
-    let header = unsafe { Header::from_bytes(&header_buffer) };
+    let header = match format {
+        HeaderFormat::Wire => {
+            stream.read_exact(&mut header_buffer)?;
+            Header::decode(&header_buffer)?
+        }
+        HeaderFormat::Legacy => {
+            let mut header_buffer = [0u8; Header::legacy_size()];
+            stream.read_exact(&mut header_buffer)?;
+            Header::decode_legacy(&header_buffer)?
+        }
+    };
 
     // Read message body
+    framing.check_body_size(&header)?;
     let body: Option<Box<Vec<Byte>>> = match header.body_size() {
         0 => None,
         _ => {
-            let mut buffer: Vec<Byte> = vec![0u8; header.body_size() as usize];
+            let mut buffer: Vec<Byte> = vec![0u8; header.body_size()];
             stream.read_exact(&mut buffer)?;
             Some(Box::new(buffer))
         }
     };
+    // Legacy headers carry no checksum
+    let body_bytes: &[Byte] = body.as_deref().map_or(&[], |body| body);
+    framing::verify(&header, &header_buffer, body_bytes)?;
 
     Ok(Message::compose(header, body))
 }
@@ -1,12 +1,25 @@
+#[cfg(feature = "async")]
+pub mod asynchronous;
 pub mod client;
+pub mod control;
 pub mod io;
 pub mod server;
+pub mod socket;
+pub mod stats;
+#[cfg(target_os = "linux")]
+pub mod tcp_info;
+pub mod transport;
+pub mod udp;
 pub mod util;
+#[cfg(target_os = "linux")]
+pub mod zerocopy;
 
 /// The maximum size of a buffer that this trait can handle
 pub const MAXIMUM_BUFFER_SIZE: u128 = u128::MAX;
 
+use crate::error::{Error, Result};
 use std::{
+    panic::{self, AssertUnwindSafe},
     sync::{mpsc, Arc, Mutex},
     thread,
 };
@@ -19,8 +32,13 @@ pub struct ThreadPool {
 type Job = Box<dyn FnOnce() + Send + 'static>;
 
 impl ThreadPool {
-    pub fn new(size: usize) -> ThreadPool {
-        assert!(size > 0);
+    /// Starts `size` workers, at least one
+    pub fn new(size: usize) -> Result<ThreadPool> {
+        if size == 0 {
+            return Err(Error::Config(
+                "A thread pool needs at least one worker".into(),
+            ));
+        }
 
         let (sender, receiver) = mpsc::channel();
 
@@ -29,22 +47,26 @@ impl ThreadPool {
         let mut workers = Vec::with_capacity(size);
 
         for id in 0..size {
-            workers.push(Worker::new(id, Arc::clone(&receiver)));
+            workers.push(Worker::new(id, Arc::clone(&receiver))?);
         }
 
-        ThreadPool {
+        Ok(ThreadPool {
             workers,
             sender: Some(sender),
-        }
+        })
     }
 
-    pub fn execute<F>(&self, f: F)
+    /// Hands `f` to the next free worker. Fails once no worker is left.
+    pub fn execute<F>(&self, f: F) -> Result<()>
     where
         F: FnOnce() + Send + 'static,
     {
         let job = Box::new(f);
 
-        self.sender.as_ref().unwrap().send(job).unwrap();
+        self.sender
+            .as_ref()
+            .and_then(|sender| sender.send(job).ok())
+            .ok_or_else(|| Error::Panicked("Thread pool".into()))
     }
 }
 
@@ -56,7 +78,9 @@ impl Drop for ThreadPool {
             println!("Shutting down worker {}", worker.id);
 
             if let Some(thread) = worker.thread.take() {
-                thread.join().unwrap();
+                if thread.join().is_err() {
+                    println!("Worker {} panicked", worker.id);
+                }
             }
         }
     }
@@ -68,29 +92,39 @@ struct Worker {
 }
 
 impl Worker {
-    fn new(id: usize, receiver: Arc<Mutex<mpsc::Receiver<Job>>>) -> Worker {
-        let thread = thread::spawn(move || loop {
-            let message = receiver.lock().unwrap().recv();
+    fn new(id: usize, receiver: Arc<Mutex<mpsc::Receiver<Job>>>) -> Result<Worker> {
+        let thread = thread::Builder::new().spawn(move || loop {
+            let message = match receiver.lock() {
+                Ok(receiver) => receiver.recv(),
+                Err(_) => {
+                    println!("Worker {id} lost its queue; shutting down.");
+                    break;
+                }
+            };
 
             match message {
                 Ok(job) => {
                     println!("Worker {id} got a job; executing.");
 
-                    job();
+                    // A failing job must not take the worker down with it
+                    if panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+                        println!("Worker {id} job panicked.");
+                    }
                 }
                 Err(_) => {
                     println!("Worker {id} disconnected; shutting down.");
                     break;
                 }
             }
-        });
+        })?;
 
-        Worker {
+        Ok(Worker {
             id,
             thread: Some(thread),
-        }
+        })
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests;
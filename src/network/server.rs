@@ -1,87 +1,367 @@
-use super::{io, ThreadPool};
-use crate::message::mtype::Type;
-use std::net::{Shutdown, TcpListener, TcpStream};
+use super::{
+    control::{self, print_combined, Proposal, Report},
+    socket::print_settings,
+    stats::ReceiverStats,
+    transport::{tcp::TcpTransport, Connection, Listener, Transport},
+    ThreadPool,
+};
+use crate::config::{Direction, Role};
+use crate::error::{Error, Result};
+use crate::interval::IntervalReporter;
+use crate::message::{header::HeaderFormat, message::Message, mtype::Type, payload::Payload};
+use crate::pacer::{self, print_pacing, Pacer, Pacing};
+use crate::print_throughput;
+use crate::results::{Metrics, StreamResults};
+use std::{
+    io::ErrorKind,
+    net::{IpAddr, Ipv4Addr},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
 
-const LOCALHOST: &str = "0.0.0.0";
+const LOCALHOST: IpAddr = IpAddr::V4(Ipv4Addr::UNSPECIFIED);
 
 /// Similar to listen, with a difference that it might listen to one (or) more clients
 /// and stop listening.
 /// It listens locally on the given port, and reads incoming messages
-/// TODO: Add the handle_client function as parameter
-pub fn listen_portable(port: u16, pool_size: usize, queue_size: usize, is_limited: Option<usize>) {
-    assert!(queue_size <= pool_size);
-    let address = format!("{}:{}", LOCALHOST, port.to_string());
-    let listener = TcpListener::bind(address).unwrap();
-
-    let pool = ThreadPool::new(pool_size);
+/// Incoming headers are expected in the given `format`.
+pub fn listen_portable(
+    port: u16,
+    pool_size: usize,
+    is_limited: Option<usize>,
+    format: HeaderFormat,
+) -> Result<()> {
+    let transport = TcpTransport::new(LOCALHOST, port, format);
+    let listener = transport.listen()?;
+    serve(listener, pool_size, is_limited, None)?;
+    Ok(())
+}
 
-    println!("Server listening on {}", port);
+/// Accepts clients on any transport's listener and hands each connection
+/// to `handle_client` on a pool of `pool_size` workers, so up to
+/// `pool_size` clients are served at once (at least one) while more are
+/// accepted; the others wait for a free worker. Stops accepting after `is_limited`
+/// connections, or never when unlimited. With an `interval`, each
+/// connection also prints interval reports.
+/// Returns the results of every connection, once all of them are closed.
+/// TODO: Add the handle_client function as parameter
+pub fn serve<L: Listener>(
+    mut listener: L,
+    pool_size: usize,
+    is_limited: Option<usize>,
+    interval: Option<Duration>,
+) -> Result<Vec<StreamResults>> {
+    let pool = ThreadPool::new(pool_size.max(1))?;
+    let results = Arc::new(Mutex::new(Vec::new()));
 
-    let mut counter = is_limited.unwrap_or_default();
+    println!("Server listening on {}", listener.local());
 
-    for stream in listener.incoming().take(queue_size) {
-        match stream {
-            Ok(stream) => {
-                println!("New connection: {}", stream.peer_addr().unwrap());
+    let mut accepted = 0;
+    while is_limited.is_none_or(|limit| accepted < limit) {
+        match listener.accept() {
+            Ok(connection) => {
+                let peer_address = connection.peer();
+                println!("New connection: {}", peer_address);
+                if let Some(socket) = connection.socket() {
+                    print_settings("", &socket);
+                }
 
-                pool.execute(|| {
-                    handle_client(stream);
-                });
+                let results = Arc::clone(&results);
+                pool.execute(move || {
+                    let stats = handle_client(connection, interval);
+                    let streams = stats.to_streams(&peer_address);
+                    if let Ok(mut results) = results.lock() {
+                        results.extend(streams);
+                    }
+                })?;
+                accepted += 1;
+            }
+            // The listener itself is gone: nothing more to accept
+            Err(e) if e.kind() == ErrorKind::NotConnected => {
+                println!("Listener closed: {}", e);
+                break;
             }
+            // A client that failed to connect does not count towards the limit
             Err(e) => {
                 println!("Connection failed: {}", e);
             }
         }
-
-        if is_limited.is_some() {
-            counter -= 1;
-            if counter == 0 {
-                break;
-            }
-        }
     }
 
     println!("Dropping listener");
     drop(listener);
+
+    // Waits for the workers to finish their connections
+    drop(pool);
+    let mut results = results
+        .lock()
+        .map_err(|_| Error::Panicked("Collecting results".into()))?;
+    Ok(std::mem::take(&mut *results))
 }
 
 /// listens locally on the given port, and reads incoming messages
-pub fn listen(port: u16, pool_size: usize, queue_size: usize) {
-    listen_portable(port, pool_size, queue_size, None);
+pub fn listen(port: u16, pool_size: usize) -> Result<()> {
+    listen_portable(port, pool_size, None, HeaderFormat::default())
 }
 
-pub fn handle_client(mut stream: TcpStream) {
-    let peer_address = stream.peer_addr().unwrap();
+/// Reads messages until the client sends `Close`, acknowledging pings on
+/// the way. Returns what was received, also printed when the connection closes.
+/// With an `interval`, received bodies are also reported every `interval`.
+/// A client proposing a reverse or bidirectional run is sent messages back
+/// from another thread until it sends `Close`. A client that proposed its
+/// test is sent the server's report after `Close`, and answers with its own.
+pub fn handle_client<C: Connection + 'static>(
+    mut connection: C,
+    interval: Option<Duration>,
+) -> ReceiverStats {
+    let peer_address = connection.peer();
+    let mut stats = ReceiverStats::new();
+    let mut reporter = interval.map(|interval| {
+        IntervalReporter::new(interval).with_tcp_info(connection.tcp_info_sampler())
+    });
+    let stop = Arc::new(AtomicBool::new(false));
+    let mut sender = None;
+    // Address of a client that proposed its test, to report to
+    let mut client = None;
+    let mut is_closed = false;
     loop {
-        let message = match io::read(&mut stream) {
-            Ok(message) => match message.mtype() {
-                Type::Close => {
-                    break;
-                }
-                _ => message,
-            },
+        let (message, is_recorded) = match connection.receive() {
+            // The proposal is not part of the measured traffic
+            Ok(message) if message.mtype() == Type::Propose => (message, false),
+            Ok(message) => {
+                let is_recorded = stats.record(&message);
+                (message, is_recorded)
+            }
             Err(e) => {
                 println!(
                     "An error occurred, terminating connection with {}\n {}",
                     peer_address, e
                 );
-                stream.shutdown(Shutdown::Both).unwrap();
+                if let Err(e) = connection.close() {
+                    println!("Unable to shut connection down: {}", e);
+                }
                 break;
             }
         };
-        if message.has_body() {
-            assert!(message.body_size() > 0);
-        } else {
-            assert_eq!(message.body_size(), 0);
+        if message.mtype() == Type::Benchmark && is_recorded {
+            if stats.messages() == 1 {
+                reporter = reporter.map(|reporter| reporter.with_deadline(stats.measure_end()));
+            }
+            if let Some(reporter) = reporter.as_mut() {
+                reporter.record(message.body_size());
+            }
         }
+        match message.mtype() {
+            Type::Close => {
+                is_closed = true;
+                break;
+            }
+            Type::Propose => match accept_proposal(&mut connection, &message, interval, &stop) {
+                Ok((proposal, handle)) => {
+                    stats.set_direction(proposal.direction);
+                    if let Some(phases) = proposal.phases() {
+                        stats.set_phases(phases);
+                    }
+                    if proposal.verify {
+                        stats.verify(proposal.payload);
+                    }
+                    sender = handle;
+                    client = Some(message.source());
+                }
+                Err(e) => {
+                    println!("Rejected proposal from {}\n {}", peer_address, e);
+                    break;
+                }
+            },
+            Type::Ping => {
+                // Latency measurement: answer right away with the same id
+                let reply = message.reply(Type::Acknoweldge);
+                if let Err(e) = connection.send(&reply) {
+                    println!("Unable to acknowledge ping from {}\n {}", peer_address, e);
+                    break;
+                }
+            }
+            _ => (),
+        }
+        if message.has_body() != (message.body_size() > 0) {
+            let e = Error::Protocol(format!(
+                "{:?} message announces a body of {} bytes, but carries {}",
+                message.mtype(),
+                message.body_size(),
+                message.body().map_or(0, |body| body.len())
+            ));
+            println!(
+                "An error occurred, terminating connection with {}\n {}",
+                peer_address, e
+            );
+            break;
+        }
+    }
+
+    stop.store(true, Ordering::Relaxed);
+    if let Some(sender) = sender {
+        match sender.join() {
+            Ok(Ok(sent)) => stats.set_sent(sent),
+            Ok(Err(e)) => println!("Unable to send to {}\n {}", peer_address, e),
+            Err(_) => println!("Sending to {} panicked", peer_address),
+        }
+    }
+
+    if let Some(reporter) = reporter.as_mut() {
+        reporter.finish(connection.tcp_info());
+        stats.set_intervals(reporter.intervals().to_vec());
+    }
+    stats.set_tcp_info(connection.tcp_info());
+    stats.set_socket(connection.socket());
+    stats.set_syscalls(connection.syscalls());
+    if let (Some(client), true) = (client, is_closed) {
+        if let Err(e) = exchange_reports(&mut connection, &mut stats, client) {
+            println!("Unable to exchange reports with {}\n {}", peer_address, e);
+        }
+    }
+    close(connection, &stats);
+    stats
+}
+
+/// Sends the report of the server, then reads the one of the client
+fn exchange_reports<C: Connection>(
+    connection: &mut C,
+    stats: &mut ReceiverStats,
+    client: IpAddr,
+) -> Result<()> {
+    let source = connection.local_address().unwrap_or(LOCALHOST);
+    let report = Report::from_streams(&stats.to_streams(&connection.peer()));
+    report.send(connection, source, client)?;
+    stats.set_peer_report(Report::receive(connection)?);
+    Ok(())
+}
+
+pub fn close<C: Connection>(connection: C, stats: &ReceiverStats) {
+    let peer_address = connection.peer();
+    println!("Closed connection to {}", peer_address);
+    // A reverse client only sends its proposal and Close
+    if stats.direction() != Direction::Reverse {
+        stats.print(&peer_address);
     }
+    print_combined("", &stats.to_streams(&peer_address), Role::Server);
+}
+
+type Sender = JoinHandle<Result<StreamResults>>;
 
-    close(stream);
+/// Acknowledges the proposal of a client, or rejects it when it cannot be
+/// run. When the client asked to be sent messages, starts sending them on
+/// another handle of the connection, filled with the proposed payload.
+/// Returns the accepted proposal.
+fn accept_proposal<C: Connection + 'static>(
+    connection: &mut C,
+    message: &Message,
+    interval: Option<Duration>,
+    stop: &Arc<AtomicBool>,
+) -> Result<(Proposal, Option<Sender>)> {
+    let proposal = match Proposal::from_message(message)
+        .and_then(|p| p.check(connection.framing().maximum_body_size).map(|_| p))
+    {
+        Ok(proposal) => proposal,
+        Err(e) => {
+            connection.send(&control::reject(message, &e))?;
+            return Err(e);
+        }
+    };
+    println!("Accepted from {}: {}", connection.peer(), proposal);
+    let sending = match proposal.direction {
+        Direction::Forward => None,
+        Direction::Reverse | Direction::Bidirectional => Some(connection.try_clone()?),
+    };
+    connection.send(&message.reply(Type::Acknoweldge))?;
+    let handle = sending.map(|sending| {
+        let source = sending.local_address().unwrap_or(message.destination());
+        let reply = Message::new(
+            source,
+            message.source(),
+            proposal.message_size,
+            Type::Benchmark,
+        );
+        let stop = Arc::clone(stop);
+        let pacing = proposal.pacing;
+        let payload = proposal.payload.clone();
+        thread::spawn(move || send_back(sending, reply, &payload, interval, pacing, &stop))
+    });
+    Ok((proposal, handle))
 }
 
-pub fn close(stream: TcpStream) {
-    let peer_addr = stream
-        .peer_addr()
-        .expect("Unable to retrieve client's address on closing socket");
-    println!("Closed connection to {}", peer_addr);
+/// Sends `msg` to the client over and over, filled with `payload`, until
+/// `stop` is set, then `Close`, at the `pacing` rate if the client asked
+/// for one. Returns what was sent, also printed.
+fn send_back<C: Connection>(
+    mut connection: C,
+    mut msg: Message,
+    payload: &Payload,
+    interval: Option<Duration>,
+    pacing: Option<Pacing>,
+    stop: &AtomicBool,
+) -> Result<StreamResults> {
+    let peer_address = connection.peer();
+    let mut reporter = interval.map(|interval| {
+        IntervalReporter::new(interval)
+            .with_label("Sent ")
+            .with_tcp_info(connection.tcp_info_sampler())
+    });
+    let mut pacer = pacing.map(Pacer::new);
+    let start = Instant::now();
+    let mut messages: u64 = 0;
+    while !stop.load(Ordering::Relaxed) {
+        if let Some(pacer) = pacer.as_mut() {
+            pacer::sleep(pacer.delay());
+        }
+        payload.refill(&mut msg, messages);
+        connection.send(&msg)?;
+        messages += 1;
+        if let Some(reporter) = reporter.as_mut() {
+            reporter.record(msg.body_size());
+        }
+    }
+    let elapsed = start.elapsed();
+    connection.send(&Message::new(
+        msg.source(),
+        msg.destination(),
+        0,
+        Type::Close,
+    ))?;
+
+    println!("Sender summary for {}:", peer_address);
+    let sent_bytes = messages * msg.body_size() as u64;
+    let tcp_info = connection.tcp_info();
+    let mut intervals = Vec::new();
+    let summary = match reporter.as_mut() {
+        Some(reporter) => {
+            let summary = reporter.finish(tcp_info);
+            intervals = reporter.intervals().to_vec();
+            summary
+        }
+        None => {
+            print_throughput("", sent_bytes as f64, elapsed.as_secs_f64());
+            if let Some(tcp_info) = &tcp_info {
+                println!("{}; Retransmits: {}", tcp_info, tcp_info.retransmits);
+            }
+            Metrics::new(Duration::ZERO, elapsed, sent_bytes, messages, None)
+                .with_tcp_info(tcp_info, None)
+        }
+    };
+    let pacing = pacer.map(|pacer| pacer.finish(msg.body_size()));
+    if let Some(pacing) = &pacing {
+        print_pacing("", pacing);
+    }
+    Ok(StreamResults {
+        peer: peer_address,
+        intervals,
+        summary,
+        syscalls: connection.syscalls(),
+        direction: Some(Direction::Reverse),
+        pacing,
+        ..Default::default()
+    })
 }
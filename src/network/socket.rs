@@ -0,0 +1,282 @@
+use crate::error::{Error, Result};
+use crate::results::SocketSettings;
+use crate::unit::data_to_string;
+use serde::{Deserialize, Serialize};
+use socket2::{Domain, Protocol, SockRef, Socket, Type};
+use std::{
+    io,
+    net::{IpAddr, SocketAddr, TcpListener, TcpStream, UdpSocket},
+};
+
+// Connections waiting to be accepted
+const BACKLOG: i32 = 1024;
+
+/// TCP socket options, set before connecting or listening. Unset options
+/// keep the kernel defaults. Connections accepted by a listener inherit
+/// its options.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+pub struct SocketOptions {
+    /// `TCP_NODELAY`: sends small writes right away
+    pub nodelay: Option<bool>,
+    /// `SO_SNDBUF` in bytes; the kernel doubles it for its bookkeeping and
+    /// caps it at `net.core.wmem_max`
+    pub send_buffer: Option<usize>,
+    /// `SO_RCVBUF` in bytes; the kernel doubles it for its bookkeeping and
+    /// caps it at `net.core.rmem_max`
+    pub receive_buffer: Option<usize>,
+    /// `TCP_CONGESTION` algorithm (cubic, reno, bbr...), Linux only
+    pub congestion: Option<String>,
+    /// `TCP_MAXSEG`: largest segment sent, in bytes
+    pub mss: Option<u32>,
+    /// `TCP_CORK`: holds partial segments back until they are full, Linux only
+    pub cork: Option<bool>,
+    /// `SO_REUSEPORT`: lets several listeners share a port
+    pub reuse_port: Option<bool>,
+    /// `SO_BINDTODEVICE`: only sends and receives through this network
+    /// interface, Linux only
+    pub interface: Option<String>,
+}
+
+impl SocketOptions {
+    /// Whether every option keeps the kernel default
+    pub fn is_default(&self) -> bool {
+        *self == SocketOptions::default()
+    }
+
+    /// Connects to `address` from `source`, or from wherever the system
+    /// chooses. Options are set beforehand, so that buffer sizes and the
+    /// MSS are negotiated in the handshake.
+    pub fn connect(&self, source: Option<SocketAddr>, address: SocketAddr) -> Result<TcpStream> {
+        let socket = self.socket(source, address)?;
+        socket.connect(&address.into())?;
+        Ok(socket.into())
+    }
+
+    /// Listens on `address` with the options set. The unspecified IPv6
+    /// address also accepts IPv4 clients.
+    pub fn listen(&self, address: SocketAddr) -> Result<TcpListener> {
+        let socket = self.socket(None, address)?;
+        // As TcpListener::bind does, so that a restarted server gets its port back
+        #[cfg(unix)]
+        socket.set_reuse_address(true)?;
+        dual_stack(&socket, address)?;
+        socket.bind(&address.into())?;
+        socket.listen(BACKLOG)?;
+        Ok(socket.into())
+    }
+
+    /// A TCP socket for `address`, with the options set and bound to
+    /// `source` but not connected yet, for runtimes that connect on their own
+    pub fn unconnected(
+        &self,
+        source: Option<SocketAddr>,
+        address: SocketAddr,
+    ) -> Result<TcpStream> {
+        Ok(self.socket(source, address)?.into())
+    }
+
+    fn socket(&self, source: Option<SocketAddr>, address: SocketAddr) -> Result<Socket> {
+        let socket = Socket::new(
+            Domain::for_address(address),
+            Type::STREAM,
+            Some(Protocol::TCP),
+        )?;
+        self.apply(&socket)?;
+        if let Some(source) = source {
+            bind_source(&socket, source)?;
+        }
+        Ok(socket)
+    }
+
+    /// Sets the options on `socket`. A value the kernel refuses is a
+    /// configuration error naming the option; a buffer the kernel caps is
+    /// only reported.
+    fn apply(&self, socket: &Socket) -> Result<()> {
+        if let Some(nodelay) = self.nodelay {
+            socket
+                .set_tcp_nodelay(nodelay)
+                .map_err(|e| refused("TCP_NODELAY", nodelay, e))?;
+        }
+        if let Some(size) = self.send_buffer {
+            socket
+                .set_send_buffer_size(size)
+                .map_err(|e| refused("SO_SNDBUF", size, e))?;
+            check_capped("SO_SNDBUF", size, socket.send_buffer_size()?, "wmem_max");
+        }
+        if let Some(size) = self.receive_buffer {
+            socket
+                .set_recv_buffer_size(size)
+                .map_err(|e| refused("SO_RCVBUF", size, e))?;
+            check_capped("SO_RCVBUF", size, socket.recv_buffer_size()?, "rmem_max");
+        }
+        if let Some(congestion) = &self.congestion {
+            #[cfg(target_os = "linux")]
+            socket
+                .set_tcp_congestion(congestion.as_bytes())
+                .map_err(|e| match e.raw_os_error() {
+                    Some(libc::ENOENT) => Error::Config(format!(
+                        "Kernel refused TCP_CONGESTION={}: unknown algorithm, see \
+                        /proc/sys/net/ipv4/tcp_available_congestion_control",
+                        congestion
+                    )),
+                    Some(libc::EPERM) => Error::Config(format!(
+                        "Kernel refused TCP_CONGESTION={}: not allowed, see \
+                        /proc/sys/net/ipv4/tcp_allowed_congestion_control",
+                        congestion
+                    )),
+                    _ => refused("TCP_CONGESTION", congestion, e),
+                })?;
+            #[cfg(not(target_os = "linux"))]
+            return Err(unsupported("TCP_CONGESTION", congestion));
+        }
+        if let Some(mss) = self.mss {
+            #[cfg(unix)]
+            socket
+                .set_tcp_mss(mss)
+                .map_err(|e| refused("TCP_MAXSEG", mss, e))?;
+            #[cfg(not(unix))]
+            return Err(unsupported("TCP_MAXSEG", mss));
+        }
+        if let Some(cork) = self.cork {
+            #[cfg(target_os = "linux")]
+            socket
+                .set_tcp_cork(cork)
+                .map_err(|e| refused("TCP_CORK", cork, e))?;
+            #[cfg(not(target_os = "linux"))]
+            return Err(unsupported("TCP_CORK", cork));
+        }
+        if let Some(interface) = &self.interface {
+            #[cfg(target_os = "linux")]
+            socket
+                .bind_device(Some(interface.as_bytes()))
+                .map_err(|e| match e.raw_os_error() {
+                    Some(libc::ENODEV) => Error::Config(format!(
+                        "Kernel refused SO_BINDTODEVICE={}: no such interface",
+                        interface
+                    )),
+                    _ => refused("SO_BINDTODEVICE", interface, e),
+                })?;
+            #[cfg(not(target_os = "linux"))]
+            return Err(unsupported("SO_BINDTODEVICE", interface));
+        }
+        if let Some(reuse_port) = self.reuse_port {
+            #[cfg(unix)]
+            socket
+                .set_reuse_port(reuse_port)
+                .map_err(|e| refused("SO_REUSEPORT", reuse_port, e))?;
+            #[cfg(not(unix))]
+            return Err(unsupported("SO_REUSEPORT", reuse_port));
+        }
+        Ok(())
+    }
+}
+
+/// Binds a client socket to its source address. A fixed source port may
+/// still be held by the previous run's connection in TIME_WAIT.
+fn bind_source(socket: &Socket, source: SocketAddr) -> Result<()> {
+    if source.port() != 0 {
+        socket.set_reuse_address(true)?;
+    }
+    socket
+        .bind(&source.into())
+        .map_err(|e| Error::Config(format!("Unable to bind source address {}: {}", source, e)))
+}
+
+/// Binds a UDP socket to `address`. The unspecified IPv6 address also
+/// receives IPv4 datagrams.
+pub fn bind_datagram(address: SocketAddr) -> io::Result<UdpSocket> {
+    let socket = Socket::new(
+        Domain::for_address(address),
+        Type::DGRAM,
+        Some(Protocol::UDP),
+    )?;
+    dual_stack(&socket, address)?;
+    socket.bind(&address.into())?;
+    Ok(socket.into())
+}
+
+/// Lets a socket bound to the unspecified IPv6 address receive IPv4
+/// traffic too, whatever the system default is
+fn dual_stack(socket: &Socket, address: SocketAddr) -> io::Result<()> {
+    match address.ip() {
+        IpAddr::V6(ip) if ip.is_unspecified() => socket.set_only_v6(false),
+        _ => Ok(()),
+    }
+}
+
+fn refused<T: std::fmt::Display>(option: &str, value: T, error: io::Error) -> Error {
+    Error::Config(format!("Kernel refused {}={}: {}", option, value, error))
+}
+
+#[allow(dead_code)]
+fn unsupported<T: std::fmt::Display>(option: &str, value: T) -> Error {
+    Error::Config(format!(
+        "{}={} is not supported on this platform",
+        option, value
+    ))
+}
+
+/// The kernel silently caps buffers at a system-wide maximum
+fn check_capped(option: &str, requested: usize, effective: usize, maximum: &str) {
+    // Linux reports twice the requested size
+    let expected = match cfg!(target_os = "linux") {
+        true => requested.saturating_mul(2),
+        false => requested,
+    };
+    if effective < expected {
+        println!(
+            "{}={} capped by the kernel to {}, raise net.core.{} to go beyond",
+            option, requested, effective, maximum
+        );
+    }
+}
+
+/// Reads the options the kernel actually applies to a connection
+pub(crate) fn settings(socket: SockRef<'_>) -> io::Result<SocketSettings> {
+    Ok(SocketSettings {
+        nodelay: socket.tcp_nodelay()?,
+        send_buffer: socket.send_buffer_size()?,
+        receive_buffer: socket.recv_buffer_size()?,
+        #[cfg(target_os = "linux")]
+        congestion: Some(congestion_name(&socket.tcp_congestion()?)),
+        #[cfg(not(target_os = "linux"))]
+        congestion: None,
+        #[cfg(unix)]
+        mss: Some(socket.tcp_mss()?),
+        #[cfg(not(unix))]
+        mss: None,
+        #[cfg(target_os = "linux")]
+        cork: Some(socket.tcp_cork()?),
+        #[cfg(not(target_os = "linux"))]
+        cork: None,
+    })
+}
+
+/// The kernel pads the name of the algorithm with NUL bytes
+#[cfg(target_os = "linux")]
+fn congestion_name(name: &[u8]) -> String {
+    String::from_utf8_lossy(name)
+        .trim_end_matches('\0')
+        .to_string()
+}
+
+/// Prints the options of a connection on one line
+pub fn print_settings(label: &str, settings: &SocketSettings) {
+    let mut line = format!(
+        "{}Socket: nodelay: {}; send buffer: {}; receive buffer: {}",
+        label,
+        settings.nodelay,
+        data_to_string(settings.send_buffer as f64),
+        data_to_string(settings.receive_buffer as f64)
+    );
+    if let Some(mss) = settings.mss {
+        line.push_str(&format!("; MSS: {}", mss));
+    }
+    if let Some(congestion) = &settings.congestion {
+        line.push_str(&format!("; congestion: {}", congestion));
+    }
+    if let Some(cork) = settings.cork {
+        line.push_str(&format!("; cork: {}", cork));
+    }
+    println!("{}", line);
+}
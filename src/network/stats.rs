@@ -0,0 +1,270 @@
+use super::control::Report;
+use crate::config::Direction;
+use crate::message::{
+    message::Message,
+    mtype::Type,
+    payload::{print_verification, Payload, Verifier},
+};
+use crate::phase::{Phase, Phases};
+use crate::results::{
+    Metrics, ReceiverMetrics, SocketSettings, StreamResults, SyscallMetrics, TcpInfoMetrics,
+};
+use crate::unit::*;
+use std::time::{Duration, Instant};
+
+/// What the receiving end of a connection measured: `Benchmark` messages,
+/// bytes split between headers and bodies, and the time from the first of
+/// them to `Close`. On duration-based runs, only the messages received
+/// while measuring.
+#[derive(Debug, Clone, Default)]
+pub struct ReceiverStats {
+    messages: u64,
+    header_bytes: u64,
+    body_bytes: u64,
+    // When the first Benchmark message arrived
+    start: Option<Instant>,
+    last_message: Option<Instant>,
+    intervals: Vec<Metrics>,
+    syscalls: Option<SyscallMetrics>,
+    socket: Option<SocketSettings>,
+    tcp_info: Option<TcpInfoMetrics>,
+    // Negotiated by the client; the server sends when it is not Forward
+    direction: Direction,
+    sent: Option<StreamResults>,
+    // What the client measured, on negotiated connections
+    peer_report: Option<Report>,
+    // Set when the sender asked for its bodies to be checked
+    verifier: Option<Verifier>,
+    // Schedule of a duration-based run, when the sender told it
+    phases: Option<Phases>,
+    // Whether a Benchmark message arrived, which starts the schedule
+    has_traffic: bool,
+}
+
+impl ReceiverStats {
+    pub fn new() -> ReceiverStats {
+        Self::default()
+    }
+
+    /// Records a message as it is received, unless it arrives during
+    /// warm-up or cool-down. Only `Benchmark` messages count as data, and
+    /// `Close` stops the clock. Returns whether the message was counted.
+    pub fn record(&mut self, msg: &Message) -> bool {
+        let now = Instant::now();
+        // The phases follow the traffic of the sender rather than the
+        // moment its proposal was accepted
+        if msg.mtype() == Type::Benchmark && !self.has_traffic {
+            self.has_traffic = true;
+            self.phases = self.phases.map(|phases| phases.starting_at(now));
+        }
+        if self
+            .phases
+            .is_some_and(|phases| phases.current() != Phase::Measure)
+        {
+            return false;
+        }
+        if let Some(verifier) = self.verifier.as_mut() {
+            verifier.verify(msg);
+        }
+        match msg.mtype() {
+            Type::Benchmark => (),
+            Type::Close if self.start.is_some() => {
+                self.last_message = Some(now);
+                return false;
+            }
+            _ => return false,
+        }
+        self.start.get_or_insert(now);
+        self.last_message = Some(now);
+        self.messages += 1;
+        self.header_bytes += Message::header_size() as u64;
+        self.body_bytes += msg.body_size() as u64;
+        true
+    }
+
+    /// Only records the messages received during the measured phase of
+    /// `phases`, started by the first `Benchmark` message
+    pub fn set_phases(&mut self, phases: Phases) {
+        self.phases = Some(phases);
+    }
+
+    /// When the measured phase ends, once the first `Benchmark` message
+    /// started it
+    pub fn measure_end(&self) -> Option<Instant> {
+        self.phases
+            .filter(|_| self.has_traffic)
+            .and_then(|phases| phases.measure_end())
+    }
+
+    /// Checks the bodies recorded from now on against `payload`
+    pub fn verify(&mut self, payload: Payload) {
+        self.verifier = Some(Verifier::new(payload));
+    }
+
+    pub fn messages(&self) -> u64 {
+        self.messages
+    }
+
+    pub fn header_bytes(&self) -> u64 {
+        self.header_bytes
+    }
+
+    pub fn body_bytes(&self) -> u64 {
+        self.body_bytes
+    }
+
+    /// Keeps the intervals reported while receiving, for the results output
+    pub fn set_intervals(&mut self, intervals: Vec<Metrics>) {
+        self.intervals = intervals;
+    }
+
+    pub fn intervals(&self) -> &[Metrics] {
+        &self.intervals
+    }
+
+    /// Keeps the options of the connection, when it is a TCP one
+    pub fn set_socket(&mut self, socket: Option<SocketSettings>) {
+        self.socket = socket;
+    }
+
+    /// Keeps a sample of the connection taken when it closed
+    pub fn set_tcp_info(&mut self, tcp_info: Option<TcpInfoMetrics>) {
+        self.tcp_info = tcp_info;
+    }
+
+    /// Keeps the direction the client asked for
+    pub fn set_direction(&mut self, direction: Direction) {
+        self.direction = direction;
+    }
+
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    /// Keeps what was sent back to the client, on reverse and
+    /// bidirectional runs
+    pub fn set_sent(&mut self, sent: StreamResults) {
+        self.sent = Some(sent);
+    }
+
+    /// Keeps the report the client sent at the end of the run
+    pub fn set_peer_report(&mut self, report: Report) {
+        self.peer_report = Some(report);
+    }
+
+    /// Keeps the system calls the connection made, when it counts them
+    pub fn set_syscalls(&mut self, syscalls: Option<SyscallMetrics>) {
+        self.syscalls = syscalls;
+    }
+
+    /// Header and body bytes
+    pub fn total_bytes(&self) -> u64 {
+        self.header_bytes + self.body_bytes
+    }
+
+    /// Time from the first `Benchmark` message to `Close`, or to the last
+    /// message recorded when the run did not end with one
+    pub fn elapsed(&self) -> Duration {
+        match (self.start, self.last_message) {
+            (Some(start), Some(last)) => last.saturating_duration_since(start),
+            _ => Duration::ZERO,
+        }
+    }
+
+    /// Bits per second received, headers included
+    pub fn throughput(&self) -> f64 {
+        bits_per_second(self.total_bytes(), self.elapsed())
+    }
+
+    /// Bits per second of message bodies only
+    pub fn goodput(&self) -> f64 {
+        bits_per_second(self.body_bytes, self.elapsed())
+    }
+
+    /// Share of received bytes spent on headers, in percent
+    pub fn overhead_percentage(&self) -> f64 {
+        match self.total_bytes() {
+            0 => 0.0,
+            total => self.header_bytes as f64 * 100.0 / total as f64,
+        }
+    }
+
+    /// Results of the connection: summary metrics count message bodies,
+    /// like the sender does; header bytes are broken down separately.
+    pub fn to_results(&self, peer_address: &str) -> StreamResults {
+        StreamResults {
+            peer: peer_address.to_string(),
+            intervals: self.intervals.clone(),
+            summary: Metrics::new(
+                Duration::ZERO,
+                self.elapsed(),
+                self.body_bytes,
+                self.messages,
+                None,
+            )
+            .with_tcp_info(self.tcp_info, None),
+            receiver: Some(ReceiverMetrics {
+                header_bytes: self.header_bytes,
+                body_bytes: self.body_bytes,
+                throughput_bits_per_second: self.throughput(),
+                goodput_bits_per_second: self.goodput(),
+                overhead_percentage: self.overhead_percentage(),
+            }),
+            syscalls: self.syscalls,
+            socket: self.socket.clone(),
+            verification: self.verifier.as_ref().map(Verifier::metrics),
+            ..Default::default()
+        }
+    }
+
+    /// Results of each direction of the connection. Only the client sends
+    /// on forward runs, only the server on reverse ones. Each one holds the
+    /// view of the client too, when it reported it.
+    pub fn to_streams(&self, peer_address: &str) -> Vec<StreamResults> {
+        let received = match self.direction {
+            Direction::Forward => Some(self.to_results(peer_address)),
+            Direction::Reverse => None,
+            Direction::Bidirectional => Some(StreamResults {
+                direction: Some(Direction::Forward),
+                ..self.to_results(peer_address)
+            }),
+        };
+        let mut streams: Vec<StreamResults> =
+            received.into_iter().chain(self.sent.clone()).collect();
+        if let Some(report) = &self.peer_report {
+            report.apply(&mut streams);
+        }
+        streams
+    }
+
+    pub fn print(&self, peer_address: &str) {
+        println!("Receiver summary for {}:", peer_address);
+        println!(
+            "Throughput: {}; Goodput: {}; Received bytes: {}; Time elapsed: {:.2}s",
+            throughput_to_string(self.throughput()),
+            throughput_to_string(self.goodput()),
+            data_to_string(self.total_bytes() as f64),
+            self.elapsed().as_secs_f64()
+        );
+        println!(
+            "Messages: {}; Body bytes: {}; Header bytes: {} ({:.2}% overhead)",
+            self.messages,
+            data_to_string(self.body_bytes as f64),
+            data_to_string(self.header_bytes as f64),
+            self.overhead_percentage()
+        );
+        if let Some(tcp_info) = &self.tcp_info {
+            println!("{}; Retransmits: {}", tcp_info, tcp_info.retransmits);
+        }
+        if let Some(verifier) = &self.verifier {
+            print_verification("", &verifier.metrics());
+        }
+    }
+}
+
+fn bits_per_second(bytes: u64, elapsed: Duration) -> f64 {
+    match elapsed.as_secs_f64() {
+        elapsed if elapsed > 0.0 => bytes as f64 * 8.0 / elapsed,
+        _ => 0.0,
+    }
+}
@@ -0,0 +1,67 @@
+use crate::interval::Sampler;
+use crate::results::TcpInfoMetrics;
+use socket2::SockRef;
+use std::{
+    io, mem,
+    os::fd::{AsFd, AsRawFd, RawFd},
+};
+
+// Pacing is reported as unlimited until the congestion control sets a rate
+const UNLIMITED_RATE: u64 = u64::MAX;
+
+/// Samples `socket` from another thread, through a duplicate of its
+/// descriptor. The duplicate keeps the connection open until the sampler
+/// is dropped.
+pub fn sampler<S: AsFd>(socket: &S) -> Option<Sampler> {
+    let socket = SockRef::from(socket).try_clone().ok()?;
+    Some(Box::new(move || sample(&socket).ok()))
+}
+
+/// Samples `TCP_INFO` on a connected TCP socket. Fields an older kernel
+/// does not fill in are left out.
+pub fn sample<S: AsRawFd>(socket: &S) -> io::Result<TcpInfoMetrics> {
+    let (info, length) = read(socket.as_raw_fd())?;
+    // Rates are in bytes per second, and missing from older kernels
+    let rate = |offset: usize, rate: u64| {
+        let is_filled = length >= offset + mem::size_of::<u64>();
+        match is_filled && rate != UNLIMITED_RATE {
+            true => Some(rate.saturating_mul(8)),
+            false => None,
+        }
+    };
+    Ok(TcpInfoMetrics {
+        retransmits: info.tcpi_total_retrans as u64,
+        rtt_microseconds: info.tcpi_rtt,
+        rtt_variance_microseconds: info.tcpi_rttvar,
+        congestion_window_segments: info.tcpi_snd_cwnd,
+        pacing_rate_bits_per_second: rate(
+            mem::offset_of!(libc::tcp_info, tcpi_pacing_rate),
+            info.tcpi_pacing_rate,
+        ),
+        delivery_rate_bits_per_second: rate(
+            mem::offset_of!(libc::tcp_info, tcpi_delivery_rate),
+            info.tcpi_delivery_rate,
+        ),
+    })
+}
+
+/// Returns the structure and how many of its bytes the kernel filled in
+fn read(fd: RawFd) -> io::Result<(libc::tcp_info, usize)> {
+    // Safety: tcp_info is plain data, zeroed is a valid value
+    let mut info: libc::tcp_info = unsafe { mem::zeroed() };
+    let mut length = mem::size_of::<libc::tcp_info>() as libc::socklen_t;
+    // Safety: `info` is valid for `length` bytes for the duration of the call
+    let result = unsafe {
+        libc::getsockopt(
+            fd,
+            libc::IPPROTO_TCP,
+            libc::TCP_INFO,
+            (&mut info as *mut libc::tcp_info).cast(),
+            &mut length,
+        )
+    };
+    match result {
+        0 => Ok((info, length as usize)),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
@@ -1,7 +1,21 @@
 mod tests {
-    use crate::message::{message::Message, mtype::Type};
-    use crate::network::{client, io::send, server};
+    use crate::error::Error;
+    use crate::message::framing::{Checksum, Framing, FramingError};
+    use crate::message::header::{Header, HeaderFormat};
+    use crate::message::{message::Message, mtype::Type, payload::Payload};
+    use crate::network::{
+        client,
+        io::{read, read_with, send, send_with},
+        server,
+        transport::{memory::MemoryTransport, Connection, Listener, Transport},
+        udp::{SequenceTracker, REORDER_WINDOW},
+        util::to_string,
+        ThreadPool,
+    };
+    use crate::pacer::Pacing;
+    use std::io::{self, IoSlice, Write};
     use std::net::{IpAddr, Ipv4Addr};
+    use std::thread;
 
     const LOCALHOST: IpAddr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
     const SOURCE: IpAddr = LOCALHOST;
@@ -13,7 +27,7 @@ mod tests {
     #[ignore = "runs indefinitley"]
     #[test]
     pub fn test_server() {
-        server::listen(PORT, 1, 1);
+        server::listen(PORT, 1).unwrap();
     }
 
     #[ignore = "might run before server"]
@@ -27,9 +41,925 @@ mod tests {
         let size = send(&mut stream, &msg).expect("Unable to send message");
         assert_eq!(size, BODY_SIZE);
 
+        // Ping is acknowledged with the same id
+        let ping = Message::new(SOURCE, DESTINATION, BODY_SIZE, Type::Ping);
+        send(&mut stream, &ping).expect("Unable to send message");
+        let reply = read(&mut stream).expect("Unable to read acknowledgement");
+        assert_eq!(reply.mtype(), Type::Acknoweldge);
+        assert_eq!(reply.id(), ping.id());
+        assert!(reply.without_body());
+
         // Message without body
         let close_message = Message::new(SOURCE, DESTINATION, 0, Type::Close);
         let size = send(&mut stream, &close_message).expect("Unable to send message");
         assert_eq!(size, 0);
     }
+
+    #[test]
+    pub fn test_sequence_tracker() {
+        let mut tracker = SequenceTracker::new();
+        let mut header = Header::new(SOURCE, DESTINATION, BODY_SIZE, MTYPE);
+        for sequence in [0, 1, 3, 2, 2, 5] {
+            header.set_sequence(sequence);
+            header.set_timestamp(sequence * 1_000);
+            tracker.record(&header, BODY_SIZE, sequence * 1_000 + 500);
+        }
+        tracker.finish(7);
+
+        assert_eq!(tracker.received(), 5);
+        assert_eq!(tracker.received_bytes(), 5 * BODY_SIZE as u64);
+        assert_eq!(tracker.reordered(), 1);
+        assert_eq!(tracker.duplicated(), 1);
+        assert_eq!(tracker.lost(), 2);
+        assert!((tracker.loss_percentage() - 200.0 / 7.0).abs() < 1e-9);
+        // Constant transit time means no jitter
+        assert_eq!(tracker.jitter(), 0.0);
+
+        // A sequence number from far ahead is counted, not enumerated
+        header.set_sequence(u64::MAX);
+        tracker.record(&header, BODY_SIZE, 0);
+        assert_eq!(tracker.lost(), u64::MAX - 6);
+        header.set_sequence(u64::MAX - REORDER_WINDOW);
+        tracker.record(&header, BODY_SIZE, 0);
+        assert_eq!(tracker.reordered(), 2);
+        // Too late to be told from a duplicate
+        header.set_sequence(8);
+        tracker.record(&header, BODY_SIZE, 0);
+        assert_eq!(tracker.duplicated(), 2);
+        tracker.finish(u64::MAX);
+        assert_eq!(tracker.received(), 7);
+    }
+
+    #[test]
+    pub fn test_sequence_tracker_jitter() {
+        let mut tracker = SequenceTracker::new();
+        let mut header = Header::new(SOURCE, DESTINATION, BODY_SIZE, MTYPE);
+        header.set_sequence(0);
+        tracker.record(&header, BODY_SIZE, 1_000);
+        header.set_sequence(1);
+        tracker.record(&header, BODY_SIZE, 2_600);
+        assert_eq!(tracker.jitter(), 100.0);
+    }
+
+    #[test]
+    pub fn test_udp_interval() {
+        use crate::network::udp;
+        use std::net::SocketAddr;
+        use std::time::Duration;
+
+        let address = SocketAddr::new(LOCALHOST, 6672);
+        let server =
+            thread::spawn(move || udp::listen(address, Some(1), Some(Duration::from_millis(50))));
+        // Datagrams sent before the server is bound would be lost
+        thread::sleep(Duration::from_millis(100));
+
+        let socket = udp::connect(None, LOCALHOST, 6672).expect("Unable to connect");
+        let mut msg = Message::new(SOURCE, DESTINATION, BODY_SIZE, MTYPE);
+        for sequence in 0..4 {
+            msg.header_as_mut().set_sequence(sequence);
+            udp::send(&socket, &msg).expect("Unable to send datagram");
+            // Nothing arrives during two intervals
+            if sequence == 2 {
+                thread::sleep(Duration::from_millis(130));
+            }
+        }
+        udp::close(&socket, SOURCE, DESTINATION, 4);
+
+        let results = server
+            .join()
+            .expect("Server panicked")
+            .expect("Server failed");
+        let intervals = &results[0].intervals;
+        assert!(intervals.len() >= 3);
+        assert_eq!(intervals[0].bytes, 3 * BODY_SIZE as u64);
+        assert!(intervals[1..intervals.len() - 1]
+            .iter()
+            .all(|interval| interval.bytes == 0));
+        assert_eq!(intervals.last().unwrap().bytes, BODY_SIZE as u64);
+        assert_eq!(results[0].summary.bytes, 4 * BODY_SIZE as u64);
+    }
+
+    #[test]
+    pub fn test_udp_reused_address() {
+        use crate::network::udp;
+        use std::net::SocketAddr;
+        use std::time::Duration;
+
+        let address = SocketAddr::new(LOCALHOST, 6673);
+        let server = thread::spawn(move || udp::listen(address, Some(2), None));
+        // Datagrams sent before the server is bound would be lost
+        thread::sleep(Duration::from_millis(100));
+
+        // Both clients send from the same address
+        let socket = udp::connect(None, LOCALHOST, 6673).expect("Unable to connect");
+        let mut msg = Message::new(SOURCE, DESTINATION, BODY_SIZE, MTYPE);
+        for sent in [3, 2] {
+            for sequence in 0..sent {
+                msg.header_as_mut().set_sequence(sequence);
+                udp::send(&socket, &msg).expect("Unable to send datagram");
+            }
+            udp::close(&socket, SOURCE, DESTINATION, sent);
+            thread::sleep(Duration::from_millis(20));
+        }
+
+        let results = server
+            .join()
+            .expect("Server panicked")
+            .expect("Server failed");
+        let bytes: Vec<u64> = results.iter().map(|stream| stream.summary.bytes).collect();
+        assert_eq!(bytes, vec![3 * BODY_SIZE as u64, 2 * BODY_SIZE as u64]);
+    }
+
+    #[test]
+    pub fn test_memory_transport() {
+        let transport = MemoryTransport::new();
+        let listener = transport.listen().expect("Unable to listen");
+        let server = thread::spawn(move || server::serve(listener, 1, Some(1), None));
+
+        let mut connection = transport.connect().expect("Unable to connect");
+        let msg = Message::new(SOURCE, DESTINATION, BODY_SIZE, MTYPE);
+        let size = connection.send(&msg).expect("Unable to send message");
+        assert_eq!(size, BODY_SIZE);
+
+        let ping = Message::new(SOURCE, DESTINATION, BODY_SIZE, Type::Ping);
+        connection.send(&ping).expect("Unable to send message");
+        let reply = connection
+            .receive()
+            .expect("Unable to read acknowledgement");
+        assert_eq!(reply.mtype(), Type::Acknoweldge);
+        assert_eq!(reply.id(), ping.id());
+
+        let close_message = Message::new(SOURCE, DESTINATION, 0, Type::Close);
+        connection
+            .send(&close_message)
+            .expect("Unable to send message");
+        server
+            .join()
+            .expect("Server panicked")
+            .expect("Server failed");
+    }
+
+    #[test]
+    pub fn test_concurrent_server() {
+        let transport = MemoryTransport::new();
+        let listener = transport.listen().expect("Unable to listen");
+        let server = thread::spawn(move || server::serve(listener, 2, Some(3), None));
+
+        // Both clients are served at once: each gets its ping acknowledged
+        // while the other one is still connected
+        let mut connections: Vec<_> = (0..2)
+            .map(|_| transport.connect().expect("Unable to connect"))
+            .collect();
+        for connection in connections.iter_mut() {
+            let ping = Message::new(SOURCE, DESTINATION, BODY_SIZE, Type::Ping);
+            connection.send(&ping).expect("Unable to send message");
+            let reply = connection
+                .receive()
+                .expect("Unable to read acknowledgement");
+            assert_eq!(reply.id(), ping.id());
+        }
+
+        // A third client waits for a free worker
+        connections.push(transport.connect().expect("Unable to connect"));
+        for connection in connections.iter_mut() {
+            let close_message = Message::new(SOURCE, DESTINATION, 0, Type::Close);
+            connection
+                .send(&close_message)
+                .expect("Unable to send message");
+        }
+
+        let results = server
+            .join()
+            .expect("Server panicked")
+            .expect("Server failed");
+        assert_eq!(results.len(), 3);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    pub fn test_unix_transport() {
+        use crate::message::header::HeaderFormat;
+        use crate::network::transport::unix::UnixTransport;
+
+        let path = std::env::temp_dir().join(format!("benchmark-{}.sock", std::process::id()));
+        let transport = UnixTransport::new(&path, HeaderFormat::Wire);
+        let listener = transport.listen().expect("Unable to listen");
+        // The socket of a running server is not taken over
+        assert!(matches!(transport.listen(), Err(Error::Config(_))));
+        let server = thread::spawn(move || server::serve(listener, 1, Some(1), None));
+
+        let mut connection = transport.connect().expect("Unable to connect");
+        let msg = Message::new(SOURCE, DESTINATION, BODY_SIZE, MTYPE);
+        let size = connection.send(&msg).expect("Unable to send message");
+        assert_eq!(size, BODY_SIZE);
+        let close_message = Message::new(SOURCE, DESTINATION, 0, Type::Close);
+        connection
+            .send(&close_message)
+            .expect("Unable to send message");
+        server
+            .join()
+            .expect("Server panicked")
+            .expect("Server failed");
+
+        // The listener removes its socket file once dropped
+        assert!(!path.exists());
+
+        // A stale socket is replaced, a regular file is not
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+        assert!(path.exists());
+        drop(transport.listen().expect("Unable to listen"));
+        std::fs::write(&path, b"not a socket").unwrap();
+        assert!(matches!(transport.listen(), Err(Error::Config(_))));
+        assert_eq!(std::fs::read(&path).unwrap(), b"not a socket");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    pub fn test_thread_pool() {
+        assert!(matches!(ThreadPool::new(0), Err(Error::Config(_))));
+
+        let pool = ThreadPool::new(2).expect("Unable to start the pool");
+        let (sender, receiver) = std::sync::mpsc::channel();
+        pool.execute(|| panic!("job failed")).unwrap();
+        for i in 0..4 {
+            let sender = sender.clone();
+            pool.execute(move || sender.send(i).unwrap()).unwrap();
+        }
+        drop(sender);
+        drop(pool);
+        let mut done: Vec<i32> = receiver.iter().collect();
+        done.sort();
+        assert_eq!(done, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    pub fn test_receiver_phases() {
+        use crate::network::stats::ReceiverStats;
+        use crate::phase::Phases;
+        use std::time::Duration;
+
+        let mut stats = ReceiverStats::new();
+        let warmup = Duration::from_millis(100);
+        stats.set_phases(Phases::new(warmup, Duration::from_secs(10), Duration::ZERO));
+        // The sender starts late: its warm-up starts with its traffic
+        thread::sleep(2 * warmup);
+        let msg = Message::new(SOURCE, DESTINATION, BODY_SIZE, Type::Benchmark);
+        assert!(!stats.record(&msg));
+        thread::sleep(warmup);
+        assert!(stats.record(&msg));
+        assert_eq!(stats.messages(), 1);
+    }
+
+    #[test]
+    pub fn test_receiver_stats() {
+        let transport = MemoryTransport::new();
+        let mut listener = transport.listen().expect("Unable to listen");
+        let server = thread::spawn(move || {
+            let connection = listener.accept().expect("Unable to accept");
+            server::handle_client(connection, None)
+        });
+
+        let mut connection = transport.connect().expect("Unable to connect");
+        // The clock starts with the first message, not when the connection opens
+        thread::sleep(std::time::Duration::from_millis(100));
+        for _ in 0..3 {
+            let msg = Message::new(SOURCE, DESTINATION, BODY_SIZE, MTYPE);
+            connection.send(&msg).expect("Unable to send message");
+        }
+        let close_message = Message::new(SOURCE, DESTINATION, 0, Type::Close);
+        connection
+            .send(&close_message)
+            .expect("Unable to send message");
+
+        let stats = server.join().expect("Server panicked");
+        // Close is not data
+        assert_eq!(stats.messages(), 3);
+        assert_eq!(stats.body_bytes(), 3 * BODY_SIZE as u64);
+        assert_eq!(stats.header_bytes(), 3 * Header::size() as u64);
+        assert_eq!(
+            stats.total_bytes(),
+            stats.body_bytes() + stats.header_bytes()
+        );
+        assert!(stats.goodput() <= stats.throughput());
+        assert!(stats.elapsed() < std::time::Duration::from_millis(100));
+    }
+
+    /// Accepts at most a few bytes per call, like a full socket buffer
+    struct ShortWriter {
+        written: Vec<u8>,
+        calls: usize,
+    }
+
+    impl Write for ShortWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.write_vectored(&[IoSlice::new(buf)])
+        }
+
+        fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+            self.calls += 1;
+            let mut size = 0;
+            for buf in bufs {
+                let length = buf.len().min(7 - size);
+                self.written.extend_from_slice(&buf[..length]);
+                size += length;
+            }
+            Ok(size)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    pub fn test_send_short_writes() {
+        let mut writer = ShortWriter {
+            written: Vec::new(),
+            calls: 0,
+        };
+        let msg = Message::new(SOURCE, DESTINATION, BODY_SIZE, MTYPE);
+        let size = send(&mut writer, &msg).expect("Unable to send message");
+        assert_eq!(size, BODY_SIZE);
+        assert_eq!(writer.written.len(), Header::size() + BODY_SIZE);
+        assert_eq!(writer.calls, (Header::size() + BODY_SIZE).div_ceil(7));
+
+        let received = read(&mut writer.written.as_slice()).expect("Unable to read message");
+        assert_eq!(received.id(), msg.id());
+        assert_eq!(received.body_size(), BODY_SIZE);
+    }
+
+    #[test]
+    pub fn test_framing() {
+        let framing = Framing {
+            checksum: Checksum::Xxh3,
+            maximum_body_size: BODY_SIZE,
+        };
+        let msg = Message::new(SOURCE, DESTINATION, BODY_SIZE, MTYPE);
+        let mut written = Vec::new();
+        send_with(&mut written, &msg, &framing).expect("Unable to send message");
+        let received = read_with(&mut written.as_slice(), HeaderFormat::Wire, &framing)
+            .expect("Unable to read message");
+        assert_eq!(received.header().checksum(), Checksum::Xxh3);
+        // Readers check whichever checksum a message carries
+        assert!(read(&mut written.as_slice()).is_ok());
+
+        let mut corrupted = written.clone();
+        corrupted[Header::size() + 3] ^= 1;
+        assert!(matches!(
+            read(&mut corrupted.as_slice()),
+            Err(Error::Framing(FramingError::ChecksumMismatch { .. }))
+        ));
+
+        // A corrupt length is refused before its body is allocated
+        let mut corrupted = written;
+        corrupted[54..62].copy_from_slice(&u64::MAX.to_be_bytes());
+        let framing = Framing::default();
+        assert!(matches!(
+            read_with(&mut corrupted.as_slice(), HeaderFormat::Wire, &framing),
+            Err(Error::Framing(FramingError::BodyTooLarge { .. }))
+        ));
+
+        // Both ends of a memory connection share the framing
+        let transport = MemoryTransport::new().with_framing(Framing {
+            checksum: Checksum::Crc32c,
+            maximum_body_size: BODY_SIZE,
+        });
+        let mut listener = transport.listen().expect("Unable to listen");
+        let mut client = transport.connect().expect("Unable to connect");
+        let mut server = listener.accept().expect("Unable to accept");
+        client.send(&msg).expect("Unable to send message");
+        let received = server.receive().expect("Unable to read message");
+        assert_eq!(received.header().checksum(), Checksum::Crc32c);
+        let large = Message::new(SOURCE, DESTINATION, BODY_SIZE + 1, MTYPE);
+        server.send(&large).expect("Unable to send message");
+        assert!(matches!(
+            client.receive(),
+            Err(Error::Framing(FramingError::BodyTooLarge { .. }))
+        ));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    pub fn test_zerocopy() {
+        use crate::message::header::HeaderFormat;
+        use crate::network::{transport::tcp::TcpTransport, zerocopy::ZEROCOPY_THRESHOLD};
+
+        let mut listener = TcpTransport::new(LOCALHOST, 0, HeaderFormat::Wire)
+            .listen()
+            .expect("Unable to listen");
+        let port = listener
+            .local()
+            .rsplit(':')
+            .next()
+            .unwrap()
+            .parse()
+            .unwrap();
+        let server = thread::spawn(move || {
+            let connection = listener.accept().expect("Unable to accept");
+            server::handle_client(connection, None)
+        });
+
+        let transport = TcpTransport::new(LOCALHOST, port, HeaderFormat::Wire);
+        let mut connection = transport.connect().expect("Unable to connect");
+        connection
+            .enable_zerocopy()
+            .expect("Unable to enable zero-copy");
+        let body_size = 4 * ZEROCOPY_THRESHOLD;
+        let msg = Message::new(SOURCE, DESTINATION, body_size, MTYPE);
+        for _ in 0..10 {
+            let size = connection.send(&msg).expect("Unable to send message");
+            assert_eq!(size, body_size);
+        }
+        connection.flush().expect("Unable to flush");
+        let close_message = Message::new(SOURCE, DESTINATION, 0, Type::Close);
+        connection
+            .send(&close_message)
+            .expect("Unable to send message");
+
+        let stats = server.join().expect("Server panicked");
+        assert_eq!(stats.body_bytes(), 10 * body_size as u64);
+    }
+
+    #[test]
+    pub fn test_socket_options() {
+        use crate::message::header::HeaderFormat;
+        use crate::network::{socket::SocketOptions, transport::tcp::TcpTransport};
+
+        let options = SocketOptions {
+            nodelay: Some(true),
+            send_buffer: Some(64 * 1024),
+            receive_buffer: Some(64 * 1024),
+            congestion: cfg!(target_os = "linux").then(|| String::from("reno")),
+            reuse_port: Some(true),
+            ..Default::default()
+        };
+        let mut listener = TcpTransport::new(LOCALHOST, 0, HeaderFormat::Wire)
+            .with_options(options.clone())
+            .listen()
+            .expect("Unable to listen");
+        let port = listener
+            .local()
+            .rsplit(':')
+            .next()
+            .unwrap()
+            .parse()
+            .unwrap();
+        let server = thread::spawn(move || listener.accept().expect("Unable to accept"));
+
+        let transport =
+            TcpTransport::new(LOCALHOST, port, HeaderFormat::Wire).with_options(options);
+        let connection = transport.connect().expect("Unable to connect");
+        let accepted = server.join().expect("Server panicked");
+        // The accepted connection inherits the options of the listener
+        for connection in [connection, accepted] {
+            let socket = connection.socket().expect("No socket settings");
+            assert!(socket.nodelay);
+            assert!(socket.send_buffer >= 64 * 1024);
+            assert!(socket.receive_buffer >= 64 * 1024);
+            if cfg!(target_os = "linux") {
+                assert_eq!(socket.congestion.as_deref(), Some("reno"));
+            }
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    pub fn test_socket_options_refused() {
+        use crate::error::Error;
+        use crate::message::header::HeaderFormat;
+        use crate::network::{socket::SocketOptions, transport::tcp::TcpTransport};
+
+        let options = SocketOptions {
+            congestion: Some(String::from("no_such_algorithm")),
+            ..Default::default()
+        };
+        let transport = TcpTransport::new(LOCALHOST, 0, HeaderFormat::Wire).with_options(options);
+        match transport.listen() {
+            Err(Error::Config(message)) => assert!(message.contains("TCP_CONGESTION")),
+            result => panic!("Unexpected result: {:?}", result.map(|_| ())),
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    pub fn test_tcp_info() {
+        use crate::message::header::HeaderFormat;
+        use crate::network::transport::tcp::TcpTransport;
+
+        let mut listener = TcpTransport::new(LOCALHOST, 0, HeaderFormat::Wire)
+            .listen()
+            .expect("Unable to listen");
+        let port = listener
+            .local()
+            .rsplit(':')
+            .next()
+            .unwrap()
+            .parse()
+            .unwrap();
+        let server = thread::spawn(move || {
+            let connection = listener.accept().expect("Unable to accept");
+            server::handle_client(connection, None).to_results("client")
+        });
+
+        let transport = TcpTransport::new(LOCALHOST, port, HeaderFormat::Wire);
+        let mut connection = transport.connect().expect("Unable to connect");
+        let msg = Message::new(SOURCE, DESTINATION, 64 * 1024, MTYPE);
+        for _ in 0..10 {
+            connection.send(&msg).expect("Unable to send message");
+        }
+        let tcp_info = connection.tcp_info().expect("No TCP_INFO");
+        assert!(tcp_info.rtt_microseconds > 0);
+        assert!(tcp_info.congestion_window_segments > 0);
+        assert_eq!(connection.retransmits(), Some(tcp_info.retransmits));
+        let close_message = Message::new(SOURCE, DESTINATION, 0, Type::Close);
+        connection
+            .send(&close_message)
+            .expect("Unable to send message");
+
+        // The server samples the connection when it closes
+        let results = server.join().expect("Server panicked");
+        assert!(results.summary.tcp_info.is_some());
+        assert!(results.summary.retransmits.is_some());
+    }
+
+    #[test]
+    pub fn test_dual_stack() {
+        use crate::message::header::HeaderFormat;
+        use crate::network::transport::tcp::TcpTransport;
+        use std::net::Ipv6Addr;
+
+        // Listens on every address by default
+        let mut listener = TcpTransport::new(LOCALHOST, 0, HeaderFormat::Wire)
+            .listen()
+            .expect("Unable to listen");
+        assert!(listener.local().starts_with("[::]:"));
+        let port = listener
+            .local()
+            .rsplit(':')
+            .next()
+            .unwrap()
+            .parse()
+            .unwrap();
+        let server = thread::spawn(move || {
+            (0..2)
+                .map(|_| listener.accept().expect("Unable to accept").peer())
+                .collect::<Vec<_>>()
+        });
+
+        let ipv6 = IpAddr::V6(Ipv6Addr::LOCALHOST);
+        for destination in [LOCALHOST, ipv6] {
+            let transport = TcpTransport::new(destination, port, HeaderFormat::Wire);
+            let connection = transport.connect().expect("Unable to connect");
+            assert_eq!(
+                connection.peer(),
+                format!("{}", to_string(destination, port))
+            );
+        }
+        // IPv4 clients are not shown as IPv4-mapped IPv6 addresses
+        let peers = server.join().expect("Server panicked");
+        assert!(peers[0].starts_with("127.0.0.1:"));
+        assert!(peers[1].starts_with("[::1]:"));
+    }
+
+    #[test]
+    pub fn test_source_address() {
+        use crate::message::header::HeaderFormat;
+        use crate::network::transport::tcp::TcpTransport;
+        use std::net::SocketAddr;
+
+        let mut listener = TcpTransport::new(LOCALHOST, 0, HeaderFormat::Wire)
+            .with_bind_address(LOCALHOST)
+            .listen()
+            .expect("Unable to listen");
+        assert!(listener.local().starts_with("127.0.0.1:"));
+        let port = listener
+            .local()
+            .rsplit(':')
+            .next()
+            .unwrap()
+            .parse()
+            .unwrap();
+        let server = thread::spawn(move || {
+            let mut connection = listener.accept().expect("Unable to accept");
+            let msg = connection.receive().expect("Unable to receive");
+            (connection.peer(), msg)
+        });
+
+        // The system chooses the source port
+        let source = SocketAddr::new(SOURCE, 0);
+        let mut connection = TcpTransport::new(DESTINATION, port, HeaderFormat::Wire)
+            .with_source(source)
+            .connect()
+            .expect("Unable to connect");
+        let client_address = connection.local_address().expect("No local address");
+        assert_eq!(client_address, SOURCE);
+        let msg = Message::new(client_address, DESTINATION, BODY_SIZE, MTYPE);
+        connection.send(&msg).expect("Unable to send message");
+
+        let (peer, received) = server.join().expect("Server panicked");
+        assert!(peer.starts_with("127.0.0.1:"));
+        assert_eq!(received.source(), SOURCE);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    pub fn test_unknown_interface() {
+        use crate::error::Error;
+        use crate::message::header::HeaderFormat;
+        use crate::network::{socket::SocketOptions, transport::tcp::TcpTransport};
+
+        let options = SocketOptions {
+            interface: Some("nowhere0".into()),
+            ..Default::default()
+        };
+        let transport = TcpTransport::new(LOCALHOST, 0, HeaderFormat::Wire).with_options(options);
+        assert!(matches!(transport.listen(), Err(Error::Config(_))));
+    }
+
+    /// A throughput test of 10 batches of 10 messages going `direction`
+    fn proposal(direction: crate::config::Direction) -> crate::network::control::Proposal {
+        use crate::config::Mode;
+        use crate::network::control::{Proposal, PROTOCOL_VERSION};
+
+        Proposal {
+            version: PROTOCOL_VERSION,
+            mode: Mode::Throughput,
+            direction,
+            message_size: BODY_SIZE,
+            batch_size: 10,
+            number_batches: 10,
+            duration_seconds: None,
+            warmup_seconds: 0.0,
+            cooldown_seconds: 0.0,
+            parallel: 1,
+            pacing: None,
+            payload: Payload::default(),
+            verify: false,
+        }
+    }
+
+    #[test]
+    pub fn test_proposal() {
+        use crate::config::Direction;
+        use crate::error::Error;
+        use crate::network::control::{Proposal, PROTOCOL_VERSION};
+
+        let proposal = proposal(Direction::Bidirectional);
+        let msg = proposal.to_message(SOURCE, DESTINATION).unwrap();
+        assert_eq!(msg.mtype(), Type::Propose);
+        assert_eq!(Proposal::from_message(&msg).unwrap(), proposal);
+        assert!(proposal.check(BODY_SIZE).is_ok());
+        assert!(proposal.check(BODY_SIZE - 1).is_err());
+        let crawling = Proposal {
+            pacing: Some(Pacing {
+                messages_per_second: 1e-300,
+                burst: 1,
+            }),
+            ..proposal.clone()
+        };
+        assert!(crawling.check(BODY_SIZE).is_err());
+        let backwards = Proposal {
+            warmup_seconds: -1.0,
+            ..proposal.clone()
+        };
+        assert!(backwards.check(BODY_SIZE).is_err());
+        let endless = Proposal {
+            duration_seconds: Some(1e30),
+            ..proposal.clone()
+        };
+        assert!(endless.check(BODY_SIZE).is_err());
+
+        let newer = Proposal {
+            version: PROTOCOL_VERSION + 1,
+            ..proposal.clone()
+        };
+        let msg = newer.to_message(SOURCE, DESTINATION).unwrap();
+        assert!(matches!(
+            Proposal::from_message(&msg),
+            Err(Error::Protocol(_))
+        ));
+        let mut garbled = proposal.to_message(SOURCE, DESTINATION).unwrap();
+        garbled.body_as_mut().unwrap()[0] = b'[';
+        assert!(Proposal::from_message(&garbled).is_err());
+        let benchmark = Message::new(SOURCE, DESTINATION, BODY_SIZE, MTYPE);
+        assert!(Proposal::from_message(&benchmark).is_err());
+    }
+
+    #[test]
+    pub fn test_rejected_proposal() {
+        use crate::config::{Direction, Mode};
+        use crate::error::Error;
+        use crate::message::header::HeaderFormat;
+        use crate::network::{control::Proposal, transport::tcp::TcpTransport};
+
+        let listener = TcpTransport::new(LOCALHOST, 0, HeaderFormat::Wire)
+            .listen()
+            .expect("Unable to listen");
+        let port = listener
+            .local()
+            .rsplit(':')
+            .next()
+            .unwrap()
+            .parse()
+            .unwrap();
+        let server = thread::spawn(move || server::serve(listener, 1, Some(2), None));
+
+        let transport = TcpTransport::new(DESTINATION, port, HeaderFormat::Wire);
+        let mut connection = transport.connect().expect("Unable to connect");
+        // Latency runs only go forward
+        let proposal = Proposal {
+            mode: Mode::Latency,
+            ..proposal(Direction::Reverse)
+        };
+        let result = proposal.propose(&mut connection, SOURCE, DESTINATION);
+        assert!(matches!(result, Err(Error::Rejected(_))));
+
+        // A body the server cannot allocate is refused before it tries
+        let mut connection = transport.connect().expect("Unable to connect");
+        let huge = Proposal {
+            mode: Mode::Throughput,
+            message_size: usize::MAX,
+            ..proposal
+        };
+        let result = huge.propose(&mut connection, SOURCE, DESTINATION);
+        assert!(matches!(result, Err(Error::Rejected(_))));
+        server
+            .join()
+            .expect("Server panicked")
+            .expect("Server failed");
+    }
+
+    #[test]
+    pub fn test_verification() {
+        use crate::config::Direction;
+        use crate::message::{header::HeaderFormat, payload::Pattern};
+        use crate::network::{control::Report, transport::tcp::TcpTransport};
+
+        let listener = TcpTransport::new(LOCALHOST, 0, HeaderFormat::Wire)
+            .listen()
+            .expect("Unable to listen");
+        let port = listener
+            .local()
+            .rsplit(':')
+            .next()
+            .unwrap()
+            .parse()
+            .unwrap();
+        let server = thread::spawn(move || server::serve(listener, 1, Some(1), None));
+
+        let transport = TcpTransport::new(DESTINATION, port, HeaderFormat::Wire);
+        let mut connection = transport.connect().expect("Unable to connect");
+        let payload = Payload::new(Pattern::Random, Vec::new());
+        let proposal = crate::network::control::Proposal {
+            payload: payload.clone(),
+            verify: true,
+            ..proposal(Direction::Forward)
+        };
+        proposal
+            .propose(&mut connection, SOURCE, DESTINATION)
+            .expect("Proposal refused");
+        let mut msg = Message::new(SOURCE, DESTINATION, BODY_SIZE, MTYPE);
+        payload.fill(&mut msg);
+        for _ in 0..3 {
+            connection.send(&msg).expect("Unable to send message");
+        }
+        msg.body_as_mut().unwrap()[7] ^= 0x10;
+        connection.send(&msg).expect("Unable to send message");
+        let close_message = Message::new(SOURCE, DESTINATION, 0, Type::Close);
+        connection
+            .send(&close_message)
+            .expect("Unable to send message");
+        Report::receive(&mut connection).expect("Unable to receive report");
+        Report::default()
+            .send(&mut connection, SOURCE, DESTINATION)
+            .expect("Unable to send report");
+
+        let results = server
+            .join()
+            .expect("Server panicked")
+            .expect("Server failed");
+        let verification = results[0].verification.clone().expect("Not verified");
+        assert_eq!(verification.pattern, Pattern::Random);
+        assert_eq!(verification.messages, 4);
+        assert_eq!(verification.corrupted_messages, 1);
+        assert_eq!(verification.corrupted_bytes, 1);
+        assert_eq!(verification.mismatch_offsets, vec![7]);
+    }
+
+    #[test]
+    pub fn test_warmup() {
+        use crate::config::Direction;
+        use crate::message::header::HeaderFormat;
+        use crate::network::{
+            control::{Proposal, Report},
+            transport::tcp::TcpTransport,
+        };
+        use std::time::Duration;
+
+        let listener = TcpTransport::new(LOCALHOST, 0, HeaderFormat::Wire)
+            .listen()
+            .expect("Unable to listen");
+        let port = listener
+            .local()
+            .rsplit(':')
+            .next()
+            .unwrap()
+            .parse()
+            .unwrap();
+        let server = thread::spawn(move || server::serve(listener, 1, Some(1), None));
+
+        let transport = TcpTransport::new(DESTINATION, port, HeaderFormat::Wire);
+        let mut connection = transport.connect().expect("Unable to connect");
+        let proposal = Proposal {
+            duration_seconds: Some(0.2),
+            warmup_seconds: 0.1,
+            cooldown_seconds: 0.1,
+            ..proposal(Direction::Forward)
+        };
+        proposal
+            .propose(&mut connection, SOURCE, DESTINATION)
+            .expect("Proposal refused");
+        // Three messages in warm-up, two measured, one in cool-down
+        let msg = Message::new(SOURCE, DESTINATION, BODY_SIZE, MTYPE);
+        for (count, pause) in [(3, 150), (2, 200), (1, 0)] {
+            for _ in 0..count {
+                connection.send(&msg).expect("Unable to send message");
+            }
+            thread::sleep(Duration::from_millis(pause));
+        }
+        let close_message = Message::new(SOURCE, DESTINATION, 0, Type::Close);
+        connection
+            .send(&close_message)
+            .expect("Unable to send message");
+        Report::receive(&mut connection).expect("Unable to receive report");
+        Report::default()
+            .send(&mut connection, SOURCE, DESTINATION)
+            .expect("Unable to send report");
+
+        let results = server
+            .join()
+            .expect("Server panicked")
+            .expect("Server failed");
+        assert_eq!(results[0].summary.messages, 2);
+        assert_eq!(results[0].summary.bytes, 2 * BODY_SIZE as u64);
+    }
+
+    #[test]
+    pub fn test_reverse() {
+        use crate::config::Direction;
+        use crate::message::header::HeaderFormat;
+        use crate::network::{control::Report, transport::tcp::TcpTransport};
+
+        let listener = TcpTransport::new(LOCALHOST, 0, HeaderFormat::Wire)
+            .listen()
+            .expect("Unable to listen");
+        let port = listener
+            .local()
+            .rsplit(':')
+            .next()
+            .unwrap()
+            .parse()
+            .unwrap();
+        let server = thread::spawn(move || server::serve(listener, 1, Some(1), None));
+
+        let transport = TcpTransport::new(DESTINATION, port, HeaderFormat::Wire);
+        let mut connection = transport.connect().expect("Unable to connect");
+        proposal(Direction::Reverse)
+            .propose(&mut connection, SOURCE, DESTINATION)
+            .expect("Proposal refused");
+        for _ in 0..10 {
+            let msg = connection.receive().expect("Unable to receive");
+            assert_eq!(msg.mtype(), MTYPE);
+            assert_eq!(msg.body_size(), BODY_SIZE);
+        }
+        let close_message = Message::new(SOURCE, DESTINATION, 0, Type::Close);
+        connection
+            .send(&close_message)
+            .expect("Unable to send message");
+        // The server stops sending once it gets Close
+        while connection.receive().expect("Unable to receive").mtype() != Type::Close {}
+        // Then reports what it sent, and waits for what was received
+        let report = Report::receive(&mut connection).expect("Unable to receive report");
+        let sent = report.reverse.expect("Missing reverse summary");
+        assert!(sent.messages >= 10);
+        assert_eq!(report.forward, None);
+        let received = Report {
+            reverse: Some(sent),
+            ..Default::default()
+        };
+        received
+            .send(&mut connection, SOURCE, DESTINATION)
+            .expect("Unable to send report");
+
+        // Only the server sent: its results hold that direction only, with
+        // the view of the client
+        let results = server
+            .join()
+            .expect("Server panicked")
+            .expect("Server failed");
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].direction, Some(Direction::Reverse));
+        assert!(results[0].summary.messages >= 10);
+        assert_eq!(results[0].peer_summary, Some(sent));
+    }
 }
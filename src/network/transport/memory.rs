@@ -0,0 +1,240 @@
+use super::{Connection, Listener, Transport};
+use crate::message::{framing::Framing, header::HeaderFormat, message::Message, Byte};
+use crate::network::io;
+use std::{
+    io::{ErrorKind, Read, Result, Write},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+};
+
+/// Carries messages through in-process pipes. Messages are still encoded
+/// and decoded, so the whole framing is exercised without binding a port.
+/// Clones of a transport share the same listener: the server listens on one
+/// clone while clients connect through others.
+///
+/// # Example
+/// ```
+/// # use benchmark_network::message::{message::Message, mtype::Type};
+/// # use benchmark_network::network::transport::{memory::MemoryTransport, Connection, Listener, Transport};
+/// # use std::net::{IpAddr, Ipv4Addr};
+/// # const LOCALHOST: IpAddr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+/// let transport = MemoryTransport::new();
+/// let mut listener = transport.listen().unwrap();
+/// let mut client = transport.connect().unwrap();
+/// let mut server = listener.accept().unwrap();
+///
+/// let msg = Message::new(LOCALHOST, LOCALHOST, 10, Type::Benchmark);
+/// client.send(&msg).unwrap();
+/// assert_eq!(server.receive().unwrap(), msg);
+/// ```
+#[derive(Debug, Clone)]
+pub struct MemoryTransport {
+    sender: Sender<MemoryConnection>,
+    receiver: Arc<Mutex<Receiver<MemoryConnection>>>,
+    counter: Arc<AtomicUsize>,
+    framing: Framing,
+}
+
+impl Default for MemoryTransport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemoryTransport {
+    pub fn new() -> MemoryTransport {
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            sender,
+            receiver: Arc::new(Mutex::new(receiver)),
+            counter: Arc::new(AtomicUsize::new(0)),
+            framing: Framing::default(),
+        }
+    }
+
+    /// Checksums sent messages and bounds received bodies as `framing`
+    /// says, on both ends of the connections this clone opens
+    pub fn with_framing(mut self, framing: Framing) -> MemoryTransport {
+        self.framing = framing;
+        self
+    }
+}
+
+impl Transport for MemoryTransport {
+    type Connection = MemoryConnection;
+    type Listener = MemoryListener;
+
+    fn connect(&self) -> crate::error::Result<MemoryConnection> {
+        let id = self.counter.fetch_add(1, Ordering::Relaxed);
+        let (client_pipe, server_pipe) = Pipe::pair();
+        let server = MemoryConnection {
+            pipe: server_pipe,
+            peer: format!("memory client {}", id),
+            framing: self.framing,
+        };
+        self.sender
+            .send(server)
+            .map_err(|_| std::io::Error::new(ErrorKind::ConnectionRefused, "no listener"))?;
+        Ok(MemoryConnection {
+            pipe: client_pipe,
+            peer: String::from("memory server"),
+            framing: self.framing,
+        })
+    }
+
+    fn listen(&self) -> crate::error::Result<MemoryListener> {
+        Ok(MemoryListener {
+            receiver: Arc::clone(&self.receiver),
+        })
+    }
+}
+
+/// Accepts in-memory connections
+#[derive(Debug)]
+pub struct MemoryListener {
+    receiver: Arc<Mutex<Receiver<MemoryConnection>>>,
+}
+
+impl Listener for MemoryListener {
+    type Connection = MemoryConnection;
+
+    fn accept(&mut self) -> Result<MemoryConnection> {
+        let receiver = self.receiver.lock().unwrap();
+        receiver
+            .recv()
+            .map_err(|_| std::io::Error::new(ErrorKind::NotConnected, "transport dropped"))
+    }
+
+    fn local(&self) -> String {
+        String::from("memory")
+    }
+}
+
+/// One end of an in-memory connection
+#[derive(Debug)]
+pub struct MemoryConnection {
+    pipe: Pipe,
+    peer: String,
+    framing: Framing,
+}
+
+impl Connection for MemoryConnection {
+    fn send(&mut self, msg: &Message) -> crate::error::Result<usize> {
+        io::send_with(&mut self.pipe, msg, &self.framing)
+    }
+
+    fn receive(&mut self) -> crate::error::Result<Message> {
+        io::read_with(&mut self.pipe, HeaderFormat::Wire, &self.framing)
+    }
+
+    fn peer(&self) -> String {
+        self.peer.clone()
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.pipe.sender.take();
+        Ok(())
+    }
+
+    fn try_clone(&self) -> Result<MemoryConnection> {
+        Ok(MemoryConnection {
+            pipe: self.pipe.try_clone()?,
+            peer: self.peer.clone(),
+            framing: self.framing,
+        })
+    }
+
+    fn framing(&self) -> Framing {
+        self.framing
+    }
+}
+
+/// A byte pipe: what is written on one end is read on the other one.
+/// Reading returns end of file once the other end is closed or dropped,
+/// with all of its clones.
+#[derive(Debug)]
+struct Pipe {
+    sender: Option<Sender<Vec<Byte>>>,
+    // Shared by the clones of an end; only one of them is expected to read
+    receiver: Arc<Mutex<Receiver<Vec<Byte>>>>,
+    pending: Vec<Byte>,
+    position: usize,
+}
+
+impl Pipe {
+    fn pair() -> (Pipe, Pipe) {
+        let (first_sender, first_receiver) = mpsc::channel();
+        let (second_sender, second_receiver) = mpsc::channel();
+        let first = Pipe {
+            sender: Some(first_sender),
+            receiver: Arc::new(Mutex::new(second_receiver)),
+            pending: Vec::new(),
+            position: 0,
+        };
+        let second = Pipe {
+            sender: Some(second_sender),
+            receiver: Arc::new(Mutex::new(first_receiver)),
+            pending: Vec::new(),
+            position: 0,
+        };
+        (first, second)
+    }
+
+    /// Another handle on the same end, with nothing pending
+    fn try_clone(&self) -> Result<Pipe> {
+        let sender = self
+            .sender
+            .clone()
+            .ok_or_else(|| std::io::Error::from(ErrorKind::BrokenPipe))?;
+        Ok(Pipe {
+            sender: Some(sender),
+            receiver: Arc::clone(&self.receiver),
+            pending: Vec::new(),
+            position: 0,
+        })
+    }
+}
+
+impl Read for Pipe {
+    fn read(&mut self, buf: &mut [Byte]) -> Result<usize> {
+        if self.position == self.pending.len() {
+            match self.receiver.lock().unwrap().recv() {
+                Ok(chunk) => {
+                    self.pending = chunk;
+                    self.position = 0;
+                }
+                // The other end is gone: end of file
+                Err(_) => return Ok(0),
+            }
+        }
+        let available = &self.pending[self.position..];
+        let size = available.len().min(buf.len());
+        buf[..size].copy_from_slice(&available[..size]);
+        self.position += size;
+        Ok(size)
+    }
+}
+
+impl Write for Pipe {
+    fn write(&mut self, buf: &[Byte]) -> Result<usize> {
+        // An empty chunk would read as end of file on the other end
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let sender = self
+            .sender
+            .as_ref()
+            .ok_or_else(|| std::io::Error::from(ErrorKind::BrokenPipe))?;
+        sender
+            .send(buf.to_vec())
+            .map_err(|_| std::io::Error::from(ErrorKind::BrokenPipe))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
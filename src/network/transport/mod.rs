@@ -0,0 +1,123 @@
+pub mod memory;
+pub mod tcp;
+#[cfg(unix)]
+pub mod unix;
+#[cfg(all(target_os = "linux", feature = "uring"))]
+pub mod uring;
+
+use crate::error::Result;
+use crate::interval::Sampler;
+use crate::message::{framing::Framing, message::Message};
+use crate::results::{SocketSettings, SyscallMetrics, TcpInfoMetrics};
+use std::{io, net::IpAddr};
+
+/// A message oriented, bidirectional connection between a client and a server.
+/// Benchmarks are written against this trait, whatever carries the bytes.
+pub trait Connection: Send {
+    /// Sends a message, returns the size of its body
+    fn send(&mut self, msg: &Message) -> Result<usize>;
+
+    /// Blocks until a whole message is received
+    fn receive(&mut self) -> Result<Message>;
+
+    /// Describes the other end of the connection, for reporting
+    fn peer(&self) -> String;
+
+    /// IP address this end of the connection is bound to, when it has one
+    fn local_address(&self) -> Option<IpAddr> {
+        None
+    }
+
+    /// Shuts the connection down in both directions
+    fn close(&mut self) -> io::Result<()>;
+
+    /// Another handle on the same connection, so that one thread sends
+    /// while another one receives. Each handle counts its own system calls.
+    fn try_clone(&self) -> io::Result<Self>
+    where
+        Self: Sized,
+    {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Sharing a connection is not supported by this transport",
+        ))
+    }
+
+    /// Sends small writes right away instead of coalescing them (Nagle's
+    /// algorithm). Transports that never delay writes ignore it.
+    fn set_nodelay(&mut self, _nodelay: bool) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Kernel statistics of the connection (`TCP_INFO`), when the transport
+    /// has them
+    fn tcp_info(&self) -> Option<TcpInfoMetrics> {
+        None
+    }
+
+    /// Samples `tcp_info` from another thread, for interval reports, when
+    /// the transport has it
+    fn tcp_info_sampler(&self) -> Option<Sampler> {
+        None
+    }
+
+    /// Running total of retransmitted segments, when the transport knows it
+    fn retransmits(&self) -> Option<u64> {
+        self.tcp_info().map(|tcp_info| tcp_info.retransmits)
+    }
+
+    /// Sends large bodies without copying them, when the transport can.
+    /// Bodies must then stay unchanged until `flush`.
+    fn enable_zerocopy(&mut self) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Zero-copy is not supported by this transport",
+        ))
+    }
+
+    /// Waits until the transport no longer holds on to the bodies sent
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Socket options as applied by the kernel, for TCP connections
+    fn socket(&self) -> Option<SocketSettings> {
+        None
+    }
+
+    /// I/O system calls made so far and messages sent or received, when
+    /// the transport counts them
+    fn syscalls(&self) -> Option<SyscallMetrics> {
+        None
+    }
+
+    /// How messages are checksummed and how large a body is read
+    fn framing(&self) -> Framing {
+        Framing::default()
+    }
+}
+
+/// Accepts incoming connections on the server side
+pub trait Listener {
+    type Connection: Connection + 'static;
+
+    /// Blocks until a client connects
+    fn accept(&mut self) -> io::Result<Self::Connection>;
+
+    /// Describes where the listener waits for clients, for reporting
+    fn local(&self) -> String;
+}
+
+/// A way of carrying messages between a client and a server: TCP, Unix
+/// sockets, in-memory pipes... A transport knows its endpoint, so that
+/// `connect` and `listen` need no further argument.
+pub trait Transport {
+    type Connection: Connection + 'static;
+    type Listener: Listener<Connection = Self::Connection>;
+
+    /// Connects to the server (client side)
+    fn connect(&self) -> Result<Self::Connection>;
+
+    /// Starts waiting for clients (server side)
+    fn listen(&self) -> Result<Self::Listener>;
+}
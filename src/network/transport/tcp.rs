@@ -0,0 +1,245 @@
+use super::{Connection, Listener, Transport};
+use crate::interval::Sampler;
+use crate::message::{framing::Framing, header::HeaderFormat, message::Message};
+#[cfg(target_os = "linux")]
+use crate::network::tcp_info;
+#[cfg(target_os = "linux")]
+use crate::network::zerocopy::{ZeroCopy, ZEROCOPY_THRESHOLD};
+use crate::network::{
+    io::{self, CountingStream},
+    socket::{self, SocketOptions},
+    util,
+};
+#[cfg(target_os = "linux")]
+use crate::results::TcpInfoMetrics;
+use crate::results::{SocketSettings, SyscallMetrics};
+use socket2::SockRef;
+use std::{
+    io::{Result, Write},
+    net::{IpAddr, Shutdown, SocketAddr, TcpListener, TcpStream},
+};
+
+/// Carries messages over TCP. Clients connect to `destination:port`,
+/// servers listen on `port` on every IPv4 and IPv6 address, or on a given one.
+#[derive(Debug, Clone)]
+pub struct TcpTransport {
+    destination: IpAddr,
+    port: u16,
+    format: HeaderFormat,
+    framing: Framing,
+    bind_address: IpAddr,
+    source: Option<SocketAddr>,
+    options: SocketOptions,
+}
+
+impl TcpTransport {
+    /// Creates a TCP transport reading headers in the given format
+    pub fn new(destination: IpAddr, port: u16, format: HeaderFormat) -> TcpTransport {
+        Self {
+            destination,
+            port,
+            format,
+            framing: Framing::default(),
+            bind_address: util::ANY_ADDRESS,
+            source: None,
+            options: SocketOptions::default(),
+        }
+    }
+
+    /// Listens on `bind_address` instead of every address
+    pub fn with_bind_address(mut self, bind_address: IpAddr) -> TcpTransport {
+        self.bind_address = bind_address;
+        self
+    }
+
+    /// Connects from `source` instead of wherever the system chooses. An
+    /// unspecified address only fixes the port, a port 0 only the address.
+    pub fn with_source(mut self, source: SocketAddr) -> TcpTransport {
+        self.source = Some(source);
+        self
+    }
+
+    /// Sets socket options on both the connecting and the listening sockets
+    pub fn with_options(mut self, options: SocketOptions) -> TcpTransport {
+        self.options = options;
+        self
+    }
+
+    /// Checksums sent messages and bounds received bodies as `framing` says
+    pub fn with_framing(mut self, framing: Framing) -> TcpTransport {
+        self.framing = framing;
+        self
+    }
+}
+
+impl Transport for TcpTransport {
+    type Connection = TcpConnection;
+    type Listener = TcpConnectionListener;
+
+    fn connect(&self) -> crate::error::Result<TcpConnection> {
+        let address = SocketAddr::new(self.destination, self.port);
+        let stream = self.options.connect(self.source, address)?;
+        Ok(TcpConnection::new(stream, self.format, self.framing))
+    }
+
+    fn listen(&self) -> crate::error::Result<TcpConnectionListener> {
+        let address = SocketAddr::new(self.bind_address, self.port);
+        let listener = self.options.listen(address)?;
+        Ok(TcpConnectionListener {
+            listener,
+            format: self.format,
+            framing: self.framing,
+        })
+    }
+}
+
+/// A connection over a TcpStream
+#[derive(Debug)]
+pub struct TcpConnection {
+    stream: CountingStream<TcpStream>,
+    format: HeaderFormat,
+    framing: Framing,
+    messages: u64,
+    // Large bodies are sent with MSG_ZEROCOPY once enabled
+    #[cfg(target_os = "linux")]
+    zerocopy: Option<ZeroCopy>,
+}
+
+impl TcpConnection {
+    pub fn new(stream: TcpStream, format: HeaderFormat, framing: Framing) -> TcpConnection {
+        Self {
+            stream: CountingStream::new(stream),
+            format,
+            framing,
+            messages: 0,
+            #[cfg(target_os = "linux")]
+            zerocopy: None,
+        }
+    }
+
+    /// Returns the underlying TcpStream
+    pub fn stream(&self) -> &TcpStream {
+        self.stream.get_ref()
+    }
+}
+
+impl Connection for TcpConnection {
+    fn send(&mut self, msg: &Message) -> crate::error::Result<usize> {
+        self.messages += 1;
+        #[cfg(target_os = "linux")]
+        if let Some(zerocopy) = self.zerocopy.as_mut() {
+            if let Some(body) = msg.body().filter(|body| body.len() >= ZEROCOPY_THRESHOLD) {
+                self.stream.write_all(&self.framing.encode(msg))?;
+                zerocopy.send_all(self.stream.get_ref(), body)?;
+                return Ok(body.len());
+            }
+        }
+        io::send_with(&mut self.stream, msg, &self.framing)
+    }
+
+    fn receive(&mut self) -> crate::error::Result<Message> {
+        self.messages += 1;
+        io::read_with(&mut self.stream, self.format, &self.framing)
+    }
+
+    fn peer(&self) -> String {
+        match self.stream().peer_addr() {
+            Ok(address) => util::canonical(address).to_string(),
+            Err(_) => String::from("unknown peer"),
+        }
+    }
+
+    fn local_address(&self) -> Option<IpAddr> {
+        let address = self.stream().local_addr().ok()?;
+        Some(address.ip().to_canonical())
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.stream().shutdown(Shutdown::Both)
+    }
+
+    fn try_clone(&self) -> Result<TcpConnection> {
+        Ok(TcpConnection::new(
+            self.stream().try_clone()?,
+            self.format,
+            self.framing,
+        ))
+    }
+
+    fn set_nodelay(&mut self, nodelay: bool) -> Result<()> {
+        self.stream().set_nodelay(nodelay)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn enable_zerocopy(&mut self) -> Result<()> {
+        if self.zerocopy.is_none() {
+            self.zerocopy = Some(ZeroCopy::enable(self.stream())?);
+        }
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    fn flush(&mut self) -> Result<()> {
+        if let Some(zerocopy) = self.zerocopy.as_mut() {
+            zerocopy.flush(self.stream.get_ref())?;
+            if zerocopy.copied() > 0 {
+                println!(
+                    "Zero-copy: the kernel copied {} of {} sends anyway",
+                    zerocopy.copied(),
+                    zerocopy.completed()
+                );
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    fn tcp_info(&self) -> Option<TcpInfoMetrics> {
+        tcp_info::sample(self.stream()).ok()
+    }
+
+    #[cfg(target_os = "linux")]
+    fn tcp_info_sampler(&self) -> Option<Sampler> {
+        tcp_info::sampler(self.stream())
+    }
+
+    fn socket(&self) -> Option<SocketSettings> {
+        socket::settings(SockRef::from(self.stream())).ok()
+    }
+
+    fn syscalls(&self) -> Option<SyscallMetrics> {
+        #[cfg(target_os = "linux")]
+        let calls = self.stream.calls() + self.zerocopy.as_ref().map_or(0, ZeroCopy::calls);
+        #[cfg(not(target_os = "linux"))]
+        let calls = self.stream.calls();
+        Some(SyscallMetrics::new(calls, self.messages))
+    }
+
+    fn framing(&self) -> Framing {
+        self.framing
+    }
+}
+
+/// Accepts TCP connections
+#[derive(Debug)]
+pub struct TcpConnectionListener {
+    listener: TcpListener,
+    format: HeaderFormat,
+    framing: Framing,
+}
+
+impl Listener for TcpConnectionListener {
+    type Connection = TcpConnection;
+
+    fn accept(&mut self) -> Result<TcpConnection> {
+        let (stream, _) = self.listener.accept()?;
+        Ok(TcpConnection::new(stream, self.format, self.framing))
+    }
+
+    fn local(&self) -> String {
+        match self.listener.local_addr() {
+            Ok(address) => address.to_string(),
+            Err(_) => String::from("unknown address"),
+        }
+    }
+}
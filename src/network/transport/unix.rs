@@ -0,0 +1,191 @@
+use super::{Connection, Listener, Transport};
+use crate::error::Error;
+use crate::message::{framing::Framing, header::HeaderFormat, message::Message};
+use crate::network::io::{self, CountingStream};
+use crate::results::SyscallMetrics;
+use std::{
+    fs,
+    io::Result,
+    net::Shutdown,
+    os::unix::{
+        fs::FileTypeExt,
+        net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+};
+
+/// Carries messages over a Unix domain stream socket, for host-local IPC.
+/// Clients connect to `path`, servers bind it.
+#[derive(Debug, Clone)]
+pub struct UnixTransport {
+    path: PathBuf,
+    format: HeaderFormat,
+    framing: Framing,
+}
+
+impl UnixTransport {
+    /// Creates a Unix socket transport reading headers in the given format
+    pub fn new<P: AsRef<Path>>(path: P, format: HeaderFormat) -> UnixTransport {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            format,
+            framing: Framing::default(),
+        }
+    }
+
+    /// Checksums sent messages and bounds received bodies as `framing` says
+    pub fn with_framing(mut self, framing: Framing) -> UnixTransport {
+        self.framing = framing;
+        self
+    }
+}
+
+impl Transport for UnixTransport {
+    type Connection = UnixConnection;
+    type Listener = UnixConnectionListener;
+
+    fn connect(&self) -> crate::error::Result<UnixConnection> {
+        let stream = UnixStream::connect(&self.path)?;
+        Ok(UnixConnection::new(
+            stream,
+            self.path.display().to_string(),
+            self.format,
+            self.framing,
+        ))
+    }
+
+    fn listen(&self) -> crate::error::Result<UnixConnectionListener> {
+        remove_stale_socket(&self.path)?;
+        let listener = UnixListener::bind(&self.path)?;
+        Ok(UnixConnectionListener {
+            listener,
+            path: self.path.clone(),
+            counter: 0,
+            format: self.format,
+            framing: self.framing,
+        })
+    }
+}
+
+/// Removes a socket file left behind by a previous run, which would make
+/// bind fail. Anything else at `path`, or the socket of a server still
+/// accepting connections, is left alone.
+fn remove_stale_socket(path: &Path) -> crate::error::Result<()> {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    if !metadata.file_type().is_socket() {
+        return Err(Error::Config(format!(
+            "{} exists and is not a socket",
+            path.display()
+        )));
+    }
+    if UnixStream::connect(path).is_ok() {
+        return Err(Error::Config(format!(
+            "{} is in use by a running server",
+            path.display()
+        )));
+    }
+    fs::remove_file(path)?;
+    Ok(())
+}
+
+/// A connection over a UnixStream
+#[derive(Debug)]
+pub struct UnixConnection {
+    stream: CountingStream<UnixStream>,
+    peer: String,
+    format: HeaderFormat,
+    framing: Framing,
+    messages: u64,
+}
+
+impl UnixConnection {
+    fn new(
+        stream: UnixStream,
+        peer: String,
+        format: HeaderFormat,
+        framing: Framing,
+    ) -> UnixConnection {
+        Self {
+            stream: CountingStream::new(stream),
+            peer,
+            format,
+            framing,
+            messages: 0,
+        }
+    }
+}
+
+impl Connection for UnixConnection {
+    fn send(&mut self, msg: &Message) -> crate::error::Result<usize> {
+        self.messages += 1;
+        io::send_with(&mut self.stream, msg, &self.framing)
+    }
+
+    fn receive(&mut self) -> crate::error::Result<Message> {
+        self.messages += 1;
+        io::read_with(&mut self.stream, self.format, &self.framing)
+    }
+
+    fn peer(&self) -> String {
+        self.peer.clone()
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.stream.get_ref().shutdown(Shutdown::Both)
+    }
+
+    fn try_clone(&self) -> Result<UnixConnection> {
+        let stream = self.stream.get_ref().try_clone()?;
+        Ok(UnixConnection::new(
+            stream,
+            self.peer.clone(),
+            self.format,
+            self.framing,
+        ))
+    }
+
+    fn syscalls(&self) -> Option<SyscallMetrics> {
+        Some(SyscallMetrics::new(self.stream.calls(), self.messages))
+    }
+
+    fn framing(&self) -> Framing {
+        self.framing
+    }
+}
+
+/// Accepts Unix socket connections. The socket file is removed when the
+/// listener is dropped.
+#[derive(Debug)]
+pub struct UnixConnectionListener {
+    listener: UnixListener,
+    path: PathBuf,
+    counter: usize,
+    format: HeaderFormat,
+    framing: Framing,
+}
+
+impl Listener for UnixConnectionListener {
+    type Connection = UnixConnection;
+
+    fn accept(&mut self) -> Result<UnixConnection> {
+        let (stream, _) = self.listener.accept()?;
+        // Clients of a Unix socket are usually unnamed: number them instead
+        self.counter += 1;
+        let peer = format!("{} client {}", self.path.display(), self.counter);
+        Ok(UnixConnection::new(stream, peer, self.format, self.framing))
+    }
+
+    fn local(&self) -> String {
+        self.path.display().to_string()
+    }
+}
+
+impl Drop for UnixConnectionListener {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
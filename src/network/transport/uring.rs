@@ -0,0 +1,389 @@
+use super::{Connection, Listener, Transport};
+use crate::interval::Sampler;
+use crate::message::{
+    framing::Framing,
+    header::{Header, HeaderFormat},
+    message::Message,
+    Byte,
+};
+use crate::network::{
+    io,
+    socket::{self, SocketOptions},
+    tcp_info, util,
+};
+use crate::results::{SocketSettings, SyscallMetrics, TcpInfoMetrics};
+use io_uring::{cqueue, opcode, squeue, types::Fd, IoUring};
+use socket2::SockRef;
+use std::{
+    io::{Error as IoError, ErrorKind, Read, Result},
+    net::{IpAddr, Shutdown, SocketAddr, TcpListener, TcpStream},
+    os::fd::AsRawFd,
+};
+
+// A header and a body in flight at once
+const RING_ENTRIES: u32 = 8;
+// Several messages are read at once when they are small
+const RECEIVE_BUFFER_SIZE: usize = 256 * 1024;
+// Indexes of the registered buffers
+const RECEIVE_BUFFER: u16 = 0;
+const HEADER_BUFFER: u16 = 1;
+// User data of the entries of a send
+const HEADER: u64 = 0;
+const BODY: u64 = 1;
+
+/// Carries messages over TCP like `TcpTransport`, but sends and receives
+/// through io_uring: the header and the body of a message are written with
+/// a single system call, and reads go into a registered buffer that holds
+/// several messages at once. Connecting and accepting are plain blocking
+/// calls, they are not on the hot path.
+#[derive(Debug, Clone)]
+pub struct UringTransport {
+    destination: IpAddr,
+    port: u16,
+    format: HeaderFormat,
+    framing: Framing,
+    bind_address: IpAddr,
+    source: Option<SocketAddr>,
+    options: SocketOptions,
+}
+
+impl UringTransport {
+    /// Creates an io_uring transport reading headers in the given format
+    pub fn new(destination: IpAddr, port: u16, format: HeaderFormat) -> UringTransport {
+        Self {
+            destination,
+            port,
+            format,
+            framing: Framing::default(),
+            bind_address: util::ANY_ADDRESS,
+            source: None,
+            options: SocketOptions::default(),
+        }
+    }
+
+    /// Listens on `bind_address` instead of every address
+    pub fn with_bind_address(mut self, bind_address: IpAddr) -> UringTransport {
+        self.bind_address = bind_address;
+        self
+    }
+
+    /// Connects from `source` instead of wherever the system chooses. An
+    /// unspecified address only fixes the port, a port 0 only the address.
+    pub fn with_source(mut self, source: SocketAddr) -> UringTransport {
+        self.source = Some(source);
+        self
+    }
+
+    /// Sets socket options on both the connecting and the listening sockets
+    pub fn with_options(mut self, options: SocketOptions) -> UringTransport {
+        self.options = options;
+        self
+    }
+
+    /// Checksums sent messages and bounds received bodies as `framing` says
+    pub fn with_framing(mut self, framing: Framing) -> UringTransport {
+        self.framing = framing;
+        self
+    }
+}
+
+impl Transport for UringTransport {
+    type Connection = UringConnection;
+    type Listener = UringConnectionListener;
+
+    fn connect(&self) -> crate::error::Result<UringConnection> {
+        let address = SocketAddr::new(self.destination, self.port);
+        let stream = self.options.connect(self.source, address)?;
+        Ok(UringConnection::new(stream, self.format, self.framing)?)
+    }
+
+    fn listen(&self) -> crate::error::Result<UringConnectionListener> {
+        let address = SocketAddr::new(self.bind_address, self.port);
+        let listener = self.options.listen(address)?;
+        Ok(UringConnectionListener {
+            listener,
+            format: self.format,
+            framing: self.framing,
+        })
+    }
+}
+
+/// A TcpStream driven by its own ring
+pub struct UringConnection {
+    // Declared first so that the ring is closed before its buffers are freed
+    ring: IoUring,
+    stream: TcpStream,
+    format: HeaderFormat,
+    framing: Framing,
+    header_buffer: Vec<Byte>,
+    receive_buffer: Vec<Byte>,
+    // Received bytes not read yet: receive_buffer[start..end]
+    start: usize,
+    end: usize,
+    messages: u64,
+    syscalls: u64,
+}
+
+impl UringConnection {
+    pub fn new(
+        stream: TcpStream,
+        format: HeaderFormat,
+        framing: Framing,
+    ) -> Result<UringConnection> {
+        let ring = IoUring::new(RING_ENTRIES)?;
+        let mut header_buffer = vec![0u8; Header::size()];
+        let mut receive_buffer = vec![0u8; RECEIVE_BUFFER_SIZE];
+        let buffers = [
+            libc::iovec {
+                iov_base: receive_buffer.as_mut_ptr().cast(),
+                iov_len: receive_buffer.len(),
+            },
+            libc::iovec {
+                iov_base: header_buffer.as_mut_ptr().cast(),
+                iov_len: header_buffer.len(),
+            },
+        ];
+        // Safety: both buffers are owned by the connection and never
+        // resized, so they outlive their registration in the ring
+        unsafe { ring.submitter().register_buffers(&buffers)? };
+        Ok(Self {
+            ring,
+            stream,
+            format,
+            framing,
+            header_buffer,
+            receive_buffer,
+            start: 0,
+            end: 0,
+            messages: 0,
+            syscalls: 0,
+        })
+    }
+
+    /// Returns the underlying TcpStream
+    pub fn stream(&self) -> &TcpStream {
+        &self.stream
+    }
+
+    /// Submits the queued entries and waits for `want` of them to complete
+    fn submit(&mut self, want: usize) -> Result<()> {
+        self.syscalls += 1;
+        self.ring.submit_and_wait(want)?;
+        Ok(())
+    }
+
+    /// Writes the header and the body linked together, so that the body
+    /// follows the header in a single submission. Returns how much of each
+    /// was written.
+    fn write_message(&mut self, body: &[Byte]) -> Result<(usize, usize)> {
+        let fd = Fd(self.stream.as_raw_fd());
+        let header = opcode::WriteFixed::new(
+            fd,
+            self.header_buffer.as_ptr(),
+            self.header_buffer.len() as u32,
+            HEADER_BUFFER,
+        )
+        .build()
+        .user_data(HEADER);
+        let entries = match body.len() {
+            0 => vec![header],
+            length => {
+                let length = length.min(u32::MAX as usize) as u32;
+                let body = opcode::Write::new(fd, body.as_ptr(), length)
+                    .build()
+                    .user_data(BODY);
+                vec![header.flags(squeue::Flags::IO_LINK), body]
+            }
+        };
+        // Safety: the header buffer and the body outlive the submission,
+        // which is waited for below
+        unsafe {
+            self.ring
+                .submission()
+                .push_multiple(&entries)
+                .map_err(|e| IoError::other(e.to_string()))?;
+        }
+        self.submit(entries.len())?;
+
+        let mut written = (0, 0);
+        let completions: Vec<cqueue::Entry> = self.ring.completion().collect();
+        for completion in completions {
+            let result = completion.result();
+            // A short header write cancels the body: it is written below
+            let size = match result {
+                result if result >= 0 => result as usize,
+                result if result == -libc::ECANCELED => 0,
+                result => return Err(IoError::from_raw_os_error(-result)),
+            };
+            match completion.user_data() {
+                HEADER => written.0 = size,
+                _ => written.1 = size,
+            }
+        }
+        Ok(written)
+    }
+
+    /// Writes `buffer` one entry at a time, until all of it is written
+    fn write_remaining(&mut self, mut buffer: &[Byte]) -> Result<()> {
+        let fd = Fd(self.stream.as_raw_fd());
+        while !buffer.is_empty() {
+            let length = buffer.len().min(u32::MAX as usize) as u32;
+            let write = opcode::Write::new(fd, buffer.as_ptr(), length).build();
+            // Safety: the buffer outlives the submission, which is waited for
+            unsafe {
+                self.ring
+                    .submission()
+                    .push(&write)
+                    .map_err(|e| IoError::other(e.to_string()))?;
+            }
+            self.submit(1)?;
+            let result = match self.ring.completion().next() {
+                Some(completion) => completion.result(),
+                None => return Err(IoError::other("Missing io_uring completion")),
+            };
+            match result {
+                0 => return Err(ErrorKind::WriteZero.into()),
+                result if result < 0 => return Err(IoError::from_raw_os_error(-result)),
+                result => buffer = &buffer[result as usize..],
+            }
+        }
+        Ok(())
+    }
+
+    /// Refills the registered receive buffer with a single read. Returns
+    /// the number of bytes read, 0 once the peer is gone.
+    fn fill(&mut self) -> Result<usize> {
+        let read = opcode::ReadFixed::new(
+            Fd(self.stream.as_raw_fd()),
+            self.receive_buffer.as_mut_ptr(),
+            self.receive_buffer.len() as u32,
+            RECEIVE_BUFFER,
+        )
+        .build();
+        // Safety: the receive buffer is registered and outlives the
+        // submission, which is waited for
+        unsafe {
+            self.ring
+                .submission()
+                .push(&read)
+                .map_err(|e| IoError::other(e.to_string()))?;
+        }
+        self.submit(1)?;
+        let result = match self.ring.completion().next() {
+            Some(completion) => completion.result(),
+            None => return Err(IoError::other("Missing io_uring completion")),
+        };
+        if result < 0 {
+            return Err(IoError::from_raw_os_error(-result));
+        }
+        self.start = 0;
+        self.end = result as usize;
+        Ok(self.end)
+    }
+}
+
+impl Read for UringConnection {
+    /// Reads from the receive buffer, refilling it once it is empty
+    fn read(&mut self, buf: &mut [Byte]) -> Result<usize> {
+        if self.start == self.end && self.fill()? == 0 {
+            return Ok(0);
+        }
+        let size = buf.len().min(self.end - self.start);
+        buf[..size].copy_from_slice(&self.receive_buffer[self.start..self.start + size]);
+        self.start += size;
+        Ok(size)
+    }
+}
+
+impl Connection for UringConnection {
+    fn send(&mut self, msg: &Message) -> crate::error::Result<usize> {
+        self.messages += 1;
+        let header = self.framing.encode(msg);
+        self.header_buffer.copy_from_slice(&header);
+        let body: &[Byte] = match msg.body() {
+            Some(body) => body,
+            None => &[],
+        };
+        let (header_written, body_written) = self.write_message(body)?;
+        if header_written < header.len() {
+            self.write_remaining(&header[header_written..])?;
+        }
+        self.write_remaining(&body[body_written..])?;
+        Ok(body.len())
+    }
+
+    fn receive(&mut self) -> crate::error::Result<Message> {
+        self.messages += 1;
+        let (format, framing) = (self.format, self.framing);
+        io::read_with(self, format, &framing)
+    }
+
+    fn peer(&self) -> String {
+        match self.stream.peer_addr() {
+            Ok(address) => util::canonical(address).to_string(),
+            Err(_) => String::from("unknown peer"),
+        }
+    }
+
+    fn local_address(&self) -> Option<IpAddr> {
+        let address = self.stream.local_addr().ok()?;
+        Some(address.ip().to_canonical())
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.stream.shutdown(Shutdown::Both)
+    }
+
+    /// The other handle gets a ring of its own
+    fn try_clone(&self) -> Result<UringConnection> {
+        UringConnection::new(self.stream.try_clone()?, self.format, self.framing)
+    }
+
+    fn set_nodelay(&mut self, nodelay: bool) -> Result<()> {
+        self.stream.set_nodelay(nodelay)
+    }
+
+    fn tcp_info(&self) -> Option<TcpInfoMetrics> {
+        tcp_info::sample(&self.stream).ok()
+    }
+
+    fn tcp_info_sampler(&self) -> Option<Sampler> {
+        tcp_info::sampler(&self.stream)
+    }
+
+    fn socket(&self) -> Option<SocketSettings> {
+        socket::settings(SockRef::from(&self.stream)).ok()
+    }
+
+    fn syscalls(&self) -> Option<SyscallMetrics> {
+        Some(SyscallMetrics::new(self.syscalls, self.messages))
+    }
+
+    fn framing(&self) -> Framing {
+        self.framing
+    }
+}
+
+/// Accepts TCP connections and gives each one a ring
+#[derive(Debug)]
+pub struct UringConnectionListener {
+    listener: TcpListener,
+    format: HeaderFormat,
+    framing: Framing,
+}
+
+impl Listener for UringConnectionListener {
+    type Connection = UringConnection;
+
+    fn accept(&mut self) -> Result<UringConnection> {
+        let (stream, _) = self.listener.accept()?;
+        UringConnection::new(stream, self.format, self.framing)
+    }
+
+    fn local(&self) -> String {
+        match self.listener.local_addr() {
+            Ok(address) => address.to_string(),
+            Err(_) => String::from("unknown address"),
+        }
+    }
+}
@@ -0,0 +1,407 @@
+use super::{
+    socket,
+    util::{self, to_string},
+};
+use crate::error::{Error, Result};
+use crate::interval::IntervalReporter;
+use crate::message::{framing, header::Header, message::Message, mtype::Type, Byte};
+use crate::results::{DatagramMetrics, Metrics, StreamResults};
+use crate::unit::*;
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    io::ErrorKind,
+    net::{IpAddr, SocketAddr, UdpSocket},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// Largest UDP payload that fits in an IPv4 datagram
+pub const MAXIMUM_DATAGRAM_SIZE: usize = 65_507;
+
+/// Largest message body that fits in a datagram next to its header
+pub const MAXIMUM_BODY_SIZE: usize = MAXIMUM_DATAGRAM_SIZE - Header::size();
+
+/// A `Close` datagram may be lost like any other, so it is sent a few times
+const CLOSE_REPEAT: usize = 3;
+
+/// The server gives up on silent clients after this long
+const IDLE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Sequence numbers behind the highest one received that are remembered
+/// as missing, to tell a late datagram from a duplicate. Bounds the memory
+/// of a tracker whatever the sequence numbers it is sent.
+pub const REORDER_WINDOW: u64 = 4096;
+
+/// Returns the current time in nanoseconds since the UNIX epoch
+pub fn now_nanos() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_nanos() as u64)
+        .unwrap_or_default()
+}
+
+/// Creates a UDP socket connected to the designated server on a specific
+/// port, sending from `source` or from wherever the system chooses
+pub fn connect(source: Option<SocketAddr>, destination: IpAddr, port: u16) -> Result<UdpSocket> {
+    let socket = match source {
+        Some(source) => UdpSocket::bind(source).map_err(|e| {
+            Error::Config(format!("Unable to bind source address {}: {}", source, e))
+        })?,
+        None => UdpSocket::bind(SocketAddr::new(util::unspecified(destination), 0))?,
+    };
+    socket.connect(to_string(destination, port))?;
+    Ok(socket)
+}
+
+/// Sends a Message as a single datagram (header followed by body)
+/// on a connected socket. Returns the body size.
+pub fn send(socket: &UdpSocket, msg: &Message) -> Result<usize> {
+    let mut datagram: Vec<Byte> = Vec::with_capacity(Header::size() + msg.body_size());
+    datagram.extend_from_slice(&msg.header().encode());
+    if let Some(body) = msg.body() {
+        datagram.extend_from_slice(body);
+    }
+    if datagram.len() > MAXIMUM_DATAGRAM_SIZE {
+        return Err(Error::Config(format!(
+            "datagram of {} bytes exceeds the UDP limit of {} bytes",
+            datagram.len(),
+            MAXIMUM_DATAGRAM_SIZE
+        )));
+    }
+    socket.send(&datagram)?;
+    Ok(msg.body_size())
+}
+
+/// Decodes a Message from a received datagram, checked against the
+/// checksum its header carries, if any
+pub fn decode(datagram: &[Byte]) -> Result<Message> {
+    let header = Header::decode(datagram)?;
+    let body = &datagram[Header::size()..];
+    if body.len() != header.body_size() {
+        return Err(Error::Protocol(format!(
+            "datagram body of {} bytes, header announced {}",
+            body.len(),
+            header.body_size()
+        )));
+    }
+    framing::verify(&header, datagram, body)?;
+    let body = match body.len() {
+        0 => None,
+        _ => Some(Box::new(body.to_vec())),
+    };
+    Ok(Message::compose(header, body))
+}
+
+/// Sends the `Close` datagram ending a stream of `sent` datagrams
+pub fn close(socket: &UdpSocket, source: IpAddr, destination: IpAddr, sent: u64) {
+    let mut msg = Message::new(source, destination, 0, Type::Close);
+    msg.header_as_mut().set_sequence(sent);
+    for repeat in 0..CLOSE_REPEAT {
+        if let Err(e) = send(socket, &msg) {
+            // Once the server is gone, repeats are refused: that's expected
+            if repeat == 0 {
+                println!("Unable to send close datagram: {}", e);
+            }
+            break;
+        }
+    }
+}
+
+/// Tracks the datagrams received from one client: loss, reordering,
+/// duplicates and interarrival jitter as defined in RFC 3550. Loss is
+/// counted as the datagrams expected minus those received, as iperf does;
+/// only the missing ones within `REORDER_WINDOW` are remembered.
+#[derive(Debug, Clone)]
+pub struct SequenceTracker {
+    next_expected: u64,
+    missing: BTreeSet<u64>,
+    received: u64,
+    received_bytes: u64,
+    reordered: u64,
+    duplicated: u64,
+    jitter: f64,
+    last_transit: Option<i64>,
+    first_arrival: Option<Instant>,
+    last_arrival: Option<Instant>,
+}
+
+impl Default for SequenceTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SequenceTracker {
+    pub fn new() -> SequenceTracker {
+        Self {
+            next_expected: 0,
+            missing: BTreeSet::new(),
+            received: 0,
+            received_bytes: 0,
+            reordered: 0,
+            duplicated: 0,
+            jitter: 0.0,
+            last_transit: None,
+            first_arrival: None,
+            last_arrival: None,
+        }
+    }
+
+    /// Records a datagram with a body of `size` bytes carrying the given
+    /// header, received at `arrival` (nanoseconds since the UNIX epoch).
+    /// A datagram older than the reorder window cannot be told from a
+    /// duplicate, and is counted as one. Returns whether the datagram was
+    /// new.
+    pub fn record(&mut self, header: &Header, size: usize, arrival: u64) -> bool {
+        let now = Instant::now();
+        self.first_arrival.get_or_insert(now);
+        self.last_arrival = Some(now);
+
+        let sequence = header.sequence();
+        if sequence >= self.next_expected {
+            self.skip_to(sequence);
+            self.next_expected = sequence.saturating_add(1);
+        } else if self.missing.remove(&sequence) {
+            self.reordered += 1;
+        } else {
+            self.duplicated += 1;
+            return false;
+        }
+        self.received += 1;
+        self.received_bytes += size as u64;
+
+        // RFC 3550 section 6.4.1: J += (|D| - J) / 16, where D is the
+        // difference in transit time of two consecutive datagrams.
+        // Clock offset between both hosts cancels out in D.
+        let transit = arrival as i64 - header.timestamp() as i64;
+        if let Some(last_transit) = self.last_transit {
+            let difference = (transit - last_transit).unsigned_abs() as f64;
+            self.jitter += (difference - self.jitter) / 16.0;
+        }
+        self.last_transit = Some(transit);
+        true
+    }
+
+    /// Ends the stream: the sender announced `sent` datagrams, any of
+    /// them not received yet is lost.
+    pub fn finish(&mut self, sent: u64) {
+        if sent > self.next_expected {
+            self.skip_to(sent);
+            self.next_expected = sent;
+        }
+    }
+
+    /// Remembers the sequence numbers from the next expected one up to
+    /// `sequence` as missing, within the reorder window, and forgets those
+    /// that fell out of it
+    fn skip_to(&mut self, sequence: u64) {
+        let floor = sequence.saturating_sub(REORDER_WINDOW);
+        self.missing = self.missing.split_off(&floor);
+        self.missing.extend(self.next_expected.max(floor)..sequence);
+    }
+
+    pub fn received(&self) -> u64 {
+        self.received
+    }
+
+    pub fn received_bytes(&self) -> u64 {
+        self.received_bytes
+    }
+
+    pub fn lost(&self) -> u64 {
+        self.next_expected.saturating_sub(self.received)
+    }
+
+    /// Lost datagrams as a percentage of those expected
+    pub fn loss_percentage(&self) -> f64 {
+        match self.next_expected {
+            0 => 0.0,
+            expected => self.lost() as f64 * 100.0 / expected as f64,
+        }
+    }
+
+    pub fn reordered(&self) -> u64 {
+        self.reordered
+    }
+
+    pub fn duplicated(&self) -> u64 {
+        self.duplicated
+    }
+
+    /// Interarrival jitter in nanoseconds
+    pub fn jitter(&self) -> f64 {
+        self.jitter
+    }
+
+    /// Time between the first and the last datagram received
+    pub fn elapsed(&self) -> Duration {
+        match (self.first_arrival, self.last_arrival) {
+            (Some(first), Some(last)) => last - first,
+            _ => Duration::ZERO,
+        }
+    }
+
+    /// Results of the stream; bytes count message bodies
+    pub fn to_results(&self, peer_address: SocketAddr) -> StreamResults {
+        StreamResults {
+            peer: peer_address.to_string(),
+            summary: Metrics::new(
+                Duration::ZERO,
+                self.elapsed(),
+                self.received_bytes,
+                self.received,
+                None,
+            ),
+            datagrams: Some(DatagramMetrics {
+                received: self.received,
+                lost: self.lost(),
+                loss_percentage: self.loss_percentage(),
+                reordered: self.reordered,
+                duplicated: self.duplicated,
+                jitter_nanoseconds: self.jitter,
+            }),
+            ..Default::default()
+        }
+    }
+
+    pub fn print(&self, peer_address: SocketAddr) {
+        let elapsed = self.elapsed().as_secs_f64();
+        let received_bytes = self.received_bytes as f64;
+        let throughput = match elapsed {
+            elapsed if elapsed > 0.0 => received_bytes * 8.0 / elapsed,
+            _ => 0.0,
+        };
+        println!("UDP report for {}:", peer_address);
+        println!(
+            "Throughput: {}; Received bytes: {}; Time elapsed: {:.2}s",
+            throughput_to_string(throughput),
+            data_to_string(received_bytes),
+            elapsed
+        );
+        println!(
+            "Datagrams: received {}, lost {} ({:.2}%), reordered {}, duplicated {}",
+            self.received,
+            self.lost(),
+            self.loss_percentage(),
+            self.reordered,
+            self.duplicated
+        );
+        println!("Jitter: {}", duration_to_string(self.jitter));
+    }
+}
+
+/// A client the server receives datagrams from, and its interval reports
+struct Client {
+    tracker: SequenceTracker,
+    reporter: Option<IntervalReporter>,
+}
+
+impl Client {
+    fn new(peer_address: SocketAddr, interval: Option<Duration>) -> Client {
+        let label = format!("[{}] ", peer_address);
+        Self {
+            tracker: SequenceTracker::default(),
+            reporter: interval.map(|interval| IntervalReporter::new(interval).with_label(&label)),
+        }
+    }
+
+    /// Prints what was received from the client and returns it
+    fn finish(mut self, peer_address: SocketAddr) -> StreamResults {
+        let mut intervals = Vec::new();
+        if let Some(reporter) = self.reporter.as_mut() {
+            reporter.finish(None);
+            intervals = reporter.intervals().to_vec();
+        }
+        self.tracker.print(peer_address);
+        StreamResults {
+            intervals,
+            ..self.tracker.to_results(peer_address)
+        }
+    }
+}
+
+/// Listens for datagrams on `address` and reports loss, reordering,
+/// duplicates and jitter for every client once it sends `Close`. Stops after
+/// `is_limited` clients, or once clients have been silent for a while.
+/// With an `interval`, what each client sent is also reported every
+/// `interval`. Returns the results of every client.
+pub fn listen(
+    address: SocketAddr,
+    is_limited: Option<usize>,
+    interval: Option<Duration>,
+) -> Result<Vec<StreamResults>> {
+    let socket = socket::bind_datagram(address)?;
+    socket.set_read_timeout(Some(IDLE_TIMEOUT))?;
+
+    println!("Server listening on UDP {}", socket.local_addr()?);
+
+    let mut clients: HashMap<SocketAddr, Client> = HashMap::new();
+    let mut finished: HashSet<SocketAddr> = HashSet::new();
+    let mut served = 0;
+    let mut results = Vec::new();
+    let mut buffer = vec![0u8; MAXIMUM_DATAGRAM_SIZE];
+    loop {
+        let (size, peer_address) = match socket.recv_from(&mut buffer) {
+            Ok((size, peer_address)) => (size, util::canonical(peer_address)),
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                if clients.is_empty() {
+                    continue;
+                }
+                println!(
+                    "No datagram for {:?}, closing remaining clients",
+                    IDLE_TIMEOUT
+                );
+                for (peer_address, client) in clients.drain() {
+                    results.push(client.finish(peer_address));
+                }
+                break;
+            }
+            Err(e) => {
+                println!("Unable to receive datagram: {}", e);
+                break;
+            }
+        };
+        let arrival = now_nanos();
+
+        let message = match decode(&buffer[..size]) {
+            Ok(message) => message,
+            Err(e) => {
+                println!("Dropping malformed datagram from {}\n {}", peer_address, e);
+                continue;
+            }
+        };
+
+        match message.mtype() {
+            // Repeated Close datagrams of finished clients are ignored
+            Type::Close if finished.contains(&peer_address) => {}
+            Type::Close => {
+                let mut client = clients
+                    .remove(&peer_address)
+                    .unwrap_or_else(|| Client::new(peer_address, None));
+                client.tracker.finish(message.header().sequence());
+                results.push(client.finish(peer_address));
+                finished.insert(peer_address);
+                served += 1;
+                if is_limited.is_some_and(|limit| served >= limit) {
+                    break;
+                }
+            }
+            _ => {
+                // A new client may reuse the address of a finished one
+                finished.remove(&peer_address);
+                let client = clients.entry(peer_address).or_insert_with(|| {
+                    println!("New UDP client: {}", peer_address);
+                    Client::new(peer_address, interval)
+                });
+                let body_size = message.body_size();
+                if client.tracker.record(&message.header(), body_size, arrival) {
+                    if let Some(reporter) = client.reporter.as_mut() {
+                        reporter.record(body_size);
+                    }
+                }
+            }
+        }
+    }
+
+    println!("Dropping socket");
+    Ok(results)
+}
@@ -1,5 +1,58 @@
-use std::net::IpAddr;
+use std::io::{Error, ErrorKind, Result};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs};
 
+/// Where servers listen unless told otherwise: every IPv6 address, and
+/// every IPv4 one through the same dual-stack socket
+pub const ANY_ADDRESS: IpAddr = IpAddr::V6(Ipv6Addr::UNSPECIFIED);
+
+/// The unspecified address of the same IP version as `address`
+pub fn unspecified(address: IpAddr) -> IpAddr {
+    match address {
+        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    }
+}
+
+/// Formats `host:port`, with IPv6 addresses in brackets: `[::1]:7777`
 pub fn to_string(host: IpAddr, port: u16) -> String {
-    format!("{}:{}", host.to_string(), port.to_string())
+    SocketAddr::new(host, port).to_string()
+}
+
+/// Resolves a host name, or parses an IP address, to its first address
+pub fn resolve(host: &str) -> Result<IpAddr> {
+    if let Ok(address) = host.parse::<IpAddr>() {
+        return Ok(address);
+    }
+    match (host, 0).to_socket_addrs()?.next() {
+        Some(address) => Ok(address.ip()),
+        None => Err(Error::new(ErrorKind::NotFound, "no address found")),
+    }
+}
+
+/// IPv4 peers of a dual-stack socket show up as `::ffff:a.b.c.d`: gives
+/// their plain IPv4 address back
+pub fn canonical(address: SocketAddr) -> SocketAddr {
+    SocketAddr::new(address.ip().to_canonical(), address.port())
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{canonical, resolve, to_string};
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+    #[test]
+    fn test_addresses() {
+        let ipv6 = IpAddr::V6(Ipv6Addr::LOCALHOST);
+        assert_eq!(to_string(ipv6, 7777), "[::1]:7777");
+        let ipv4 = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        assert_eq!(to_string(ipv4, 7777), "127.0.0.1:7777");
+
+        assert_eq!(resolve("::1").unwrap(), ipv6);
+        assert!(resolve("localhost").unwrap().is_loopback());
+        assert!(resolve("nowhere.invalid").is_err());
+
+        let mapped = SocketAddr::new(IpAddr::V6(Ipv4Addr::LOCALHOST.to_ipv6_mapped()), 7777);
+        assert_eq!(canonical(mapped), SocketAddr::new(ipv4, 7777));
+    }
 }
@@ -0,0 +1,184 @@
+use crate::message::Byte;
+use std::{
+    io::{self, ErrorKind},
+    mem,
+    net::TcpStream,
+    os::fd::{AsRawFd, RawFd},
+};
+
+// Not exported by libc: from linux/errqueue.h and asm-generic/socket.h
+const SO_ZEROCOPY: libc::c_int = 60;
+const SO_EE_ORIGIN_ZEROCOPY: u8 = 5;
+const SO_EE_CODE_ZEROCOPY_COPIED: u8 = 1;
+// How long to wait for completions before giving up
+const POLL_TIMEOUT_MILLISECONDS: libc::c_int = 5000;
+
+/// Bodies smaller than this are copied anyway: below it, pinning the pages
+/// and reading the completion costs more than the copy
+pub const ZEROCOPY_THRESHOLD: usize = 16 * 1024;
+
+/// Sends buffers with `MSG_ZEROCOPY`: the kernel reads them in place instead
+/// of copying them into the socket buffer, and tells once it is done with
+/// each send on the socket error queue. Until then a buffer must stay
+/// unchanged, which `flush` waits for.
+#[derive(Debug, Default)]
+pub struct ZeroCopy {
+    // Sends are numbered by the kernel, from 0
+    sent: u32,
+    completed: u32,
+    // Completions the kernel copied after all, e.g. over loopback
+    copied: u32,
+    calls: u64,
+}
+
+impl ZeroCopy {
+    /// Allows zero-copy sends on `stream`
+    pub fn enable(stream: &TcpStream) -> io::Result<ZeroCopy> {
+        let enable: libc::c_int = 1;
+        // Safety: the option value is a valid c_int for the duration of the call
+        let result = unsafe {
+            libc::setsockopt(
+                stream.as_raw_fd(),
+                libc::SOL_SOCKET,
+                SO_ZEROCOPY,
+                (&enable as *const libc::c_int).cast(),
+                mem::size_of::<libc::c_int>() as libc::socklen_t,
+            )
+        };
+        match result {
+            0 => Ok(ZeroCopy::default()),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+
+    /// Sends all of `buffer` without copying it, then collects the
+    /// completions already available
+    pub fn send_all(&mut self, stream: &TcpStream, mut buffer: &[Byte]) -> io::Result<()> {
+        let fd = stream.as_raw_fd();
+        while !buffer.is_empty() {
+            self.calls += 1;
+            // Safety: the buffer is valid for its length, and is kept
+            // unchanged by the caller until `flush`
+            let result =
+                unsafe { libc::send(fd, buffer.as_ptr().cast(), buffer.len(), libc::MSG_ZEROCOPY) };
+            if result < 0 {
+                let error = io::Error::last_os_error();
+                match error.kind() {
+                    ErrorKind::Interrupted => continue,
+                    // Too many sends pinned at once: wait for some to complete
+                    _ if error.raw_os_error() == Some(libc::ENOBUFS) => {
+                        self.wait(fd)?;
+                        continue;
+                    }
+                    _ => return Err(error),
+                }
+            }
+            self.sent = self.sent.wrapping_add(1);
+            buffer = &buffer[result as usize..];
+        }
+        self.collect(fd)
+    }
+
+    /// Waits until the kernel is done with every buffer sent so far
+    pub fn flush(&mut self, stream: &TcpStream) -> io::Result<()> {
+        let fd = stream.as_raw_fd();
+        while self.completed != self.sent {
+            self.wait(fd)?;
+        }
+        Ok(())
+    }
+
+    /// Sends completed so far
+    pub fn completed(&self) -> u32 {
+        self.completed
+    }
+
+    /// Completed sends the kernel had to copy after all
+    pub fn copied(&self) -> u32 {
+        self.copied
+    }
+
+    /// System calls made to send and collect completions
+    pub fn calls(&self) -> u64 {
+        self.calls
+    }
+
+    /// Blocks until completions are available, then collects them
+    fn wait(&mut self, fd: RawFd) -> io::Result<()> {
+        let mut poll = libc::pollfd {
+            fd,
+            // The error queue is always polled for
+            events: 0,
+            revents: 0,
+        };
+        self.calls += 1;
+        // Safety: `poll` is a valid pollfd for the duration of the call
+        match unsafe { libc::poll(&mut poll, 1, POLL_TIMEOUT_MILLISECONDS) } {
+            0 => Err(io::Error::new(
+                ErrorKind::TimedOut,
+                "No zero-copy completion received",
+            )),
+            result if result < 0 => Err(io::Error::last_os_error()),
+            _ => self.collect(fd),
+        }
+    }
+
+    /// Reads the completions waiting on the error queue, without blocking
+    fn collect(&mut self, fd: RawFd) -> io::Result<()> {
+        loop {
+            // u64 keeps the control messages aligned
+            let mut control = [0u64; 16];
+            // Safety: msghdr is plain data, zeroed is a valid empty header
+            let mut header: libc::msghdr = unsafe { mem::zeroed() };
+            header.msg_control = control.as_mut_ptr().cast();
+            header.msg_controllen = mem::size_of_val(&control) as _;
+
+            self.calls += 1;
+            // Safety: `header` points to `control`, alive during the call
+            let result =
+                unsafe { libc::recvmsg(fd, &mut header, libc::MSG_ERRQUEUE | libc::MSG_DONTWAIT) };
+            if result < 0 {
+                let error = io::Error::last_os_error();
+                return match error.kind() {
+                    ErrorKind::WouldBlock => Ok(()),
+                    ErrorKind::Interrupted => continue,
+                    _ => Err(error),
+                };
+            }
+
+            // Safety: the control messages were filled in by recvmsg
+            let mut message = unsafe { libc::CMSG_FIRSTHDR(&header) };
+            while !message.is_null() {
+                // Safety: `message` points into `control`, and the data of
+                // an IP_RECVERR message is a sock_extended_err
+                let error = unsafe {
+                    let message_ref = &*message;
+                    let is_error = (message_ref.cmsg_level == libc::SOL_IP
+                        && message_ref.cmsg_type == libc::IP_RECVERR)
+                        || (message_ref.cmsg_level == libc::SOL_IPV6
+                            && message_ref.cmsg_type == libc::IPV6_RECVERR);
+                    match is_error {
+                        true => Some(
+                            libc::CMSG_DATA(message)
+                                .cast::<libc::sock_extended_err>()
+                                .read_unaligned(),
+                        ),
+                        false => None,
+                    }
+                };
+                if let Some(error) = error {
+                    if error.ee_errno == 0 && error.ee_origin == SO_EE_ORIGIN_ZEROCOPY {
+                        // Completions cover the range of sends [ee_info, ee_data]
+                        let count = error.ee_data.wrapping_sub(error.ee_info).wrapping_add(1);
+                        self.completed = self.completed.wrapping_add(count);
+                        if error.ee_code & SO_EE_CODE_ZEROCOPY_COPIED != 0 {
+                            self.copied = self.copied.wrapping_add(count);
+                        }
+                    }
+                }
+                // Safety: both pointers come from the same header
+                message = unsafe { libc::CMSG_NXTHDR(&header, message) };
+            }
+        }
+    }
+}
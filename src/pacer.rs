@@ -0,0 +1,193 @@
+use crate::{
+    results::PacingMetrics,
+    unit::{duration_to_string, throughput_to_string},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    hint, thread,
+    time::{Duration, Instant},
+};
+
+// Sleeps shorter than this are spun instead: the scheduler oversleeps by
+// about as much
+const SPIN: Duration = Duration::from_micros(100);
+
+/// Target rate of a paced sender
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct Pacing {
+    pub messages_per_second: f64,
+    /// Messages sent back to back when the sender is ahead, to make up for
+    /// sleeps that last too long
+    pub burst: u32,
+}
+
+impl Pacing {
+    /// Time between two messages, unless the rate is too low for it to be
+    /// represented
+    pub fn period(&self) -> Option<Duration> {
+        Duration::try_from_secs_f64(1.0 / self.messages_per_second).ok()
+    }
+}
+
+/// Token bucket: tokens come in at the target rate, up to `burst` of them,
+/// and each message takes one. A sender slower than the rate falls behind
+/// its schedule, message `n` being due `n` periods after the first one;
+/// the bucket only lets it catch up by `burst` messages.
+#[derive(Debug)]
+pub struct Pacer {
+    pacing: Pacing,
+    period: Duration,
+    // Negative while messages are sent ahead of their token
+    tokens: f64,
+    start: Option<Instant>,
+    refilled_at: Instant,
+    messages: u64,
+    last_send: Instant,
+    lag: Duration,
+    max_lag: Duration,
+}
+
+impl Pacer {
+    /// A pacer whose bucket starts full. The rate is expected to have a
+    /// `period`.
+    pub fn new(pacing: Pacing) -> Pacer {
+        let now = Instant::now();
+        Self {
+            pacing,
+            period: pacing.period().unwrap_or(Duration::MAX),
+            tokens: pacing.burst as f64,
+            start: None,
+            refilled_at: now,
+            messages: 0,
+            last_send: now,
+            lag: Duration::ZERO,
+            max_lag: Duration::ZERO,
+        }
+    }
+
+    /// Takes a token for the next message. Returns how long to wait before
+    /// sending it.
+    pub fn delay(&mut self) -> Duration {
+        let now = Instant::now();
+        let start = *self.start.get_or_insert(now);
+        let refill = (now - self.refilled_at).as_secs_f64() * self.pacing.messages_per_second;
+        self.tokens = (self.tokens + refill).min(self.pacing.burst as f64) - 1.0;
+        self.refilled_at = now;
+        let delay = match self.tokens {
+            tokens if tokens >= 0.0 => Duration::ZERO,
+            tokens => self.period.mul_f64(-tokens),
+        };
+
+        let send = now + delay;
+        let due = start + self.period.mul_f64(self.messages as f64);
+        self.lag = send.saturating_duration_since(due);
+        self.max_lag = self.max_lag.max(self.lag);
+        self.messages += 1;
+        self.last_send = send;
+        delay
+    }
+
+    /// What was achieved against the target, for messages of `body_size`
+    pub fn finish(&self, body_size: usize) -> PacingMetrics {
+        let bits = body_size as f64 * 8.0;
+        let elapsed = match self.start {
+            Some(start) => (self.last_send - start).as_secs_f64(),
+            None => 0.0,
+        };
+        // The first message starts the clock
+        let achieved = match elapsed {
+            elapsed if elapsed > 0.0 => self.messages.saturating_sub(1) as f64 / elapsed,
+            _ => 0.0,
+        };
+        PacingMetrics {
+            target_messages_per_second: self.pacing.messages_per_second,
+            target_bits_per_second: self.pacing.messages_per_second * bits,
+            achieved_messages_per_second: achieved,
+            achieved_bits_per_second: achieved * bits,
+            burst_messages: self.pacing.burst,
+            max_lag_seconds: self.max_lag.as_secs_f64(),
+            final_lag_seconds: self.lag.as_secs_f64(),
+        }
+    }
+}
+
+/// Blocks for `duration`, spinning through its end for precision
+pub fn sleep(duration: Duration) {
+    let deadline = Instant::now() + duration;
+    loop {
+        let now = Instant::now();
+        if now >= deadline {
+            return;
+        }
+        match deadline - now {
+            left if left > SPIN => thread::sleep(left - SPIN),
+            _ => hint::spin_loop(),
+        }
+    }
+}
+
+/// Prints the achieved rate next to the target, and how far the sender
+/// fell behind its schedule
+pub fn print_pacing(label: &str, pacing: &PacingMetrics) {
+    println!(
+        "{}Pacing: target {} ({:.0} messages/s); achieved {} ({:.0} messages/s); \
+        behind schedule: {} at most, {} at the end",
+        label,
+        throughput_to_string(pacing.target_bits_per_second),
+        pacing.target_messages_per_second,
+        throughput_to_string(pacing.achieved_bits_per_second),
+        pacing.achieved_messages_per_second,
+        duration_to_string(pacing.max_lag_seconds * 1e9),
+        duration_to_string(pacing.final_lag_seconds * 1e9)
+    );
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{sleep, Pacer, Pacing};
+    use std::time::Duration;
+
+    #[test]
+    fn test_pacer() {
+        let pacing = Pacing {
+            messages_per_second: 1000.0,
+            burst: 4,
+        };
+        let mut pacer = Pacer::new(pacing);
+        // The bucket starts full
+        for _ in 0..4 {
+            assert_eq!(pacer.delay(), Duration::ZERO);
+        }
+        // Then each message waits for its token, at most a period
+        let delay = pacer.delay();
+        assert!(delay <= Duration::from_millis(1));
+        sleep(delay);
+        for _ in 0..20 {
+            sleep(pacer.delay());
+        }
+        let metrics = pacer.finish(125);
+        assert_eq!(metrics.target_bits_per_second, 1_000_000.0);
+        // Ahead of the schedule by the burst at best, however loaded the
+        // machine running the test is
+        assert!(metrics.achieved_messages_per_second > 500.0);
+        assert!(metrics.achieved_messages_per_second < 1300.0);
+        assert_eq!(metrics.burst_messages, 4);
+    }
+
+    #[test]
+    fn test_pacer_behind() {
+        let pacing = Pacing {
+            messages_per_second: 1000.0,
+            burst: 1,
+        };
+        let mut pacer = Pacer::new(pacing);
+        pacer.delay();
+        // A sender stalled for 10 periods cannot catch up
+        sleep(Duration::from_millis(10));
+        assert_eq!(pacer.delay(), Duration::ZERO);
+        let metrics = pacer.finish(1);
+        assert!(metrics.final_lag_seconds >= 0.008);
+        assert_eq!(metrics.max_lag_seconds, metrics.final_lag_seconds);
+    }
+}
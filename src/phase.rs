@@ -0,0 +1,90 @@
+use std::time::{Duration, Instant};
+
+/// Phases of a duration-based run. Traffic sent during warm-up and
+/// cool-down is excluded from the reported statistics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Warmup,
+    Measure,
+    Cooldown,
+    Done,
+}
+
+/// Wall-clock schedule of a run: warm-up, then measurement for `duration`,
+/// then cool-down. The clock starts when the schedule is created, unless
+/// it is moved with `starting_at`.
+#[derive(Debug, Clone, Copy)]
+pub struct Phases {
+    start: Instant,
+    warmup: Duration,
+    duration: Duration,
+    cooldown: Duration,
+}
+
+impl Phases {
+    pub fn new(warmup: Duration, duration: Duration, cooldown: Duration) -> Phases {
+        Self {
+            start: Instant::now(),
+            warmup,
+            duration,
+            cooldown,
+        }
+    }
+
+    /// The same schedule, started at `start` instead
+    pub fn starting_at(self, start: Instant) -> Phases {
+        Self { start, ..self }
+    }
+
+    /// Returns the phase the run is in right now
+    pub fn current(&self) -> Phase {
+        self.at(self.start.elapsed())
+    }
+
+    /// Returns the phase the run is in `elapsed` after its start
+    pub fn at(&self, elapsed: Duration) -> Phase {
+        let measured = self.warmup.saturating_add(self.duration);
+        if elapsed < self.warmup {
+            Phase::Warmup
+        } else if elapsed < measured {
+            Phase::Measure
+        } else if elapsed < measured.saturating_add(self.cooldown) {
+            Phase::Cooldown
+        } else {
+            Phase::Done
+        }
+    }
+
+    /// When the measured phase ends, unless it is too far away to tell
+    pub fn measure_end(&self) -> Option<Instant> {
+        self.start
+            .checked_add(self.warmup.saturating_add(self.duration))
+    }
+
+    /// Length of the measured phase
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{Phase, Phases};
+    use std::time::Duration;
+
+    #[test]
+    fn test_phases() {
+        let second = Duration::from_secs(1);
+        let phases = Phases::new(second, 2 * second, second);
+        assert_eq!(phases.at(Duration::ZERO), Phase::Warmup);
+        assert_eq!(phases.at(second), Phase::Measure);
+        assert_eq!(phases.at(2 * second), Phase::Measure);
+        assert_eq!(phases.at(3 * second), Phase::Cooldown);
+        assert_eq!(phases.at(4 * second), Phase::Done);
+
+        let phases = Phases::new(Duration::ZERO, second, Duration::ZERO);
+        assert_eq!(phases.at(Duration::ZERO), Phase::Measure);
+        assert_eq!(phases.at(second), Phase::Done);
+    }
+}
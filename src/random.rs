@@ -0,0 +1,46 @@
+/// SplitMix64: a tiny, fast generator of 64-bit numbers. Not for
+/// cryptography, but the same seed always gives the same sequence, on
+/// either side of a connection.
+#[derive(Debug, Clone)]
+pub struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    pub fn new(seed: u64) -> SplitMix64 {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in [0, 1), with the 53 bits of precision of an f64
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::SplitMix64;
+
+    #[test]
+    fn test_split_mix() {
+        // Reference values of the original implementation
+        let mut random = SplitMix64::new(0);
+        assert_eq!(random.next_u64(), 0xe220_a839_7b1d_cdaf);
+        assert_eq!(random.next_u64(), 0x6e78_9e6a_a1b9_65f4);
+
+        let mut random = SplitMix64::new(42);
+        for _ in 0..1000 {
+            let value = random.next_f64();
+            assert!((0.0..1.0).contains(&value));
+        }
+    }
+}
@@ -0,0 +1,800 @@
+use crate::{
+    config::{Arrivals, Config, Direction, Role, SendMode},
+    error,
+    histogram::Histogram,
+    message::payload::Pattern,
+    unit::{duration_to_string, throughput_to_string},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    fs::File,
+    io::{self, Write},
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// How results are written at the end of a run
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// Human readable lines only
+    #[default]
+    Text,
+    /// One JSON document: metadata, config, per-interval and final metrics
+    Json,
+    /// One row per interval, per stream summary and for the total
+    Csv,
+}
+
+/// Everything measured by one side of a run. All numbers are raw, in the
+/// unit stated by the field name (bytes, seconds, bits per second...).
+#[derive(Debug, Serialize, Clone)]
+pub struct RunResults {
+    pub metadata: Metadata,
+    pub config: Config,
+    pub streams: Vec<StreamResults>,
+    pub total: Metrics,
+    pub cost: CostMetrics,
+}
+
+/// What was run, where and when
+#[derive(Debug, Serialize, Clone)]
+pub struct Metadata {
+    pub tool: String,
+    pub version: String,
+    pub role: Role,
+    pub started_at_unix_seconds: f64,
+    pub finished_at_unix_seconds: f64,
+}
+
+/// Results of one connection (or UDP client)
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct StreamResults {
+    pub peer: String,
+    pub intervals: Vec<Metrics>,
+    pub summary: Metrics,
+    pub latency: Option<LatencyMetrics>,
+    pub datagrams: Option<DatagramMetrics>,
+    pub receiver: Option<ReceiverMetrics>,
+    pub syscalls: Option<SyscallMetrics>,
+    /// Options of a TCP connection, as applied by the kernel
+    pub socket: Option<SocketSettings>,
+    /// Set when comparing send modes
+    pub send_mode: Option<SendMode>,
+    /// Set on reverse and bidirectional runs: which way the messages of
+    /// this stream went
+    pub direction: Option<Direction>,
+    /// What the other side measured for the same messages, when it
+    /// reported it at the end of the run
+    pub peer_summary: Option<Metrics>,
+    /// Set when the sender was paced to a target rate
+    pub pacing: Option<PacingMetrics>,
+    /// Set on open-loop runs, whose `latency` is timed from when each
+    /// request was due
+    pub open_loop: Option<OpenLoopMetrics>,
+    /// Set when the receiver checked bodies against their pattern
+    pub verification: Option<VerificationMetrics>,
+}
+
+/// Transfer over a time span, relative to the start of the stream
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub struct Metrics {
+    pub start_seconds: f64,
+    pub end_seconds: f64,
+    pub bytes: u64,
+    pub messages: u64,
+    pub bits_per_second: f64,
+    pub messages_per_second: f64,
+    pub retransmits: Option<u64>,
+    /// Kernel view of the connection at the end of the span
+    pub tcp_info: Option<TcpInfoMetrics>,
+}
+
+/// A `TCP_INFO` sample of a connection, Linux only. Rates the kernel does
+/// not report are left out.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TcpInfoMetrics {
+    /// Segments retransmitted since the connection was opened
+    pub retransmits: u64,
+    /// Smoothed round-trip time
+    pub rtt_microseconds: u32,
+    pub rtt_variance_microseconds: u32,
+    pub congestion_window_segments: u32,
+    pub pacing_rate_bits_per_second: Option<u64>,
+    pub delivery_rate_bits_per_second: Option<u64>,
+}
+
+/// Round-trip time distribution, in nanoseconds
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Default)]
+pub struct LatencyMetrics {
+    pub samples: u64,
+    pub min_nanoseconds: u64,
+    pub mean_nanoseconds: f64,
+    pub p50_nanoseconds: u64,
+    pub p90_nanoseconds: u64,
+    pub p99_nanoseconds: u64,
+    pub p999_nanoseconds: u64,
+    pub max_nanoseconds: u64,
+}
+
+/// Datagram accounting of a UDP stream
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Default)]
+pub struct DatagramMetrics {
+    pub received: u64,
+    pub lost: u64,
+    pub loss_percentage: f64,
+    pub reordered: u64,
+    pub duplicated: u64,
+    pub jitter_nanoseconds: f64,
+}
+
+/// Bytes received on a stream connection, split between headers and bodies
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Default)]
+pub struct ReceiverMetrics {
+    pub header_bytes: u64,
+    pub body_bytes: u64,
+    pub throughput_bits_per_second: f64,
+    pub goodput_bits_per_second: f64,
+    pub overhead_percentage: f64,
+}
+
+/// Schedule of an open-loop run and how the client kept to it
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Default)]
+pub struct OpenLoopMetrics {
+    pub arrivals: Arrivals,
+    pub requests: u64,
+    pub target_requests_per_second: f64,
+    pub achieved_requests_per_second: f64,
+    /// Requests sent and not answered yet, at most
+    pub max_outstanding: u64,
+    /// Latest a request left after it was due
+    pub max_send_lag_seconds: f64,
+    /// Timed from when each request actually left, which hides the time
+    /// requests spent queued behind slow ones
+    pub uncorrected_latency: LatencyMetrics,
+}
+
+/// Received bodies checked against the pattern they were sent with
+#[derive(Debug, Serialize, Clone, PartialEq, Default)]
+pub struct VerificationMetrics {
+    pub pattern: Pattern,
+    pub messages: u64,
+    pub corrupted_messages: u64,
+    /// Bytes that differ from the pattern, over all corrupted messages
+    pub corrupted_bytes: u64,
+    /// Offset of the first mismatch in each of the first corrupted messages
+    pub mismatch_offsets: Vec<u64>,
+}
+
+/// Rate a paced sender achieved against its target. The sender is behind
+/// its schedule when a message leaves later than the target rate would
+/// have sent it.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Default)]
+pub struct PacingMetrics {
+    pub target_bits_per_second: f64,
+    pub target_messages_per_second: f64,
+    pub achieved_bits_per_second: f64,
+    pub achieved_messages_per_second: f64,
+    pub burst_messages: u32,
+    pub max_lag_seconds: f64,
+    pub final_lag_seconds: f64,
+}
+
+/// I/O system calls made by a connection, for messages sent and received
+/// alike
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Default)]
+pub struct SyscallMetrics {
+    pub syscalls: u64,
+    pub messages: u64,
+    pub syscalls_per_message: f64,
+}
+
+impl fmt::Display for TcpInfoMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "RTT: {} ± {}; Cwnd: {} segments",
+            duration_to_string(self.rtt_microseconds as f64 * 1000.0),
+            duration_to_string(self.rtt_variance_microseconds as f64 * 1000.0),
+            self.congestion_window_segments
+        )?;
+        if let Some(pacing_rate) = self.pacing_rate_bits_per_second {
+            write!(f, "; Pacing:{}", throughput_to_string(pacing_rate as f64))?;
+        }
+        if let Some(delivery_rate) = self.delivery_rate_bits_per_second {
+            write!(
+                f,
+                "; Delivery:{}",
+                throughput_to_string(delivery_rate as f64)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// TCP socket options of a connection, read back from the kernel. Options
+/// the platform does not expose are left out.
+#[derive(Debug, Serialize, Clone, PartialEq, Default)]
+pub struct SocketSettings {
+    pub nodelay: bool,
+    pub send_buffer: usize,
+    pub receive_buffer: usize,
+    pub congestion: Option<String>,
+    pub mss: Option<u32>,
+    pub cork: Option<bool>,
+}
+
+/// What a run cost the whole process
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Default)]
+pub struct CostMetrics {
+    pub elapsed_seconds: f64,
+    pub cpu_seconds: Option<f64>,
+    /// CPU time over elapsed time: 100% is one core busy all along
+    pub cpu_percentage: Option<f64>,
+    /// Sum over the streams that count their system calls
+    pub syscalls: Option<SyscallMetrics>,
+}
+
+impl SyscallMetrics {
+    pub fn new(syscalls: u64, messages: u64) -> SyscallMetrics {
+        let syscalls_per_message = match messages {
+            0 => 0.0,
+            messages => syscalls as f64 / messages as f64,
+        };
+        Self {
+            syscalls,
+            messages,
+            syscalls_per_message,
+        }
+    }
+
+    /// Sums the system calls of several connections
+    pub fn aggregate<'a, I: IntoIterator<Item = &'a SyscallMetrics>>(
+        metrics: I,
+    ) -> Option<SyscallMetrics> {
+        metrics.into_iter().fold(None, |total, metric| {
+            let (syscalls, messages) = total.map_or((0, 0), |total: SyscallMetrics| {
+                (total.syscalls, total.messages)
+            });
+            Some(SyscallMetrics::new(
+                syscalls + metric.syscalls,
+                messages + metric.messages,
+            ))
+        })
+    }
+}
+
+impl Metrics {
+    /// Computes the rates of `bytes` and `messages` transferred between
+    /// `start` and `end`
+    pub fn new(
+        start: Duration,
+        end: Duration,
+        bytes: u64,
+        messages: u64,
+        retransmits: Option<u64>,
+    ) -> Metrics {
+        let elapsed = end.saturating_sub(start).as_secs_f64();
+        let (bits_per_second, messages_per_second) = match elapsed {
+            elapsed if elapsed > 0.0 => (bytes as f64 * 8.0 / elapsed, messages as f64 / elapsed),
+            _ => (0.0, 0.0),
+        };
+        Self {
+            start_seconds: start.as_secs_f64(),
+            end_seconds: end.as_secs_f64(),
+            bytes,
+            messages,
+            bits_per_second,
+            messages_per_second,
+            retransmits,
+            tcp_info: None,
+        }
+    }
+
+    /// Attaches a `TCP_INFO` sample taken at the end of the span.
+    /// Retransmits are then counted from the `previous` sample, or from the
+    /// start of the connection.
+    pub fn with_tcp_info(
+        self,
+        tcp_info: Option<TcpInfoMetrics>,
+        previous: Option<&TcpInfoMetrics>,
+    ) -> Metrics {
+        let retransmits = tcp_info.map(|tcp_info| {
+            let previous = previous.map_or(0, |previous| previous.retransmits);
+            tcp_info.retransmits.saturating_sub(previous)
+        });
+        Metrics {
+            retransmits,
+            tcp_info,
+            ..self
+        }
+    }
+
+    /// Sums the transfers of concurrent streams over their common time span
+    pub fn aggregate<'a, I: IntoIterator<Item = &'a Metrics>>(metrics: I) -> Metrics {
+        let mut start = f64::MAX;
+        let mut end = 0.0f64;
+        let mut bytes = 0;
+        let mut messages = 0;
+        let mut retransmits: Option<u64> = None;
+        for metric in metrics {
+            start = start.min(metric.start_seconds);
+            end = end.max(metric.end_seconds);
+            bytes += metric.bytes;
+            messages += metric.messages;
+            if let Some(metric_retransmits) = metric.retransmits {
+                retransmits = Some(retransmits.unwrap_or_default() + metric_retransmits);
+            }
+        }
+        if start > end {
+            return Metrics::default();
+        }
+        Metrics::new(
+            Duration::from_secs_f64(start),
+            Duration::from_secs_f64(end),
+            bytes,
+            messages,
+            retransmits,
+        )
+    }
+
+    pub fn elapsed_seconds(&self) -> f64 {
+        self.end_seconds - self.start_seconds
+    }
+}
+
+impl From<&Histogram> for LatencyMetrics {
+    fn from(histogram: &Histogram) -> Self {
+        Self {
+            samples: histogram.count(),
+            min_nanoseconds: histogram.min(),
+            mean_nanoseconds: histogram.mean(),
+            p50_nanoseconds: histogram.percentile(50.0),
+            p90_nanoseconds: histogram.percentile(90.0),
+            p99_nanoseconds: histogram.percentile(99.0),
+            p999_nanoseconds: histogram.percentile(99.9),
+            max_nanoseconds: histogram.max(),
+        }
+    }
+}
+
+impl Metadata {
+    /// Describes a run started at `started_at`, finishing now
+    pub fn new(role: Role, started_at: SystemTime) -> Metadata {
+        Self {
+            tool: env!("CARGO_PKG_NAME").to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            role,
+            started_at_unix_seconds: unix_seconds(started_at),
+            finished_at_unix_seconds: unix_seconds(SystemTime::now()),
+        }
+    }
+}
+
+fn unix_seconds(time: SystemTime) -> f64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs_f64())
+        .unwrap_or_default()
+}
+
+impl RunResults {
+    /// Gathers the results of all streams, the total summing them up
+    pub fn new(
+        metadata: Metadata,
+        config: Config,
+        streams: Vec<StreamResults>,
+        cost: CostMetrics,
+    ) -> RunResults {
+        let total = Metrics::aggregate(streams.iter().map(|stream| &stream.summary));
+        Self {
+            metadata,
+            config,
+            streams,
+            total,
+            cost,
+        }
+    }
+
+    /// Writes the results in the given format, to `path` or to the standard
+    /// output. Text results are already printed while running.
+    pub fn write(&self, format: OutputFormat, path: Option<&Path>) -> error::Result<()> {
+        let mut output: Box<dyn Write> = match path {
+            Some(path) => Box::new(File::create(path)?),
+            None => Box::new(io::stdout()),
+        };
+        match format {
+            OutputFormat::Text => (),
+            OutputFormat::Json => {
+                serde_json::to_writer_pretty(&mut output, self)?;
+                writeln!(output)?;
+            }
+            OutputFormat::Csv => self.write_csv(&mut output)?,
+        }
+        output.flush()?;
+        Ok(())
+    }
+
+    /// Writes one row per interval, one per stream summary and one for the
+    /// total. Latency, datagram, pacing, open-loop, verification, socket,
+    /// receiver and system call columns are only filled on summaries, CPU
+    /// columns on the total, TCP_INFO columns wherever the kernel was
+    /// sampled, the direction on reverse and bidirectional runs. Lists and
+    /// the summary of the other side are only in the JSON output.
+    pub fn write_csv<W: Write>(&self, output: &mut W) -> io::Result<()> {
+        writeln!(
+            output,
+            "stream,peer,direction,kind,start_seconds,end_seconds,bytes,messages,bits_per_second,\
+             messages_per_second,retransmits,rtt_microseconds,rtt_variance_microseconds,\
+             congestion_window_segments,pacing_rate_bits_per_second,\
+             delivery_rate_bits_per_second,latency_samples,latency_min_nanoseconds,\
+             latency_mean_nanoseconds,latency_p50_nanoseconds,latency_p90_nanoseconds,\
+             latency_p99_nanoseconds,latency_p999_nanoseconds,latency_max_nanoseconds,\
+             datagrams_received,datagrams_lost,loss_percentage,datagrams_reordered,\
+             datagrams_duplicated,jitter_nanoseconds,send_mode,pacing_target_bits_per_second,\
+             pacing_target_messages_per_second,pacing_achieved_bits_per_second,\
+             pacing_achieved_messages_per_second,pacing_burst_messages,pacing_max_lag_seconds,\
+             pacing_final_lag_seconds,arrivals,requests,target_requests_per_second,\
+             achieved_requests_per_second,max_outstanding,max_send_lag_seconds,\
+             uncorrected_latency_p50_nanoseconds,uncorrected_latency_p99_nanoseconds,\
+             uncorrected_latency_max_nanoseconds,verification_pattern,verified_messages,\
+             corrupted_messages,corrupted_bytes,nodelay,send_buffer,receive_buffer,congestion,\
+             mss,cork,header_bytes,body_bytes,receiver_throughput_bits_per_second,\
+             receiver_goodput_bits_per_second,overhead_percentage,syscalls,\
+             syscalls_per_message,cpu_seconds,cpu_percentage"
+        )?;
+        for (index, stream) in self.streams.iter().enumerate() {
+            let name = index.to_string();
+            for interval in &stream.intervals {
+                let row = Row {
+                    direction: stream.direction,
+                    ..Row::new(&name, &stream.peer, "interval", interval)
+                };
+                row.write(output)?;
+            }
+            let row = Row {
+                latency: stream.latency.as_ref(),
+                datagrams: stream.datagrams.as_ref(),
+                send_mode: stream.send_mode,
+                pacing: stream.pacing.as_ref(),
+                open_loop: stream.open_loop.as_ref(),
+                verification: stream.verification.as_ref(),
+                socket: stream.socket.as_ref(),
+                receiver: stream.receiver.as_ref(),
+                syscalls: stream.syscalls.as_ref(),
+                direction: stream.direction,
+                ..Row::new(&name, &stream.peer, "summary", &stream.summary)
+            };
+            row.write(output)?;
+        }
+        let row = Row {
+            syscalls: self.cost.syscalls.as_ref(),
+            cost: Some(&self.cost),
+            ..Row::new("all", "", "total", &self.total)
+        };
+        row.write(output)
+    }
+}
+
+/// One CSV row: metrics of a time span, and whatever else is known about
+/// it. Unknown columns are left empty.
+struct Row<'a> {
+    stream: &'a str,
+    peer: &'a str,
+    direction: Option<Direction>,
+    kind: &'a str,
+    metrics: &'a Metrics,
+    latency: Option<&'a LatencyMetrics>,
+    datagrams: Option<&'a DatagramMetrics>,
+    send_mode: Option<SendMode>,
+    pacing: Option<&'a PacingMetrics>,
+    open_loop: Option<&'a OpenLoopMetrics>,
+    verification: Option<&'a VerificationMetrics>,
+    socket: Option<&'a SocketSettings>,
+    receiver: Option<&'a ReceiverMetrics>,
+    syscalls: Option<&'a SyscallMetrics>,
+    cost: Option<&'a CostMetrics>,
+}
+
+impl<'a> Row<'a> {
+    fn new(stream: &'a str, peer: &'a str, kind: &'a str, metrics: &'a Metrics) -> Row<'a> {
+        Self {
+            stream,
+            peer,
+            direction: None,
+            kind,
+            metrics,
+            latency: None,
+            datagrams: None,
+            send_mode: None,
+            pacing: None,
+            open_loop: None,
+            verification: None,
+            socket: None,
+            receiver: None,
+            syscalls: None,
+            cost: None,
+        }
+    }
+
+    fn write<W: Write>(&self, output: &mut W) -> io::Result<()> {
+        let metrics = self.metrics;
+        let latency = match self.latency {
+            Some(latency) => format!(
+                "{},{},{},{},{},{},{},{}",
+                latency.samples,
+                latency.min_nanoseconds,
+                latency.mean_nanoseconds,
+                latency.p50_nanoseconds,
+                latency.p90_nanoseconds,
+                latency.p99_nanoseconds,
+                latency.p999_nanoseconds,
+                latency.max_nanoseconds
+            ),
+            None => ",,,,,,,".to_string(),
+        };
+        let datagrams = match self.datagrams {
+            Some(datagrams) => format!(
+                "{},{},{},{},{},{}",
+                datagrams.received,
+                datagrams.lost,
+                datagrams.loss_percentage,
+                datagrams.reordered,
+                datagrams.duplicated,
+                datagrams.jitter_nanoseconds
+            ),
+            None => ",,,,,".to_string(),
+        };
+        let pacing = match self.pacing {
+            Some(pacing) => format!(
+                "{},{},{},{},{},{},{}",
+                pacing.target_bits_per_second,
+                pacing.target_messages_per_second,
+                pacing.achieved_bits_per_second,
+                pacing.achieved_messages_per_second,
+                pacing.burst_messages,
+                pacing.max_lag_seconds,
+                pacing.final_lag_seconds
+            ),
+            None => ",,,,,,".to_string(),
+        };
+        let open_loop = match self.open_loop {
+            Some(open_loop) => format!(
+                "{:?},{},{},{},{},{},{},{},{}",
+                open_loop.arrivals,
+                open_loop.requests,
+                open_loop.target_requests_per_second,
+                open_loop.achieved_requests_per_second,
+                open_loop.max_outstanding,
+                open_loop.max_send_lag_seconds,
+                open_loop.uncorrected_latency.p50_nanoseconds,
+                open_loop.uncorrected_latency.p99_nanoseconds,
+                open_loop.uncorrected_latency.max_nanoseconds
+            ),
+            None => ",,,,,,,,".to_string(),
+        };
+        let verification = match self.verification {
+            Some(verification) => format!(
+                "{:?},{},{},{}",
+                verification.pattern,
+                verification.messages,
+                verification.corrupted_messages,
+                verification.corrupted_bytes
+            ),
+            None => ",,,".to_string(),
+        };
+        let socket = match self.socket {
+            Some(socket) => format!(
+                "{},{},{},{},{},{}",
+                socket.nodelay,
+                socket.send_buffer,
+                socket.receive_buffer,
+                csv_field(socket.congestion.as_deref().unwrap_or_default()),
+                optional(socket.mss),
+                optional(socket.cork)
+            ),
+            None => ",,,,,".to_string(),
+        };
+        let receiver = match self.receiver {
+            Some(receiver) => format!(
+                "{},{},{},{},{}",
+                receiver.header_bytes,
+                receiver.body_bytes,
+                receiver.throughput_bits_per_second,
+                receiver.goodput_bits_per_second,
+                receiver.overhead_percentage
+            ),
+            None => ",,,,".to_string(),
+        };
+        let tcp_info = match metrics.tcp_info {
+            Some(tcp_info) => format!(
+                "{},{},{},{},{}",
+                tcp_info.rtt_microseconds,
+                tcp_info.rtt_variance_microseconds,
+                tcp_info.congestion_window_segments,
+                optional(tcp_info.pacing_rate_bits_per_second),
+                optional(tcp_info.delivery_rate_bits_per_second)
+            ),
+            None => ",,,,".to_string(),
+        };
+        let syscalls = match self.syscalls {
+            Some(syscalls) => format!("{},{}", syscalls.syscalls, syscalls.syscalls_per_message),
+            None => ",".to_string(),
+        };
+        let cost = match self.cost {
+            Some(cost) => format!(
+                "{},{}",
+                optional(cost.cpu_seconds),
+                optional(cost.cpu_percentage)
+            ),
+            None => ",".to_string(),
+        };
+        writeln!(
+            output,
+            "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+            self.stream,
+            csv_field(self.peer),
+            optional(self.direction.map(|direction| format!("{:?}", direction))),
+            self.kind,
+            metrics.start_seconds,
+            metrics.end_seconds,
+            metrics.bytes,
+            metrics.messages,
+            metrics.bits_per_second,
+            metrics.messages_per_second,
+            optional(metrics.retransmits),
+            tcp_info,
+            latency,
+            datagrams,
+            optional(self.send_mode.map(|send_mode| format!("{:?}", send_mode))),
+            pacing,
+            open_loop,
+            verification,
+            socket,
+            receiver,
+            syscalls,
+            cost
+        )
+    }
+}
+
+/// An empty CSV field stands for an unknown value
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+/// Quotes a CSV field when it holds a separator or a quote
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{
+        CostMetrics, Metadata, Metrics, ReceiverMetrics, RunResults, SocketSettings, StreamResults,
+        SyscallMetrics,
+    };
+    use crate::config::{Config, Role};
+    use std::time::{Duration, SystemTime};
+
+    const CONFIG: &str = "
+server: \"127.0.0.1\"
+client: \"127.0.0.1\"
+port: 7777
+message_size: 100
+batch_size: 10
+number_batches: 1
+number_clients: 1
+role: \"Client\"
+";
+
+    #[test]
+    fn test_metrics() {
+        let metrics = Metrics::new(
+            Duration::from_secs(1),
+            Duration::from_secs(3),
+            1000,
+            10,
+            None,
+        );
+        assert_eq!(metrics.bits_per_second, 4000.0);
+        assert_eq!(metrics.messages_per_second, 5.0);
+        assert_eq!(metrics.elapsed_seconds(), 2.0);
+
+        let empty = Metrics::new(Duration::ZERO, Duration::ZERO, 1000, 10, None);
+        assert_eq!(empty.bits_per_second, 0.0);
+    }
+
+    #[test]
+    fn test_aggregate() {
+        let first = Metrics::new(Duration::ZERO, Duration::from_secs(2), 1000, 10, Some(1));
+        let second = Metrics::new(
+            Duration::from_secs(1),
+            Duration::from_secs(4),
+            3000,
+            30,
+            None,
+        );
+        let total = Metrics::aggregate([&first, &second]);
+        assert_eq!(total.start_seconds, 0.0);
+        assert_eq!(total.end_seconds, 4.0);
+        assert_eq!(total.bytes, 4000);
+        assert_eq!(total.messages, 40);
+        assert_eq!(total.bits_per_second, 8000.0);
+        assert_eq!(total.retransmits, Some(1));
+
+        assert_eq!(Metrics::aggregate([]), Metrics::default());
+    }
+
+    #[test]
+    fn test_write() {
+        let config: Config = serde_yaml::from_str(CONFIG).unwrap();
+        let stream = StreamResults {
+            peer: String::from("127.0.0.1:7777"),
+            intervals: vec![
+                Metrics::new(Duration::ZERO, Duration::from_secs(1), 1000, 10, None),
+                Metrics::new(
+                    Duration::from_secs(1),
+                    Duration::from_secs(2),
+                    3000,
+                    30,
+                    None,
+                ),
+            ],
+            summary: Metrics::new(Duration::ZERO, Duration::from_secs(2), 4000, 40, None),
+            socket: Some(SocketSettings {
+                nodelay: true,
+                send_buffer: 4096,
+                receive_buffer: 8192,
+                congestion: Some(String::from("cubic")),
+                ..Default::default()
+            }),
+            receiver: Some(ReceiverMetrics {
+                header_bytes: 100,
+                body_bytes: 4000,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let metadata = Metadata::new(Role::Client, SystemTime::now());
+        let cost = CostMetrics {
+            elapsed_seconds: 2.0,
+            cpu_seconds: Some(1.0),
+            cpu_percentage: Some(50.0),
+            syscalls: SyscallMetrics::aggregate(&[SyscallMetrics::new(80, 40)]),
+        };
+        let results = RunResults::new(metadata, config, vec![stream], cost);
+        assert_eq!(results.total.bits_per_second, 16000.0);
+
+        let json = serde_json::to_value(&results).unwrap();
+        assert_eq!(json["config"]["message_size"], 100);
+        assert_eq!(json["streams"][0]["intervals"][1]["bytes"], 3000);
+        assert_eq!(json["total"]["bits_per_second"], 16000.0);
+
+        let mut csv = Vec::new();
+        results.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let rows: Vec<&str> = csv.lines().collect();
+        // Header, two intervals, the stream summary and the total
+        assert_eq!(rows.len(), 5);
+        for row in &rows {
+            assert_eq!(row.split(',').count(), 66);
+        }
+        let header: Vec<&str> = rows[0].split(',').collect();
+        let summary: Vec<&str> = rows[3].split(',').collect();
+        let column = |name| summary[header.iter().position(|&column| column == name).unwrap()];
+        assert_eq!(column("kind"), "summary");
+        assert_eq!(column("send_buffer"), "4096");
+        assert_eq!(column("congestion"), "cubic");
+        assert_eq!(column("mss"), "");
+        assert_eq!(column("body_bytes"), "4000");
+        assert_eq!(column("pacing_target_bits_per_second"), "");
+        assert!(rows[1].starts_with("0,127.0.0.1:7777,,interval,0,1,1000,10,8000,10,"));
+        assert!(rows[4].starts_with("all,,,total,0,2,4000,40,16000,20,"));
+        assert!(rows[4].ends_with(",80,2,1,50"));
+    }
+}
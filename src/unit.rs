@@ -1,5 +1,5 @@
 fn to_metric(size: f64) -> String {
-    const KILO: f64 = 1000 as f64;
+    const KILO: f64 = 1000_f64;
     const MEGA: f64 = KILO * KILO;
     const GIGA: f64 = KILO * MEGA;
     const TERA: f64 = KILO * GIGA;
@@ -29,6 +29,22 @@ fn to_metric(size: f64) -> String {
     format!("{formatted_size:.2}{unit}")
 }
 
+/// Parses an amount with an optional K, M, G or T suffix, in powers of
+/// 1000 like the printed ones: `100M` is 100 000 000
+pub fn parse_metric(value: &str) -> Result<f64, String> {
+    let (number, multiplier) = match value.char_indices().last() {
+        Some((index, 'k' | 'K')) => (&value[..index], 1e3),
+        Some((index, 'm' | 'M')) => (&value[..index], 1e6),
+        Some((index, 'g' | 'G')) => (&value[..index], 1e9),
+        Some((index, 't' | 'T')) => (&value[..index], 1e12),
+        _ => (value, 1.0),
+    };
+    number
+        .parse::<f64>()
+        .map(|number| number * multiplier)
+        .map_err(|e| e.to_string())
+}
+
 pub fn data_to_string(data_amount: f64) -> String {
     format!(" {}B", to_metric(data_amount))
 }
@@ -36,3 +52,20 @@ pub fn data_to_string(data_amount: f64) -> String {
 pub fn throughput_to_string(throughput: f64) -> String {
     format!(" {}b/s", to_metric(throughput))
 }
+
+/// Formats a duration given in nanoseconds with the most readable unit
+pub fn duration_to_string(nanoseconds: f64) -> String {
+    const MICRO: f64 = 1000_f64;
+    const MILLI: f64 = MICRO * MICRO;
+    const SECOND: f64 = MICRO * MILLI;
+
+    if nanoseconds >= SECOND {
+        format!("{:.2}s", nanoseconds / SECOND)
+    } else if nanoseconds >= MILLI {
+        format!("{:.2}ms", nanoseconds / MILLI)
+    } else if nanoseconds >= MICRO {
+        format!("{:.2}µs", nanoseconds / MICRO)
+    } else {
+        format!("{:.0}ns", nanoseconds)
+    }
+}
@@ -0,0 +1,109 @@
+use crate::results::{CostMetrics, StreamResults, SyscallMetrics};
+use std::time::{Duration, Instant};
+
+/// Measures the CPU time the process spends over a run, to tell how much
+/// an I/O backend costs next to the throughput it reaches
+#[derive(Debug, Clone, Copy)]
+pub struct Usage {
+    start: Instant,
+    cpu_start: Option<Duration>,
+}
+
+impl Usage {
+    pub fn start() -> Usage {
+        Self {
+            start: Instant::now(),
+            cpu_start: process_cpu_time(),
+        }
+    }
+
+    /// Ends the measure; system calls are summed over the `streams` that
+    /// counted them
+    pub fn finish(&self, streams: &[StreamResults]) -> CostMetrics {
+        let elapsed = self.start.elapsed().as_secs_f64();
+        let cpu_seconds = match (self.cpu_start, process_cpu_time()) {
+            (Some(start), Some(end)) => Some(end.saturating_sub(start).as_secs_f64()),
+            _ => None,
+        };
+        let cpu_percentage = match cpu_seconds {
+            Some(cpu_seconds) if elapsed > 0.0 => Some(cpu_seconds * 100.0 / elapsed),
+            _ => None,
+        };
+        let syscalls = streams.iter().filter_map(|stream| stream.syscalls.as_ref());
+        CostMetrics {
+            elapsed_seconds: elapsed,
+            cpu_seconds,
+            cpu_percentage,
+            syscalls: SyscallMetrics::aggregate(syscalls),
+        }
+    }
+}
+
+/// Prints the CPU time and system calls of a run, when known
+pub fn print_cost(cost: &CostMetrics) {
+    if let (Some(cpu_seconds), Some(cpu_percentage)) = (cost.cpu_seconds, cost.cpu_percentage) {
+        println!(
+            "CPU time: {:.3}s ({:.1}% of {:.2}s)",
+            cpu_seconds, cpu_percentage, cost.elapsed_seconds
+        );
+    }
+    if let Some(syscalls) = cost.syscalls {
+        println!(
+            "Syscalls: {} for {} messages ({:.2} per message)",
+            syscalls.syscalls, syscalls.messages, syscalls.syscalls_per_message
+        );
+    }
+}
+
+/// CPU time consumed by all threads of the process so far
+#[cfg(unix)]
+fn process_cpu_time() -> Option<Duration> {
+    let mut time = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // Safety: `time` is a valid timespec for the duration of the call
+    let result = unsafe { libc::clock_gettime(libc::CLOCK_PROCESS_CPUTIME_ID, &mut time) };
+    match result {
+        0 => Some(Duration::new(time.tv_sec as u64, time.tv_nsec as u32)),
+        _ => None,
+    }
+}
+
+#[cfg(not(unix))]
+fn process_cpu_time() -> Option<Duration> {
+    None
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::Usage;
+    use crate::results::{StreamResults, SyscallMetrics};
+
+    #[test]
+    fn test_usage() {
+        let usage = Usage::start();
+        // Burn some CPU
+        let sum: u64 = (0..1_000_000u64).map(|value| value % 7).sum();
+        assert!(sum > 0);
+
+        let streams = [
+            StreamResults {
+                syscalls: Some(SyscallMetrics::new(10, 5)),
+                ..Default::default()
+            },
+            StreamResults::default(),
+            StreamResults {
+                syscalls: Some(SyscallMetrics::new(30, 5)),
+                ..Default::default()
+            },
+        ];
+        let cost = usage.finish(&streams);
+        assert_eq!(cost.syscalls, Some(SyscallMetrics::new(40, 10)));
+        assert_eq!(cost.syscalls.unwrap().syscalls_per_message, 4.0);
+        if cfg!(unix) {
+            assert!(cost.cpu_seconds.unwrap() > 0.0);
+        }
+    }
+}
@@ -0,0 +1,71 @@
+#![cfg(feature = "async")]
+
+mod asynchronous {
+    use benchmark_network::{
+        config::Config,
+        message::{framing::Framing, header::HeaderFormat},
+        network::asynchronous,
+    };
+    use tokio::{net::TcpListener, runtime::Runtime};
+
+    const CONFIG: &str = "
+server: \"127.0.0.1\"
+client: \"127.0.0.1\"
+port: 7777
+message_size: 1000
+batch_size: 10
+number_batches: 2
+number_clients: 1
+role: \"Client\"
+runtime: \"Async\"
+";
+
+    /// Runs the async client against an async server on a free port and
+    /// returns what the server received from each connection
+    fn run(arguments: &[(&str, &str)], connections: usize) -> Vec<u64> {
+        let mut config: Config = serde_yaml::from_str(CONFIG).unwrap();
+        for (variable, value) in arguments {
+            config.parse_attributes(variable, Some(value)).unwrap();
+        }
+
+        let runtime = Runtime::new().unwrap();
+        let listener = runtime
+            .block_on(TcpListener::bind("127.0.0.1:0"))
+            .expect("Unable to listen");
+        let port = listener.local_addr().unwrap().port().to_string();
+        config.parse_attributes("port", Some(&port)).unwrap();
+        let server = runtime.spawn(asynchronous::serve(
+            listener,
+            HeaderFormat::Wire,
+            Framing::default(),
+            Some(connections),
+            None,
+        ));
+
+        let streams = asynchronous::run_client(&config).expect("Client failed");
+        assert_eq!(streams.len(), connections);
+
+        let results = runtime.block_on(server).expect("Server panicked");
+        results
+            .iter()
+            .map(|stream| stream.receiver.unwrap().body_bytes)
+            .collect()
+    }
+
+    #[test]
+    pub fn test_throughput() {
+        assert_eq!(run(&[], 1), vec![2 * 10 * 1000]);
+    }
+
+    #[test]
+    pub fn test_latency() {
+        // Pings are answered, not counted as received data
+        assert_eq!(run(&[("mode", "latency")], 1), vec![0]);
+    }
+
+    #[test]
+    pub fn test_many_connections() {
+        let received = run(&[("parallel", "200"), ("workers", "2")], 200);
+        assert!(received.iter().all(|&bytes| bytes == 2 * 10 * 1000));
+    }
+}
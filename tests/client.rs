@@ -4,7 +4,7 @@ mod client {
     #[ignore = "might run before server"]
     #[test]
     pub fn test() {
-        let config: Config = Config::read_config_file();
-        run_client(config)
+        let config: Config = Config::read_config_file().unwrap();
+        run_client(config).unwrap()
     }
 }
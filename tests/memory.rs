@@ -0,0 +1,242 @@
+mod memory {
+    use benchmark_network::{
+        config::Config,
+        error::Error,
+        message::mtype::Type,
+        network::{
+            server,
+            transport::{memory::MemoryTransport, Connection, Listener, Transport},
+        },
+        run_client_with, run_server_with,
+    };
+    use std::thread;
+
+    const CONFIG: &str = "
+server: \"127.0.0.1\"
+client: \"127.0.0.1\"
+port: 7777
+message_size: 1000
+batch_size: 100
+number_batches: 2
+number_clients: 1
+role: \"Client\"
+";
+
+    fn run(arguments: &[(&str, &str)]) {
+        let mut config: Config = serde_yaml::from_str(CONFIG).unwrap();
+        for (variable, value) in arguments {
+            config.parse_attributes(variable, Some(value)).unwrap();
+        }
+
+        let server_config: Config = serde_yaml::from_str(CONFIG).unwrap();
+        let transport = MemoryTransport::new();
+        let server_transport = transport.clone();
+        let server =
+            thread::spawn(move || run_server_with(&server_transport, &server_config, Some(1)));
+        run_client_with(&transport, &config).expect("Client failed");
+        server
+            .join()
+            .expect("Server panicked")
+            .expect("Server failed");
+    }
+
+    #[test]
+    pub fn test_throughput() {
+        run(&[]);
+    }
+
+    #[test]
+    pub fn test_latency() {
+        run(&[("mode", "latency")]);
+    }
+
+    #[test]
+    pub fn test_duration() {
+        run(&[("duration", "0.2"), ("warmup", "0.1"), ("cooldown", "0.1")]);
+        run(&[("duration", "0.2"), ("mode", "latency")]);
+    }
+
+    #[test]
+    pub fn test_interval() {
+        run(&[("duration", "0.2"), ("interval", "50")]);
+        run(&[("interval", "50")]);
+    }
+
+    #[test]
+    pub fn test_interval_cooldown() {
+        // Cool-down traffic neither shows as intervals nor stretches the summary
+        let path = std::env::temp_dir().join(format!("cooldown-{}.json", std::process::id()));
+        run(&[
+            ("duration", "0.2"),
+            ("cooldown", "0.3"),
+            ("interval", "100"),
+            ("output-format", "json"),
+            ("output-file", path.to_str().unwrap()),
+        ]);
+        let file = std::fs::File::open(&path).expect("Unable to open results");
+        let results: serde_json::Value = serde_json::from_reader(file).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let stream = &results["streams"][0];
+        let summary = &stream["summary"];
+        let elapsed = summary["end_seconds"].as_f64().unwrap();
+        assert!((0.15..=0.2).contains(&elapsed), "{}s", elapsed);
+        let bytes = summary["bytes"].as_f64().unwrap();
+        let bits_per_second = summary["bits_per_second"].as_f64().unwrap();
+        assert!(bytes > 0.0);
+        assert!((bits_per_second - bytes * 8.0 / elapsed).abs() < 1.0);
+        for interval in stream["intervals"].as_array().unwrap() {
+            assert!(interval["end_seconds"].as_f64().unwrap() <= elapsed);
+            assert!(interval["bytes"].as_u64().unwrap() > 0);
+        }
+    }
+
+    #[test]
+    pub fn test_direction() {
+        run(&[("direction", "reverse")]);
+        run(&[("direction", "bidirectional")]);
+        run(&[
+            ("direction", "reverse"),
+            ("duration", "0.2"),
+            ("interval", "50"),
+        ]);
+        run(&[("direction", "bidirectional"), ("duration", "0.2")]);
+    }
+
+    #[test]
+    pub fn test_pacing() {
+        run(&[("message_rate", "2000"), ("burst", "4")]);
+        run(&[("bandwidth", "8M"), ("mode", "latency")]);
+        run(&[
+            ("direction", "reverse"),
+            ("duration", "0.2"),
+            ("message_rate", "1000"),
+        ]);
+    }
+
+    #[test]
+    pub fn test_open_loop() {
+        run(&[("mode", "open_loop"), ("message_rate", "2000")]);
+        run(&[
+            ("mode", "open_loop"),
+            ("arrivals", "poisson"),
+            ("message_rate", "5000"),
+            ("duration", "0.2"),
+            ("warmup", "0.05"),
+        ]);
+
+        // Warm-up and cool-down requests do not make up the achieved rate
+        let path = std::env::temp_dir().join(format!("open-loop-{}.json", std::process::id()));
+        run(&[
+            ("mode", "open_loop"),
+            ("message_rate", "2000"),
+            ("duration", "0.2"),
+            ("warmup", "0.1"),
+            ("cooldown", "0.1"),
+            ("output-format", "json"),
+            ("output-file", path.to_str().unwrap()),
+        ]);
+        let file = std::fs::File::open(&path).expect("Unable to open results");
+        let results: serde_json::Value = serde_json::from_reader(file).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let open_loop = &results["streams"][0]["open_loop"];
+        // Give or take the requests due right on a phase boundary
+        let requests = open_loop["requests"].as_u64().unwrap();
+        assert!((399..=401).contains(&requests), "{} requests", requests);
+        let achieved = open_loop["achieved_requests_per_second"].as_f64().unwrap();
+        assert!(
+            (achieved - 2000.0).abs() <= 5.0,
+            "achieved {} requests/s",
+            achieved
+        );
+    }
+
+    #[test]
+    pub fn test_patterns() {
+        run(&[("pattern", "random"), ("verify", "true")]);
+        run(&[
+            ("pattern", "counter"),
+            ("mode", "latency"),
+            ("verify", "true"),
+        ]);
+        run(&[
+            ("pattern", "random"),
+            ("direction", "bidirectional"),
+            ("duration", "0.2"),
+            ("verify", "true"),
+        ]);
+    }
+
+    #[test]
+    pub fn test_parallel() {
+        let mut config: Config = serde_yaml::from_str(CONFIG).unwrap();
+        config.parse_attributes("parallel", Some("3")).unwrap();
+
+        let transport = MemoryTransport::new();
+        let mut listener = transport.listen().unwrap();
+        let server = thread::spawn(move || {
+            let handles: Vec<_> = (0..3)
+                .map(|_| {
+                    let connection = listener.accept().unwrap();
+                    thread::spawn(move || server::handle_client(connection, None))
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("Connection panicked"))
+                .collect::<Vec<_>>()
+        });
+        run_client_with(&transport, &config).expect("Client failed");
+
+        let stats = server.join().expect("Server panicked");
+        assert_eq!(stats.len(), 3);
+        for stats in stats {
+            assert_eq!(stats.body_bytes(), 2 * 100 * 1000);
+        }
+    }
+
+    #[test]
+    pub fn test_output_file() {
+        let path = std::env::temp_dir().join(format!("benchmark-{}.json", std::process::id()));
+        let path_string = path.to_str().unwrap();
+        run(&[("output-format", "json"), ("output-file", path_string)]);
+
+        let file = std::fs::File::open(&path).expect("Unable to open results");
+        let results: serde_json::Value = serde_json::from_reader(file).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(results["metadata"]["role"], "Client");
+        assert_eq!(
+            results["streams"][0]["intervals"].as_array().unwrap().len(),
+            2
+        );
+        assert_eq!(results["total"]["bytes"], 2 * 100 * 1000);
+    }
+
+    #[test]
+    pub fn test_protocol_error() {
+        let mut config: Config = serde_yaml::from_str(CONFIG).unwrap();
+        config.parse_attributes("mode", Some("latency")).unwrap();
+
+        // Answers the first ping with something else than an acknowledgement
+        let transport = MemoryTransport::new();
+        let mut listener = transport.listen().unwrap();
+        let server = thread::spawn(move || {
+            let mut connection = listener.accept().unwrap();
+            let ping = connection.receive().unwrap();
+            connection.send(&ping.reply(Type::Benchmark)).unwrap();
+        });
+        let result = run_client_with(&transport, &config);
+        assert!(matches!(result, Err(Error::Protocol(_))));
+        server.join().expect("Server panicked");
+    }
+
+    #[test]
+    pub fn test_invalid_config() {
+        // Library callers are validated too, instead of panicking later
+        for (variable, value) in [("duration", "1e30"), ("message_rate", "1e-300")] {
+            let mut config: Config = serde_yaml::from_str(CONFIG).unwrap();
+            config.parse_attributes(variable, Some(value)).unwrap();
+            let result = run_client_with(&MemoryTransport::new(), &config);
+            assert!(matches!(result, Err(Error::Config(_))));
+        }
+    }
+}
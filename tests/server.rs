@@ -4,8 +4,8 @@ mod server {
     #[test]
     #[ignore = "runs indefinitely"]
     pub fn test() {
-        let config: Config = Config::read_config_file();
-        let number_of_clients = config.number_clients();
-        run_server(config, Some(number_of_clients));
+        let config: Config = Config::read_config_file().unwrap();
+        let is_limited = config.clients_limit();
+        run_server(config, is_limited).unwrap();
     }
 }
@@ -0,0 +1,79 @@
+#![cfg(all(target_os = "linux", feature = "uring"))]
+
+mod uring {
+    use benchmark_network::{
+        message::{header::HeaderFormat, message::Message, mtype::Type},
+        network::{
+            server,
+            transport::{uring::UringTransport, Connection, Listener, Transport},
+        },
+    };
+    use io_uring::IoUring;
+    use std::{
+        net::{IpAddr, Ipv4Addr},
+        thread,
+    };
+
+    const LOCALHOST: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+    const BODY_SIZE: usize = 1000;
+    const MESSAGES: u64 = 200;
+
+    /// io_uring may be disabled by the kernel or a seccomp policy
+    fn is_supported() -> bool {
+        match IoUring::new(2) {
+            Ok(_) => true,
+            Err(e) => {
+                println!("Skipping, io_uring is unavailable: {}", e);
+                false
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_send_receive() {
+        if !is_supported() {
+            return;
+        }
+        let listener = UringTransport::new(LOCALHOST, 0, HeaderFormat::Wire)
+            .listen()
+            .expect("Unable to listen");
+        let port = listener
+            .local()
+            .rsplit(':')
+            .next()
+            .unwrap()
+            .parse()
+            .unwrap();
+        let server = thread::spawn(move || server::serve(listener, 1, Some(1), None));
+
+        let transport = UringTransport::new(LOCALHOST, port, HeaderFormat::Wire);
+        let mut connection = transport.connect().expect("Unable to connect");
+        let msg = Message::new(LOCALHOST, LOCALHOST, BODY_SIZE, Type::Benchmark);
+        for _ in 0..MESSAGES {
+            assert_eq!(connection.send(&msg).unwrap(), BODY_SIZE);
+        }
+        let ping = Message::new(LOCALHOST, LOCALHOST, BODY_SIZE, Type::Ping);
+        connection.send(&ping).unwrap();
+        let reply = connection.receive().unwrap();
+        assert_eq!(reply.mtype(), Type::Acknoweldge);
+        assert_eq!(reply.id(), ping.id());
+        let close = Message::new(LOCALHOST, LOCALHOST, 0, Type::Close);
+        assert_eq!(connection.send(&close).unwrap(), 0);
+
+        // Header and body go out in a single system call
+        let syscalls = connection.syscalls().unwrap();
+        assert_eq!(syscalls.messages, MESSAGES + 3);
+        assert_eq!(syscalls.syscalls, MESSAGES + 3);
+
+        let streams = server
+            .join()
+            .expect("Server panicked")
+            .expect("Server failed");
+        let receiver = streams[0].receiver.unwrap();
+        // The ping is answered, not counted as received data
+        assert_eq!(receiver.body_bytes, MESSAGES * BODY_SIZE as u64);
+        // Reads gather several messages at once
+        let syscalls = streams[0].syscalls.unwrap();
+        assert!(syscalls.syscalls < syscalls.messages);
+    }
+}